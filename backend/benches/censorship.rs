@@ -0,0 +1,118 @@
+//! Compares censoring a room's whole history with the precompiled ban matcher
+//! against rescanning the message once per banned word.
+//!
+//! Run with `cargo bench --bench censorship`.
+
+use babel::data::{CENSORSHIP_REPLACEMENT, FilterConfig};
+use babel::filter::CensorshipFilter;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::sync::Arc;
+
+const COUNTRIES: [&str; 4] = ["A", "B", "C", "D"];
+
+fn make_config(bans_per_country: usize) -> FilterConfig {
+    let banned_words = COUNTRIES
+        .iter()
+        .map(|country| {
+            let words = (0..bans_per_country)
+                .map(|i| format!("{}banned{}", country.to_lowercase(), i))
+                .collect();
+            (country.to_string(), words)
+        })
+        .collect();
+    FilterConfig {
+        banned_words,
+        ..Default::default()
+    }
+}
+
+/// Builds a history of short symbol messages, roughly one in four containing a banned word.
+fn make_history(len: usize, bans_per_country: usize) -> Vec<(String, String)> {
+    (0..len)
+        .map(|i| {
+            let country = COUNTRIES[i % COUNTRIES.len()];
+            let content = if i % 4 == 0 {
+                format!(
+                    "you say {}banned{} not me",
+                    country.to_lowercase(),
+                    i % bans_per_country
+                )
+            } else {
+                "hello you what is power and change".to_string()
+            };
+            (country.to_string(), content)
+        })
+        .collect()
+}
+
+/// The approach the matcher replaced: every banned word lowercases and rescans the message.
+fn rescan_censor(content: &str, banned: &[&String]) -> (String, bool) {
+    let mut tokens: Vec<String> = content.split_whitespace().map(str::to_string).collect();
+    let mut was_censored = false;
+    for word in banned {
+        let word = word.to_lowercase();
+        if content.to_lowercase().split_whitespace().any(|t| t == word) {
+            for token in tokens.iter_mut().filter(|t| t.to_lowercase() == word) {
+                *token = CENSORSHIP_REPLACEMENT.to_string();
+                was_censored = true;
+            }
+        }
+    }
+    (tokens.join(" "), was_censored)
+}
+
+fn bench_censor_history(c: &mut Criterion) {
+    let mut group = c.benchmark_group("censor_history");
+    let viewer = "B".to_string();
+
+    for bans_per_country in [8, 256] {
+        for history_len in [1_000, 5_000] {
+            let config = Arc::new(make_config(bans_per_country));
+            let history = make_history(history_len, bans_per_country);
+            let filter = CensorshipFilter::new(Arc::clone(&config));
+            let parameter = format!("{history_len}msgs/{bans_per_country}bans");
+
+            group.bench_with_input(
+                BenchmarkId::new("compiled", &parameter),
+                &history,
+                |b, history| {
+                    b.iter(|| {
+                        for (sender, content) in history {
+                            black_box(filter.censor_message(content, Some(sender), Some(&viewer)));
+                        }
+                    })
+                },
+            );
+
+            let empty = Vec::new();
+            group.bench_with_input(
+                BenchmarkId::new("rescan", &parameter),
+                &history,
+                |b, history| {
+                    b.iter(|| {
+                        for (sender, content) in history {
+                            let banned: Vec<&String> = [sender, &viewer]
+                                .into_iter()
+                                .flat_map(|c| config.banned_words.get(c).unwrap_or(&empty))
+                                .collect();
+                            black_box(rescan_censor(content, &banned));
+                        }
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_compile(c: &mut Criterion) {
+    let config = Arc::new(make_config(256));
+    c.bench_function("compile_ban_lists/256bans", |b| {
+        b.iter(|| black_box(CensorshipFilter::new(Arc::clone(&config))))
+    });
+}
+
+criterion_group!(benches, bench_censor_history, bench_compile);
+criterion_main!(benches);
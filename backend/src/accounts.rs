@@ -0,0 +1,687 @@
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use rand::RngCore;
+use rand::distr::{Alphanumeric, SampleString};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use tracing::warn;
+
+use crate::data::*;
+use crate::store::{AccountStore, StoreError};
+use crate::tokens::{SessionId, TokenClaims, TokenError, TokenSigner};
+use crate::utils;
+
+/// Environment variable setting how many seconds session tokens last.
+pub const TOKEN_TTL_ENV: &str = "BABEL_TOKEN_TTL";
+
+/// Seconds a session token lasts when `TOKEN_TTL_ENV` is unset; refreshing
+/// a token gives it this long again.
+pub const DEFAULT_TOKEN_TTL: u64 = 24 * 60 * 60;
+
+/// Environment variable naming a JSON file of development accounts to load,
+/// mapping each fixed token to a user id and country.
+pub const FIXTURES_ENV: &str = "BABEL_USER_FIXTURES";
+
+pub const MAX_USERNAME_LEN: usize = 32;
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Hash checked against when logging in to an account without a password.
+static DUMMY_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password("no account has this").expect("a fixed password hashes"));
+
+/// Names players cannot take because the server uses them.
+const RESERVED_USERNAMES: &[&str] = &["SYSTEM"];
+
+pub type Token = String;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    /// Has a password and is saved in the store.
+    Registered,
+    /// Holds its name only while it has a live session.
+    Guest,
+    /// Loaded from a fixture file for development; never saved.
+    Fixture,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    pub user_id: UserId,
+    pub kind: AccountKind,
+    /// Argon2 hash in PHC string format; only registered accounts have one.
+    pub password_hash: Option<String>,
+    pub created_at: Timestamp,
+    /// Tokens issued before this time cannot be refreshed.
+    #[serde(default)]
+    pub revoked_before: Timestamp,
+    /// For guests, when the last token issued to them expires.
+    #[serde(default)]
+    pub held_until: Option<Timestamp>,
+}
+
+/// Who a token belongs to, and until when.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub user_id: UserId,
+    pub country: CountryCode,
+    /// `None` for fixture tokens, which never expire.
+    pub expires_at: Option<Timestamp>,
+}
+
+/// A token handed to a client at login or refresh.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IssuedToken {
+    pub token: Token,
+    pub expires_at: Timestamp,
+}
+
+/// Why a registration or login was refused.
+#[derive(Debug)]
+pub enum AccountError {
+    /// The name is empty, too long, reserved or has unsupported characters.
+    InvalidUsername,
+    /// The password is shorter than `MIN_PASSWORD_LEN`.
+    WeakPassword,
+    /// Another account, registered or guest, has this name.
+    UsernameTaken,
+    /// No registered account matches the name and password.
+    InvalidCredentials,
+    /// The token is forged, malformed or expired.
+    InvalidToken(TokenError),
+    /// The token's session was logged out or its account's tokens revoked.
+    SessionEnded,
+    /// The password could not be hashed.
+    Hashing(argon2::password_hash::Error),
+    /// The account could not be saved.
+    Store(StoreError),
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUsername => write!(
+                f,
+                "usernames are 1 to {MAX_USERNAME_LEN} letters, digits, '_' or '-'"
+            ),
+            Self::WeakPassword => {
+                write!(f, "passwords need at least {MIN_PASSWORD_LEN} characters")
+            }
+            Self::UsernameTaken => write!(f, "this username is taken"),
+            Self::InvalidCredentials => write!(f, "wrong username or password"),
+            Self::InvalidToken(err) => err.fmt(f),
+            Self::SessionEnded => write!(f, "the session has ended"),
+            Self::Hashing(err) => write!(f, "cannot hash password: {err}"),
+            Self::Store(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+impl From<StoreError> for AccountError {
+    fn from(err: StoreError) -> Self {
+        Self::Store(err)
+    }
+}
+
+/// One entry of a fixture file.
+#[derive(Deserialize)]
+struct FixtureToken {
+    user_id: UserId,
+    country: CountryCode,
+}
+
+/// Player accounts, and the session tokens issued to them.
+///
+/// Tokens are signed and carry everything needed to check them, apart from
+/// whether their session has ended. Checking a token looks that up in memory,
+/// so logging out and revoking take effect at once. Revocations are saved with
+/// the account, but logged out sessions are only held in memory: after a
+/// restart, a token whose session was logged out is accepted again until it
+/// expires, up to `token_ttl` seconds after it was issued.
+pub struct Accounts {
+    accounts: DashMap<UserId, Account>,
+    signer: TokenSigner,
+    /// Sessions ended by logging out, with the expiry of their last token.
+    ended_sessions: DashMap<SessionId, Timestamp>,
+    /// Fixed development tokens loaded from a fixture file.
+    fixtures: HashMap<Token, Session>,
+    store: Arc<dyn AccountStore>,
+    /// Seconds a new token lasts.
+    token_ttl: u64,
+}
+
+impl Accounts {
+    /// Load the registered accounts saved in `store`. Tokens are signed with
+    /// `signer`.
+    pub fn load(
+        store: Arc<dyn AccountStore>,
+        signer: TokenSigner,
+        token_ttl: u64,
+    ) -> Result<Self, StoreError> {
+        let accounts = store
+            .load_accounts()?
+            .into_iter()
+            .map(|account| (account.user_id.clone(), account))
+            .collect();
+        Ok(Self {
+            accounts,
+            signer,
+            ended_sessions: DashMap::new(),
+            fixtures: HashMap::new(),
+            store,
+            token_ttl,
+        })
+    }
+
+    /// Add the development accounts in a fixture file, each logged in with
+    /// the token it is listed under. Names of registered accounts are skipped.
+    /// Returns the number of accounts added.
+    pub fn load_fixtures(&mut self, path: impl AsRef<Path>) -> usize {
+        let fixtures: HashMap<Token, FixtureToken> = utils::deserialize_from_file(path);
+        let mut loaded = 0;
+        for (token, fixture) in fixtures {
+            match self.accounts.entry(fixture.user_id.clone()) {
+                Entry::Occupied(entry) if entry.get().kind != AccountKind::Fixture => {
+                    warn!(user_id = %fixture.user_id, "Skipping fixture account: name taken");
+                    continue;
+                }
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert(Account {
+                        user_id: fixture.user_id.clone(),
+                        kind: AccountKind::Fixture,
+                        password_hash: None,
+                        created_at: utils::now(),
+                        revoked_before: 0,
+                        held_until: None,
+                    });
+                    loaded += 1;
+                }
+            }
+            self.fixtures.insert(
+                token,
+                Session {
+                    user_id: fixture.user_id,
+                    country: fixture.country,
+                    expires_at: None,
+                },
+            );
+        }
+        loaded
+    }
+
+    /// Create a registered account. Hashing the password is slow, so call
+    /// this off the async runtime's workers.
+    pub fn register(&self, username: &str, password: &str) -> Result<(), AccountError> {
+        validate_username(username)?;
+        if password.chars().count() < MIN_PASSWORD_LEN {
+            return Err(AccountError::WeakPassword);
+        }
+        // Hash before taking the entry, so a slow hash does not hold the map
+        let password_hash = hash_password(password)?;
+        let now = utils::now();
+        self.release_idle_guest(username, now);
+
+        match self.accounts.entry(username.to_string()) {
+            Entry::Occupied(_) => Err(AccountError::UsernameTaken),
+            Entry::Vacant(entry) => {
+                let account = Account {
+                    user_id: username.to_string(),
+                    kind: AccountKind::Registered,
+                    password_hash: Some(password_hash),
+                    created_at: now,
+                    revoked_before: 0,
+                    held_until: None,
+                };
+                self.store.save_account(&account)?;
+                entry.insert(account);
+                Ok(())
+            }
+        }
+    }
+
+    /// Log in to a registered account. Checking the password is slow, so
+    /// call this off the async runtime's workers.
+    pub fn login(
+        &self,
+        username: &str,
+        password: &str,
+        country: CountryCode,
+    ) -> Result<IssuedToken, AccountError> {
+        // Copy the hash out so the slow check does not hold the map
+        let hash = self
+            .accounts
+            .get(username)
+            .and_then(|account| account.password_hash.clone());
+        let verified = match hash {
+            Some(hash) => verify_password(password, &hash),
+            // Take as long for unknown names, so timing does not tell which exist
+            None => {
+                verify_password(password, &DUMMY_HASH);
+                false
+            }
+        };
+        if !verified {
+            return Err(AccountError::InvalidCredentials);
+        }
+        let session_id = Alphanumeric.sample_string(&mut rand::rng(), 16);
+        Ok(self.issue(username.to_string(), country, session_id, utils::now()))
+    }
+
+    /// Log in as a guest, holding the name until the guest's last token expires.
+    pub fn login_guest(
+        &self,
+        username: &str,
+        country: CountryCode,
+    ) -> Result<IssuedToken, AccountError> {
+        validate_username(username)?;
+        let now = utils::now();
+        self.release_idle_guest(username, now);
+
+        match self.accounts.entry(username.to_string()) {
+            Entry::Occupied(_) => Err(AccountError::UsernameTaken),
+            Entry::Vacant(entry) => {
+                let session_id = Alphanumeric.sample_string(&mut rand::rng(), 16);
+                let issued = self.issue(username.to_string(), country, session_id, now);
+                entry.insert(Account {
+                    user_id: username.to_string(),
+                    kind: AccountKind::Guest,
+                    password_hash: None,
+                    created_at: now,
+                    revoked_before: 0,
+                    held_until: Some(issued.expires_at),
+                });
+                Ok(issued)
+            }
+        }
+    }
+
+    /// The session of a validly signed, unexpired token whose session has not
+    /// ended, or of a fixture token.
+    pub fn authenticate(&self, token: &str) -> Option<Session> {
+        if let Some(session) = self.fixtures.get(token) {
+            return Some(session.clone());
+        }
+        let claims = self.signer.verify(token, utils::now()).ok()?;
+        self.check_session(&claims).ok()?;
+        Some(Session {
+            user_id: claims.sub,
+            country: claims.country,
+            expires_at: Some(claims.exp),
+        })
+    }
+
+    /// Exchange a live token for a new one of the same session with a later
+    /// expiry. Refused once the session has ended or the account's tokens
+    /// have been revoked.
+    pub fn refresh(&self, token: &str) -> Result<IssuedToken, AccountError> {
+        let now = utils::now();
+        let claims = self
+            .signer
+            .verify(token, now)
+            .map_err(AccountError::InvalidToken)?;
+        self.check_session(&claims)?;
+        let mut account = self
+            .accounts
+            .get_mut(&claims.sub)
+            .ok_or(AccountError::SessionEnded)?;
+
+        let issued = self.issue(claims.sub, claims.country, claims.sid, now);
+        if account.kind == AccountKind::Guest {
+            account.held_until = Some(issued.expires_at);
+        }
+        Ok(issued)
+    }
+
+    /// End the session of a token, so none of its tokens are accepted.
+    pub fn logout(&self, token: &str) -> Result<(), AccountError> {
+        let now = utils::now();
+        let claims = self
+            .signer
+            .verify(token, now)
+            .map_err(AccountError::InvalidToken)?;
+        self.ended_sessions
+            .retain(|_, expires_at| now < *expires_at);
+        self.ended_sessions.insert(claims.sid, claims.exp);
+        Ok(())
+    }
+
+    /// Revoke every token issued to a user so far; none are accepted.
+    /// Saves the account, so call it off the async workers.
+    pub fn revoke_all(&self, user_id: &UserId) -> Result<(), AccountError> {
+        let account = {
+            let Some(mut account) = self.accounts.get_mut(user_id) else {
+                return Ok(());
+            };
+            // Tokens issued within the current second are revoked too
+            account.revoked_before = utils::now() + 1;
+            account.clone()
+        };
+        // Saved once the map is unlocked, so other logins do not wait on it
+        if account.kind == AccountKind::Registered {
+            self.store.save_account(&account)?;
+        }
+        Ok(())
+    }
+
+    /// Fails when the token's session was logged out, its account is gone or
+    /// the account's tokens were revoked after it was issued.
+    fn check_session(&self, claims: &TokenClaims) -> Result<(), AccountError> {
+        if self.ended_sessions.contains_key(&claims.sid) {
+            return Err(AccountError::SessionEnded);
+        }
+        let revoked = self
+            .accounts
+            .get(&claims.sub)
+            .is_none_or(|account| claims.iat < account.revoked_before);
+        if revoked {
+            return Err(AccountError::SessionEnded);
+        }
+        Ok(())
+    }
+
+    fn issue(
+        &self,
+        user_id: UserId,
+        country: CountryCode,
+        session_id: SessionId,
+        now: Timestamp,
+    ) -> IssuedToken {
+        let expires_at = now + self.token_ttl;
+        let token = self.signer.sign(&TokenClaims {
+            sub: user_id,
+            country,
+            sid: session_id,
+            iat: now,
+            exp: expires_at,
+        });
+        IssuedToken { token, expires_at }
+    }
+
+    /// Free a guest's name once its last token has expired.
+    fn release_idle_guest(&self, user_id: &str, now: Timestamp) {
+        self.accounts.remove_if(user_id, |_, account| {
+            account.kind == AccountKind::Guest
+                && account.held_until.is_none_or(|until| now >= until)
+        });
+    }
+}
+
+fn validate_username(username: &str) -> Result<(), AccountError> {
+    let valid = !username.is_empty()
+        && username.len() <= MAX_USERNAME_LEN
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !RESERVED_USERNAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(username));
+    if valid {
+        Ok(())
+    } else {
+        Err(AccountError::InvalidUsername)
+    }
+}
+
+fn hash_password(password: &str) -> Result<String, AccountError> {
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).map_err(AccountError::Hashing)?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(AccountError::Hashing)?;
+    Ok(hash.to_string())
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryRoomStore;
+
+    fn make_signer() -> TokenSigner {
+        TokenSigner::new("k".repeat(crate::tokens::MIN_KEY_LEN)).unwrap()
+    }
+
+    fn make_accounts(store: Arc<dyn AccountStore>) -> Accounts {
+        Accounts::load(store, make_signer(), 60).unwrap()
+    }
+
+    #[test]
+    fn test_register_and_login() {
+        let accounts = make_accounts(Arc::new(MemoryRoomStore::default()));
+        accounts.register("alice", "correct horse").unwrap();
+
+        assert!(matches!(
+            accounts.register("alice", "another password"),
+            Err(AccountError::UsernameTaken)
+        ));
+        assert!(matches!(
+            accounts.login("alice", "wrong password", "A".to_string()),
+            Err(AccountError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            accounts.login("nobody", "correct horse", "A".to_string()),
+            Err(AccountError::InvalidCredentials)
+        ));
+
+        let issued = accounts
+            .login("alice", "correct horse", "A".to_string())
+            .unwrap();
+        let session = accounts.authenticate(&issued.token).unwrap();
+        assert_eq!(session.user_id, "alice");
+        assert_eq!(session.country, "A");
+        assert!(session.expires_at.is_some());
+    }
+
+    #[test]
+    fn test_passwords_are_hashed_and_saved() {
+        let store: Arc<dyn AccountStore> = Arc::new(MemoryRoomStore::default());
+        make_accounts(Arc::clone(&store))
+            .register("alice", "correct horse")
+            .unwrap();
+
+        let saved = store.load_accounts().unwrap();
+        assert_eq!(saved.len(), 1);
+        let hash = saved[0].password_hash.as_deref().unwrap();
+        assert!(!hash.contains("correct horse"));
+
+        // Accounts survive a restart, and so do tokens signed with the same key
+        let issued = make_accounts(Arc::clone(&store))
+            .login("alice", "correct horse", "B".to_string())
+            .unwrap();
+        let reloaded = make_accounts(store);
+        assert_eq!(
+            reloaded.authenticate(&issued.token).unwrap().user_id,
+            "alice"
+        );
+    }
+
+    #[test]
+    fn test_registration_rules() {
+        let accounts = make_accounts(Arc::new(MemoryRoomStore::default()));
+        for name in ["", "SYSTEM", "system", "has space", &"x".repeat(33)] {
+            assert!(
+                matches!(
+                    accounts.register(name, "long enough"),
+                    Err(AccountError::InvalidUsername)
+                ),
+                "{name:?}"
+            );
+        }
+        assert!(matches!(
+            accounts.register("alice", "short"),
+            Err(AccountError::WeakPassword)
+        ));
+    }
+
+    #[test]
+    fn test_guest_holds_name_until_token_expires() {
+        let accounts = make_accounts(Arc::new(MemoryRoomStore::default()));
+        let issued = utils::at_time(1000, || {
+            accounts.login_guest("alice", "A".to_string()).unwrap()
+        });
+        assert_eq!(issued.expires_at, 1060);
+
+        utils::at_time(1030, || {
+            assert!(matches!(
+                accounts.login_guest("alice", "B".to_string()),
+                Err(AccountError::UsernameTaken)
+            ));
+            assert!(matches!(
+                accounts.register("alice", "correct horse"),
+                Err(AccountError::UsernameTaken)
+            ));
+        });
+
+        // Refreshing keeps holding the name
+        let refreshed = utils::at_time(1050, || accounts.refresh(&issued.token).unwrap());
+        assert_eq!(refreshed.expires_at, 1110);
+        utils::at_time(1100, || {
+            assert!(accounts.authenticate(&issued.token).is_none());
+            assert!(accounts.authenticate(&refreshed.token).is_some());
+            assert!(accounts.login_guest("alice", "B".to_string()).is_err());
+        });
+
+        assert!(utils::at_time(1110, || accounts.login_guest("alice", "B".to_string())).is_ok());
+    }
+
+    #[test]
+    fn test_guest_cannot_take_registered_name() {
+        let accounts = make_accounts(Arc::new(MemoryRoomStore::default()));
+        accounts.register("alice", "correct horse").unwrap();
+        assert!(matches!(
+            accounts.login_guest("alice", "A".to_string()),
+            Err(AccountError::UsernameTaken)
+        ));
+    }
+
+    #[test]
+    fn test_tokens_are_checked_by_signature() {
+        let accounts = make_accounts(Arc::new(MemoryRoomStore::default()));
+        let issued = utils::at_time(1000, || {
+            accounts.login_guest("alice", "A".to_string()).unwrap()
+        });
+        assert!(utils::at_time(1059, || accounts.authenticate(&issued.token)).is_some());
+        assert!(utils::at_time(1060, || accounts.authenticate(&issued.token)).is_none());
+
+        // Tokens signed with another key are refused
+        let other = Accounts::load(
+            Arc::new(MemoryRoomStore::default()),
+            TokenSigner::random(),
+            60,
+        )
+        .unwrap();
+        let foreign = utils::at_time(1000, || {
+            other.login_guest("mallory", "A".to_string()).unwrap()
+        });
+        assert!(utils::at_time(1010, || accounts.authenticate(&foreign.token)).is_none());
+        assert!(matches!(
+            utils::at_time(1010, || accounts.refresh(&foreign.token)),
+            Err(AccountError::InvalidToken(TokenError::BadSignature))
+        ));
+    }
+
+    #[test]
+    fn test_logout_ends_session() {
+        let accounts = make_accounts(Arc::new(MemoryRoomStore::default()));
+        accounts.register("alice", "correct horse").unwrap();
+        let (first, second) = utils::at_time(1000, || {
+            let first = accounts
+                .login("alice", "correct horse", "A".to_string())
+                .unwrap();
+            let second = accounts
+                .login("alice", "correct horse", "A".to_string())
+                .unwrap();
+            (first, second)
+        });
+
+        utils::at_time(1010, || {
+            accounts.logout(&first.token).unwrap();
+            assert!(accounts.authenticate(&first.token).is_none());
+            assert!(matches!(
+                accounts.refresh(&first.token),
+                Err(AccountError::SessionEnded)
+            ));
+            // Other sessions of the account are unaffected
+            assert!(accounts.authenticate(&second.token).is_some());
+            assert!(accounts.refresh(&second.token).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_revoke_all_ends_sessions() {
+        let store: Arc<dyn AccountStore> = Arc::new(MemoryRoomStore::default());
+        let accounts = make_accounts(Arc::clone(&store));
+        accounts.register("alice", "correct horse").unwrap();
+        let issued = utils::at_time(1000, || {
+            accounts
+                .login("alice", "correct horse", "A".to_string())
+                .unwrap()
+        });
+        let other = utils::at_time(1000, || {
+            accounts.login_guest("bob", "B".to_string()).unwrap()
+        });
+
+        utils::at_time(1010, || {
+            accounts.revoke_all(&"alice".to_string()).unwrap();
+            assert!(accounts.authenticate(&issued.token).is_none());
+            assert!(matches!(
+                accounts.refresh(&issued.token),
+                Err(AccountError::SessionEnded)
+            ));
+            assert!(accounts.refresh(&other.token).is_ok());
+        });
+
+        // The revocation is saved with the account
+        let reloaded = make_accounts(store);
+        assert!(utils::at_time(1020, || reloaded.authenticate(&issued.token)).is_none());
+        assert!(matches!(
+            utils::at_time(1020, || reloaded.refresh(&issued.token)),
+            Err(AccountError::SessionEnded)
+        ));
+        let fresh = utils::at_time(1020, || {
+            reloaded
+                .login("alice", "correct horse", "A".to_string())
+                .unwrap()
+        });
+        assert!(utils::at_time(1030, || reloaded.refresh(&fresh.token)).is_ok());
+    }
+
+    #[test]
+    fn test_load_fixtures() {
+        let path = std::env::temp_dir().join(format!("babel_fixtures_{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{
+                "token-alice": {"user_id": "alice", "country": "A"},
+                "token-bob": {"user_id": "bob", "country": "B"}
+            }"#,
+        )
+        .unwrap();
+
+        let mut accounts = make_accounts(Arc::new(MemoryRoomStore::default()));
+        accounts.register("bob", "correct horse").unwrap();
+        assert_eq!(accounts.load_fixtures(&path), 1);
+        std::fs::remove_file(&path).unwrap();
+
+        let session = accounts.authenticate("token-alice").unwrap();
+        assert_eq!(session.user_id, "alice");
+        assert_eq!(session.expires_at, None);
+        assert!(accounts.authenticate("token-bob").is_none());
+    }
+}
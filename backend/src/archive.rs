@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::ToSchema;
+
+use crate::data::*;
+use crate::events::LogEntry;
+use crate::packs::PackId;
+
+pub type GameId = u64;
+
+/// What is known about a finished game without reading its messages.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct GameSummary {
+    /// Assigned by the store when the game is archived.
+    pub id: GameId,
+    pub room_id: RoomId,
+    pub pack: PackId,
+    pub settings: RoomSettings,
+    /// Everyone who played, with the country they played for.
+    pub players: Vec<Participant>,
+    /// Countries the game was generated with.
+    pub countries: Vec<CountryCode>,
+    /// Banned words by country code and route rules by route key ("A->B").
+    pub banned_words: HashMap<String, Vec<String>>,
+    pub started_at: Timestamp,
+    pub ended_at: Timestamp,
+    /// Seconds between the start and the end of the game.
+    pub duration: u64,
+    pub victory: bool,
+    pub message_count: usize,
+}
+
+/// A finished game with its full history.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedGame {
+    pub summary: GameSummary,
+    /// Messages as they were sent.
+    pub messages: Vec<Message>,
+    /// The messages as each country saw them.
+    pub views: BTreeMap<CountryCode, Vec<CensoredMessage>>,
+    /// The room's event log, which replays the game from the room's creation.
+    #[serde(default)]
+    pub log: Vec<LogEntry>,
+}
+
+/// One line of a game export.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportLine<'a> {
+    Game(&'a GameSummary),
+    Message(&'a Message),
+    View {
+        country: &'a CountryCode,
+        message: &'a CensoredMessage,
+    },
+}
+
+impl ArchivedGame {
+    /// Archive a room as it is when it closes, at `ended_at`, with every
+    /// message it had, including those it no longer kept in memory, and the
+    /// log that replays it.
+    pub fn from_room(
+        room: &dyn Room,
+        messages: Vec<Message>,
+        log: Vec<LogEntry>,
+        ended_at: Timestamp,
+    ) -> Self {
+        let record = room.record();
+        let countries = room.settings().generation.country_codes();
+        let views = countries
+            .iter()
+            .map(|country| {
+                let view = messages
+                    .iter()
+                    .map(|message| room.censor_message_for(message, country))
+                    .collect();
+                (country.clone(), view)
+            })
+            .collect();
+
+        let summary = GameSummary {
+            id: 0,
+            room_id: record.room_id,
+            pack: room.pack().id.clone(),
+            settings: record.settings,
+            players: room.players().to_vec(),
+            countries,
+            banned_words: room.filter_config().answer_key(),
+            started_at: record.game.started_at,
+            ended_at,
+            duration: ended_at.saturating_sub(record.game.started_at),
+            // Solving the puzzle outright finishes the game without notes
+            victory: record.game.victory_achieved || record.phase == RoomPhase::Finished,
+            message_count: messages.len(),
+        };
+        Self {
+            summary,
+            messages,
+            views,
+            log,
+        }
+    }
+
+    /// The export lines: the summary, then every raw message, then each
+    /// country's view of the messages.
+    pub fn export_lines(&self) -> impl Iterator<Item = ExportLine<'_>> {
+        let messages = self.messages.iter().map(ExportLine::Message);
+        let views = self.views.iter().flat_map(|(country, view)| {
+            view.iter()
+                .map(move |message| ExportLine::View { country, message })
+        });
+        std::iter::once(ExportLine::Game(&self.summary))
+            .chain(messages)
+            .chain(views)
+    }
+
+    /// The game as newline-delimited JSON, one export line per line.
+    pub fn to_ndjson(&self) -> Result<String, serde_json::Error> {
+        let mut out = String::new();
+        for line in self.export_lines() {
+            out.push_str(&serde_json::to_string(&line)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::WordPack;
+    use crate::room::ChatRoom;
+
+    fn finished_room() -> ChatRoom {
+        let settings = RoomSettings {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let pack = WordPack::builtin();
+        let mut room = ChatRoom::generate(
+            "room_1".to_string(),
+            &FilterConfig::default(),
+            &pack,
+            &settings,
+        )
+        .unwrap();
+        let words = room.allowed_words().to_vec();
+        room.add_participant("alice".to_string(), "A".to_string());
+        room.add_participant("bob".to_string(), "B".to_string());
+        room.skip_lobby();
+        for (user, country, word) in [("alice", "A", &words[0]), ("bob", "B", &words[1])] {
+            room.process_action(
+                &user.to_string(),
+                &country.to_string(),
+                UserAction::SendMessage(word.clone()),
+            )
+            .unwrap();
+        }
+        room.remove_participant(&"alice".to_string());
+        room.remove_participant(&"bob".to_string());
+        room
+    }
+
+    fn all_messages(room: &ChatRoom) -> Vec<Message> {
+        room.messages_after(0).to_vec()
+    }
+
+    #[test]
+    fn test_archive_keeps_players_who_left() {
+        let room = finished_room();
+        let started_at = room.record().game.started_at;
+        let game = ArchivedGame::from_room(&room, all_messages(&room), Vec::new(), started_at + 90);
+
+        let players: Vec<&str> = game
+            .summary
+            .players
+            .iter()
+            .map(|p| p.user_id.as_str())
+            .collect();
+        assert_eq!(players, vec!["alice", "bob"]);
+        assert_eq!(game.summary.duration, 90);
+        assert!(!game.summary.victory);
+        assert_eq!(
+            game.summary.countries,
+            room.settings().generation.country_codes()
+        );
+        assert_eq!(game.summary.banned_words, room.filter_config().answer_key());
+        assert_eq!(game.summary.message_count, game.messages.len());
+        for country in &game.summary.countries {
+            assert_eq!(game.views[country].len(), game.messages.len());
+        }
+    }
+
+    #[test]
+    fn test_export_is_one_json_object_per_line() {
+        let room = finished_room();
+        let game = ArchivedGame::from_room(&room, all_messages(&room), Vec::new(), 0);
+        let ndjson = game.to_ndjson().unwrap();
+
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let count = |kind: &str| lines.iter().filter(|l| l["type"] == kind).count();
+        assert_eq!(lines[0]["type"], "game");
+        assert_eq!(lines[0]["room_id"], "room_1");
+        assert_eq!(count("message"), game.messages.len());
+        assert_eq!(
+            count("view"),
+            game.messages.len() * game.summary.countries.len()
+        );
+        assert!(
+            lines
+                .iter()
+                .filter(|l| l["type"] == "view")
+                .all(|l| l["country"].is_string() && l["message"]["content"].is_string())
+        );
+    }
+}
@@ -1,5 +1,10 @@
+use crate::filter::Censored;
+use crate::packs::{DEFAULT_PACK_ID, PackId, PackInfo, WordPack, WordPackRegistry};
+use crate::store::{RoomRecord, StoredRoom};
+use crate::words::{GenerationError, GenerationParams};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use utoipa::ToSchema;
 
 pub type RoomId = String;
@@ -7,13 +12,40 @@ pub type UserId = String;
 pub type MessageId = u64;
 pub type CountryCode = String;
 pub type Timestamp = u64;
+pub type GameSeed = u64;
+
+/// Largest seed generated for a room, so seeds stay exact as JSON numbers in JavaScript.
+pub const MAX_GENERATED_SEED: GameSeed = (1 << 53) - 1;
 
 pub const MAX_USER_ACTIONS: usize = 100;
 
+/// Seconds a participant whose connection dropped keeps their seat by default.
+pub const DEFAULT_RECONNECT_GRACE: u64 = 60;
+
+/// Longest reconnect grace a room can be created with, in seconds.
+pub const MAX_RECONNECT_GRACE: u64 = 3600;
+
+/// Messages a room keeps in memory by default.
+pub const DEFAULT_HISTORY_CAP: usize = 50;
+
+/// Most messages a room can be created to keep in memory.
+pub const MAX_HISTORY_CAP: usize = 1000;
+
 /// The replacement string used when censoring banned words.
 pub const CENSORSHIP_REPLACEMENT: &str = "***";
 
-#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// Display name of a country code, matching the names shown by the frontend.
+pub fn country_name(code: &str) -> &str {
+    match code {
+        "A" => "Alveria",
+        "B" => "Brezna",
+        "C" => "Corvistan",
+        "D" => "Dravia",
+        other => other,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct Message {
     pub id: MessageId,
     pub sender_id: UserId,
@@ -22,32 +54,95 @@ pub struct Message {
     pub timestamp: Timestamp,
 }
 
+/// A run of a room's messages to read, for paging through its history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageRange {
+    /// Only messages with greater ids.
+    pub after: MessageId,
+    /// Only messages with smaller ids.
+    pub before: MessageId,
+    /// Most messages to read.
+    pub limit: usize,
+    /// Read the oldest messages of the range rather than the newest.
+    pub from_oldest: bool,
+}
+
+impl MessageRange {
+    /// The messages of the range, from `messages` in id order.
+    pub fn select<'a>(&self, messages: &'a [Message]) -> &'a [Message] {
+        let start = messages.partition_point(|m| m.id <= self.after);
+        let end = messages.partition_point(|m| m.id < self.before).max(start);
+        let in_range = &messages[start..end];
+        if self.from_oldest {
+            &in_range[..in_range.len().min(self.limit)]
+        } else {
+            &in_range[in_range.len().saturating_sub(self.limit)..]
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct CensoredMessage {
     pub id: MessageId,
     pub sender_id: UserId,
     pub content: String,
     pub was_censored: bool,
+    /// The viewer's firewall drops the message, so it is not shown at all.
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+impl CensoredMessage {
+    /// `message` as a viewer is shown it.
+    pub fn new(message: &Message, censored: Censored) -> Self {
+        let (content, was_censored, hidden) = match censored {
+            Censored::Shown(content, was_censored) => (content, was_censored, false),
+            Censored::Hidden => (String::new(), true, true),
+        };
+        Self {
+            id: message.id,
+            sender_id: message.sender_id.clone(),
+            content,
+            was_censored,
+            hidden,
+        }
+    }
 }
 
 /// System-level actions handled by the Room/RoomManager
-#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum SystemAction {
     SendMessage(String),
     SendMessageArray(Vec<String>),
+    /// Enter the room, or resume a seat kept after a disconnection. Sent when
+    /// a participant's socket connects.
+    JoinRoom,
     LeaveRoom,
+    /// Sent when a participant's socket closes. They keep their seat for the
+    /// room's reconnect grace period, then leave.
+    Disconnect,
+    /// Move to another country while the room is in the lobby. Refused if
+    /// the country has as many players as the one being left.
+    ChooseCountry(CountryCode),
+    /// Ask another player to trade countries while the room is in the lobby.
+    /// The seats are swapped once the other player asks back.
+    RequestSwap(UserId),
+    /// Tell the host whether the player is ready to start.
+    SetReady(bool),
+    /// Start the game. Only the host can, once every player is ready.
+    StartGame,
 }
 
 /// Game-specific actions delegated to GameEngine/GameRules
-#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum GameAction {
     SubmitNotes(HashMap<CountryCode, Vec<String>>),
 }
 
 /// Transport layer envelope for user actions
-#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum UserAction {
     System(SystemAction),
@@ -59,17 +154,146 @@ pub enum UserAction {
     LeaveRoom,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+impl UserAction {
+    /// Whether the action plays the game: sending messages or sharing notes.
+    /// Only participants can play.
+    pub fn is_play(&self) -> bool {
+        matches!(
+            self,
+            UserAction::System(SystemAction::SendMessage(_) | SystemAction::SendMessageArray(_))
+                | UserAction::Game(_)
+                | UserAction::SendMessage(_)
+                | UserAction::SendMessageArray(_)
+                | UserAction::SubmitNotes(_)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct Participant {
     pub user_id: UserId,
     pub country: CountryCode,
     pub joined_at: Timestamp,
+    /// When the participant's connection dropped, while they have not
+    /// reconnected. They keep their seat until the room's grace period ends.
+    #[serde(default)]
+    pub disconnected_at: Option<Timestamp>,
+}
+
+/// Where a room is in its lifecycle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomPhase {
+    /// Players gather, pick countries and get ready. No messages are sent yet.
+    #[default]
+    Lobby,
+    /// The host started the game; players chat and share notes.
+    InProgress,
+    /// The puzzle was solved. Players can still chat until they leave.
+    Finished,
+    /// Everyone left and the room is shutting down.
+    Closed,
+}
+
+impl RoomPhase {
+    /// Whether players can take `action` in this phase.
+    pub fn allows(self, action: &UserAction) -> bool {
+        use RoomPhase::*;
+        match action {
+            UserAction::System(
+                SystemAction::JoinRoom | SystemAction::LeaveRoom | SystemAction::Disconnect,
+            )
+            | UserAction::LeaveRoom => self != Closed,
+            UserAction::System(
+                SystemAction::ChooseCountry(_)
+                | SystemAction::RequestSwap(_)
+                | SystemAction::SetReady(_)
+                | SystemAction::StartGame,
+            ) => self == Lobby,
+            UserAction::System(
+                SystemAction::SendMessage(_) | SystemAction::SendMessageArray(_),
+            )
+            | UserAction::SendMessage(_)
+            | UserAction::SendMessageArray(_) => matches!(self, InProgress | Finished),
+            UserAction::Game(GameAction::SubmitNotes(_)) | UserAction::SubmitNotes(_) => {
+                self == InProgress
+            }
+        }
+    }
+}
+
+impl fmt::Display for RoomPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lobby => write!(f, "in the lobby"),
+            Self::InProgress => write!(f, "in progress"),
+            Self::Finished => write!(f, "finished"),
+            Self::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+/// Why a room refused a player's action.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ActionError {
+    /// The action is not allowed in the room's current phase.
+    WrongPhase(RoomPhase),
+    /// The player is not in the room.
+    NotParticipant,
+    /// The room is full, or its game started without the player.
+    CannotJoin,
+    /// Only the host can start the game.
+    NotHost,
+    /// The game cannot start until every player is ready.
+    PlayersNotReady,
+    /// The country is not one of the game's, or has as many players as the
+    /// player's own.
+    CountryUnavailable,
+    /// The other player is not in the room or is in the same country.
+    InvalidSwap,
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongPhase(phase) => write!(f, "not allowed while the game is {phase}"),
+            Self::NotParticipant => write!(f, "you are not in this room"),
+            Self::CannotJoin => write!(f, "the room is full or its game has started"),
+            Self::NotHost => write!(f, "only the host can start the game"),
+            Self::PlayersNotReady => write!(f, "not every player is ready"),
+            Self::CountryUnavailable => write!(f, "that country is not available"),
+            Self::InvalidSwap => write!(f, "cannot swap countries with that player"),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+/// An action a room refused, reported to the player who sent it.
+#[derive(Clone, Debug)]
+pub struct ActionRejection {
+    pub user_id: UserId,
+    pub error: ActionError,
+}
+
+/// Who is in a room and where the room is in its lifecycle; everything in
+/// the room state apart from its messages.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RoomRoster {
+    pub phase: RoomPhase,
+    /// The player who can start the game: the one who has been in the room
+    /// the longest.
+    pub host: Option<UserId>,
+    /// Players who are ready for the game to start.
+    pub ready: Vec<UserId>,
+    pub participants: Vec<Participant>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct RoomState {
     pub room_id: RoomId,
-    pub participants: Vec<Participant>,
+    #[serde(flatten)]
+    pub roster: RoomRoster,
     pub recent_messages: Vec<CensoredMessage>,
 }
 
@@ -96,17 +320,151 @@ pub struct VictoryState {
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct RoomUpdate {
-    pub room_state: RoomState,
+    /// Counts the updates published for the room, starting at 0 for the
+    /// state the room was started with. Set when the update is published.
+    pub version: u64,
     /// Raw messages that need to be censored per-user before sending to clients.
     pub new_messages: Vec<Message>,
     pub notifications: Vec<Notification>,
     pub room_closed: bool,
     pub victory: Option<VictoryState>,
+    /// Actions refused since the last update; each is sent only to its player.
+    #[serde(skip)]
+    pub rejections: Vec<ActionRejection>,
+}
+
+/// How a country's firewall treats a message containing one of its banned words.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CensorshipStrategy {
+    /// Replace each banned word with `CENSORSHIP_REPLACEMENT`.
+    #[default]
+    Mask,
+    /// Silently remove each banned word.
+    DropToken,
+    /// Hide the whole message.
+    DropMessage,
+    /// Replace each banned word with an allowed word no country bans.
+    Decoy,
+    /// Remove each banned word and shuffle the remaining words.
+    Shuffle,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct FilterConfig {
+    /// Banned words per country. An entry of several space-separated symbols
+    /// bans a phrase: its symbols are censored only when they appear together
+    /// in that order.
     pub banned_words: HashMap<CountryCode, Vec<String>>,
+    /// Censorship strategy per country; countries not listed use `CensorshipStrategy::Mask`.
+    #[serde(default)]
+    pub strategies: HashMap<CountryCode, CensorshipStrategy>,
+    /// Extra words banned only on messages from a sender country (outer key)
+    /// to a receiver country (inner key), on top of both countries' own lists.
+    #[serde(default)]
+    pub routes: HashMap<CountryCode, HashMap<CountryCode, Vec<String>>>,
+    /// Bans added to countries' lists as the game goes on.
+    #[serde(default)]
+    pub escalations: Vec<Escalation>,
+}
+
+/// What makes a scheduled escalation fire.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EscalationTrigger {
+    /// Seconds elapsed since the game started.
+    ElapsedSecs(u64),
+    /// Messages sent by players in the room.
+    MessagesSent(usize),
+    /// Distinct bans discovered across all players' notes.
+    Discoveries(usize),
+}
+
+/// A scheduled upgrade of a country's firewall.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Escalation {
+    pub trigger: EscalationTrigger,
+    pub country: CountryCode,
+    /// Words or phrases added to the country's ban list.
+    pub words: Vec<String>,
+}
+
+/// Separator between sender and receiver in the answer key of a route rule, as in "A->B".
+pub const ROUTE_KEY_SEPARATOR: &str = "->";
+
+/// Answer key for the words banned on messages from `sender` to `receiver`.
+pub fn route_key(sender: &CountryCode, receiver: &CountryCode) -> String {
+    format!("{sender}{ROUTE_KEY_SEPARATOR}{receiver}")
+}
+
+impl FilterConfig {
+    /// The complete set of bans a solution has to name: each country's list
+    /// under its code, and each non-empty route rule under its `route_key`.
+    pub fn answer_key(&self) -> HashMap<String, Vec<String>> {
+        let routes = self.routes.iter().flat_map(|(sender, receivers)| {
+            receivers
+                .iter()
+                .filter(|(_, words)| !words.is_empty())
+                .map(move |(receiver, words)| (route_key(sender, receiver), words.clone()))
+        });
+        self.banned_words
+            .iter()
+            .map(|(country, words)| (country.clone(), words.clone()))
+            .chain(routes)
+            .collect()
+    }
+}
+
+/// Rules a host picks when creating a room.
+///
+/// Every field has a default, so an empty object creates a standard game.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct RoomSettings {
+    /// Censor words banned in the sender's country.
+    pub sender_censor: bool,
+    /// Censor words banned in the receiver's country.
+    pub receiver_censor: bool,
+    /// Show senders their own messages uncensored.
+    pub shadow_ban: bool,
+    /// Most participants the room admits; unlimited when absent.
+    pub max_players: Option<usize>,
+    /// Word pack to draw the room's words from.
+    pub pack: PackId,
+    /// Seed for the room's words; random when absent. A created room always
+    /// reports the seed it used, so its settings replay the same puzzle.
+    pub seed: Option<GameSeed>,
+    /// Countries, bans per country and ban overlap.
+    #[serde(flatten)]
+    pub generation: GenerationParams,
+    /// Seconds a participant whose connection dropped keeps their seat.
+    pub reconnect_grace: u64,
+    /// Messages the room keeps in memory. Older ones are read back from the
+    /// store.
+    pub history_cap: usize,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            sender_censor: true,
+            receiver_censor: true,
+            shadow_ban: false,
+            max_players: None,
+            pack: DEFAULT_PACK_ID.to_string(),
+            seed: None,
+            generation: GenerationParams::default(),
+            reconnect_grace: DEFAULT_RECONNECT_GRACE,
+            history_cap: DEFAULT_HISTORY_CAP,
+        }
+    }
+}
+
+impl RoomSettings {
+    /// Whether a room with `participant_count` players has room for one more.
+    pub fn admits(&self, participant_count: usize) -> bool {
+        self.max_players.is_none_or(|max| participant_count < max)
+    }
 }
 
 /// Trait defining the core behavior of a game room.
@@ -123,21 +481,44 @@ pub trait Room: Send + Sync {
     /// Check if the room has no participants.
     fn is_empty(&self) -> bool;
 
-    /// Add a participant to the room.
-    /// Returns true if the participant was added, false if already present.
+    /// Add a participant to the room, seated in a country the room picks
+    /// with `country` as the player's preference.
+    /// Returns true if the participant was added, false if already present
+    /// or the room is full.
     fn add_participant(&mut self, user_id: UserId, country: CountryCode) -> bool;
 
+    /// Country a current participant is seated in.
+    fn country_of(&self, user_id: &UserId) -> Option<&CountryCode>;
+
     /// Remove a participant from the room.
     /// Returns true if the participant was removed, false if not found.
     fn remove_participant(&mut self, user_id: &UserId) -> bool;
 
+    /// Everyone who has joined the room, including those who have left.
+    fn players(&self) -> &[Participant];
+
     /// Process a user action and return the resulting message and notifications.
+    /// Fails if the action is not allowed in the room's phase or state.
     fn process_action(
         &mut self,
         user_id: &UserId,
         country: &CountryCode,
         action: UserAction,
-    ) -> (Option<Message>, Vec<Notification>);
+    ) -> Result<(Option<Message>, Vec<Notification>), ActionError>;
+
+    /// Where the room is in its lifecycle.
+    fn phase(&self) -> RoomPhase;
+
+    /// Move the room to `RoomPhase::Closed` once everyone has left.
+    fn close(&mut self);
+
+    /// Get who is in the room and its phase.
+    fn roster(&self) -> RoomRoster;
+
+    /// Counts the changes to the censorship rules since the room started,
+    /// such as escalations and the win unlocking every country. Messages
+    /// censored before the count changed must be censored again.
+    fn censorship_epoch(&self) -> u64;
 
     /// Get the room state censored for a specific country.
     fn get_censored_state_for(&self, country: &CountryCode) -> RoomState;
@@ -148,6 +529,10 @@ pub trait Room: Send + Sync {
     /// Trigger victory condition (used when puzzle is solved).
     fn win(&mut self);
 
+    /// Advance time-based game mechanics such as scheduled escalations.
+    /// Returns the resulting notifications.
+    fn tick(&mut self) -> Vec<Notification>;
+
     /// Get all player notes.
     fn get_player_notes(&self) -> &HashMap<UserId, HashMap<CountryCode, Vec<String>>>;
 
@@ -165,11 +550,44 @@ pub trait Room: Send + Sync {
 
     /// Get the list of allowed words.
     fn allowed_words(&self) -> &[String];
+
+    /// Get the seed the room's game was generated from.
+    fn seed(&self) -> GameSeed;
+
+    /// Get the word pack the room's game was generated from.
+    fn pack(&self) -> &PackInfo;
+
+    /// Get the settings the room was created with.
+    fn settings(&self) -> &RoomSettings;
+
+    /// Capture everything needed to restore the room, apart from its messages.
+    fn record(&self) -> RoomRecord;
+
+    /// Get the messages with ids greater than `id` the room still keeps in
+    /// memory, in id order.
+    fn messages_after(&self, id: MessageId) -> &[Message];
+
+    /// Let go of the oldest messages beyond the room's history cap, as long
+    /// as they are saved: those with ids up to `saved_through`.
+    fn drop_saved_messages(&mut self, saved_through: MessageId);
 }
 
 pub trait RoomConfig: Send + Sync {
     fn get_filter_config(&self) -> &FilterConfig;
-    fn init_room(&self, room_id: RoomId) -> Box<dyn Room>;
+    /// Word packs new rooms can choose from.
+    fn word_packs(&self) -> &WordPackRegistry;
+    fn init_room(
+        &self,
+        room_id: RoomId,
+        pack: &WordPack,
+        settings: &RoomSettings,
+    ) -> Result<Box<dyn Room>, GenerationError>;
+    /// Rebuild a saved room; `pack` is the pack named in its settings.
+    fn restore_room(
+        &self,
+        pack: &WordPack,
+        stored: StoredRoom,
+    ) -> Result<Box<dyn Room>, GenerationError>;
 }
 
 #[derive(Clone, Debug, Serialize, ToSchema)]
@@ -177,6 +595,7 @@ pub struct RoomInfo {
     pub filter_config: FilterConfig,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UserMessage {
     pub user_id: UserId,
     pub country: CountryCode,
@@ -0,0 +1,450 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::archive::ArchivedGame;
+use crate::data::*;
+use crate::packs::PackId;
+use crate::utils::at_time;
+use crate::words::GenerationError;
+
+/// Something that changed a room, recorded so the room can be rebuilt.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomEvent {
+    /// The room was created with these settings, including the seed used.
+    Created { settings: RoomSettings },
+    /// A message handled by the room runner: joins the sender if needed,
+    /// then applies the action.
+    UserMessage(UserMessage),
+    /// An action applied directly by an HTTP endpoint, without joining.
+    Action {
+        user_id: UserId,
+        country: CountryCode,
+        action: UserAction,
+    },
+    /// A periodic tick that applied time-based mechanics.
+    Tick,
+    /// The puzzle was solved through a solve endpoint.
+    Win,
+}
+
+/// One entry of a room's event log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Time the event was applied; replay applies it at the same time.
+    pub timestamp: Timestamp,
+    /// Seed of the room's game.
+    pub seed: GameSeed,
+    pub event: RoomEvent,
+}
+
+/// Why a log could not be replayed, or replayed to a different state.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The log does not start with a `Created` event.
+    MissingCreation,
+    /// The word pack named in the settings is not loaded.
+    UnknownPack(PackId),
+    /// The puzzle could not be regenerated from the settings.
+    Generation(GenerationError),
+    /// The log could not be read.
+    Store(crate::store::StoreError),
+    /// The rebuilt room's state differs from the live room's, or from the
+    /// archived game's.
+    StateDiffers,
+    /// The rebuilt room's messages differ from the live room's or the
+    /// archived game's, starting at this message id, or the message counts
+    /// differ when `None`.
+    MessagesDiffer(Option<MessageId>),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingCreation => write!(f, "the log does not start with the room's creation"),
+            Self::UnknownPack(id) => write!(f, "unknown word pack '{id}'"),
+            Self::Generation(err) => err.fmt(f),
+            Self::Store(err) => err.fmt(f),
+            Self::StateDiffers => write!(f, "the rebuilt room state differs from the original"),
+            Self::MessagesDiffer(Some(id)) => {
+                write!(f, "the rebuilt messages differ from message {id}")
+            }
+            Self::MessagesDiffer(None) => {
+                write!(f, "the rebuilt room has a different number of messages")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Apply an event to a room and return the resulting message and
+/// notifications, or why the room refused the action. The live room and a
+/// replay both go through here, so applying the same events at the same
+/// times gives the same room.
+///
+/// `Created` events are not applied; they describe the fresh room instead.
+pub fn apply_event(
+    room: &mut dyn Room,
+    event: RoomEvent,
+) -> Result<(Option<Message>, Vec<Notification>), ActionError> {
+    match event {
+        RoomEvent::Created { .. } => Ok((None, Vec::new())),
+        RoomEvent::UserMessage(user_message) => {
+            let mut notifications = Vec::new();
+            // Handle join if user not in room. Leaving or dropping the
+            // connection must not bring back a player who already left.
+            let joins = !matches!(
+                user_message.action,
+                UserAction::System(SystemAction::LeaveRoom | SystemAction::Disconnect)
+                    | UserAction::LeaveRoom
+            );
+            if joins
+                && !room
+                    .participants()
+                    .iter()
+                    .any(|p| p.user_id == user_message.user_id)
+            {
+                if !room.add_participant(user_message.user_id.clone(), user_message.country.clone())
+                {
+                    // Players who are not in the room cannot act
+                    return Err(ActionError::CannotJoin);
+                }
+                notifications.push(Notification {
+                    message: format!("{} joined the room", user_message.user_id),
+                });
+            }
+            let (message, action_notifications) = room.process_action(
+                &user_message.user_id,
+                &user_message.country,
+                user_message.action,
+            )?;
+            notifications.extend(action_notifications);
+            room.check_victory();
+            Ok((message, notifications))
+        }
+        RoomEvent::Action {
+            user_id,
+            country,
+            action,
+        } => {
+            let result = room.process_action(&user_id, &country, action);
+            room.check_victory();
+            result
+        }
+        RoomEvent::Tick => Ok((None, room.tick())),
+        RoomEvent::Win => {
+            room.win();
+            Ok((None, Vec::new()))
+        }
+    }
+}
+
+/// Rebuild a room by replaying its log into a fresh room.
+pub fn rebuild_room(
+    config: &dyn RoomConfig,
+    room_id: RoomId,
+    log: &[LogEntry],
+) -> Result<Box<dyn Room>, ReplayError> {
+    let Some((
+        LogEntry {
+            timestamp,
+            event: RoomEvent::Created { settings },
+            ..
+        },
+        events,
+    )) = log.split_first()
+    else {
+        return Err(ReplayError::MissingCreation);
+    };
+
+    let pack = config
+        .word_packs()
+        .get(&settings.pack)
+        .ok_or_else(|| ReplayError::UnknownPack(settings.pack.clone()))?;
+    let mut room = at_time(*timestamp, || config.init_room(room_id, &pack, settings))
+        .map_err(ReplayError::Generation)?;
+    for entry in events {
+        // Actions refused here were refused in the live room as well
+        let _ = at_time(entry.timestamp, || {
+            apply_event(room.as_mut(), entry.event.clone())
+        });
+    }
+    Ok(room)
+}
+
+/// Check that replaying `log` rebuilds exactly the state of `live`.
+pub fn verify_replay(
+    config: &dyn RoomConfig,
+    live: &dyn Room,
+    log: &[LogEntry],
+) -> Result<(), ReplayError> {
+    let rebuilt = rebuild_room(config, live.room_id().clone(), log)?;
+    if rebuilt.record() != live.record() {
+        return Err(ReplayError::StateDiffers);
+    }
+
+    // The live room may have let go of its oldest messages; the ones it
+    // still keeps must match
+    let live_messages = live.messages_after(0);
+    let dropped_through = live_messages
+        .first()
+        .map_or(live.record().message_counter, |first| first.id - 1);
+    compare_messages(live_messages, rebuilt.messages_after(dropped_through))
+}
+
+/// Check that replaying an archived game's log rebuilds the players and
+/// messages it was archived with.
+pub fn verify_archived(config: &dyn RoomConfig, game: &ArchivedGame) -> Result<(), ReplayError> {
+    let rebuilt = rebuild_room(config, game.summary.room_id.clone(), &game.log)?;
+    if rebuilt.players() != game.summary.players {
+        return Err(ReplayError::StateDiffers);
+    }
+    compare_messages(&game.messages, rebuilt.messages_after(0))
+}
+
+fn compare_messages(expected: &[Message], rebuilt: &[Message]) -> Result<(), ReplayError> {
+    if let Some((message, _)) = expected
+        .iter()
+        .zip(rebuilt)
+        .find(|(expected, rebuilt)| expected != rebuilt)
+    {
+        return Err(ReplayError::MessagesDiffer(Some(message.id)));
+    }
+    if rebuilt.len() != expected.len() {
+        return Err(ReplayError::MessagesDiffer(None));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::{WordPack, WordPackRegistry};
+    use crate::room::ChatRoom;
+    use crate::store::StoredRoom;
+
+    struct TestConfig {
+        filter_config: FilterConfig,
+        word_packs: WordPackRegistry,
+    }
+
+    impl RoomConfig for TestConfig {
+        fn get_filter_config(&self) -> &FilterConfig {
+            &self.filter_config
+        }
+
+        fn word_packs(&self) -> &WordPackRegistry {
+            &self.word_packs
+        }
+
+        fn init_room(
+            &self,
+            room_id: RoomId,
+            pack: &WordPack,
+            settings: &RoomSettings,
+        ) -> Result<Box<dyn Room>, GenerationError> {
+            let room = ChatRoom::generate(room_id, &self.filter_config, pack, settings)?;
+            Ok(Box::new(room))
+        }
+
+        fn restore_room(
+            &self,
+            pack: &WordPack,
+            stored: StoredRoom,
+        ) -> Result<Box<dyn Room>, GenerationError> {
+            Ok(Box::new(ChatRoom::restore(
+                &self.filter_config,
+                pack,
+                stored,
+            )?))
+        }
+    }
+
+    fn test_config() -> TestConfig {
+        TestConfig {
+            filter_config: FilterConfig::default(),
+            word_packs: WordPackRegistry::default(),
+        }
+    }
+
+    fn send(user_id: &str, country: &str, text: &str) -> RoomEvent {
+        RoomEvent::UserMessage(UserMessage {
+            user_id: user_id.to_string(),
+            country: country.to_string(),
+            action: UserAction::SendMessage(text.to_string()),
+        })
+    }
+
+    fn system(user_id: &str, action: SystemAction) -> RoomEvent {
+        RoomEvent::UserMessage(UserMessage {
+            user_id: user_id.to_string(),
+            country: String::new(),
+            action: UserAction::System(action),
+        })
+    }
+
+    /// Players get ready, joining as they do, then the first one starts.
+    fn start_with(user_ids: &[&str]) -> Vec<RoomEvent> {
+        let mut events: Vec<RoomEvent> = user_ids
+            .iter()
+            .map(|user_id| system(user_id, SystemAction::SetReady(true)))
+            .collect();
+        events.push(system(user_ids[0], SystemAction::StartGame));
+        events
+    }
+
+    fn seeded_settings() -> RoomSettings {
+        RoomSettings {
+            seed: Some(42),
+            ..Default::default()
+        }
+    }
+
+    /// Words the seeded room accepts, so test messages are not dropped.
+    fn allowed_words(config: &TestConfig) -> Vec<String> {
+        let pack = WordPack::builtin();
+        let room = config
+            .init_room("room_1".to_string(), &pack, &seeded_settings())
+            .unwrap();
+        room.allowed_words().to_vec()
+    }
+
+    /// Creates a room and applies `events` to it one second apart, logging
+    /// everything the way the room manager does.
+    fn play(config: &TestConfig, events: Vec<RoomEvent>) -> (Box<dyn Room>, Vec<LogEntry>) {
+        let settings = seeded_settings();
+        let pack = config.word_packs.get(&settings.pack).unwrap();
+        let mut timestamp = 1_000_000;
+        let mut room = at_time(timestamp, || {
+            config.init_room("room_1".to_string(), &pack, &settings)
+        })
+        .unwrap();
+        let mut log = vec![LogEntry {
+            timestamp,
+            seed: room.seed(),
+            event: RoomEvent::Created {
+                settings: room.settings().clone(),
+            },
+        }];
+        for event in events {
+            timestamp += 1000;
+            let _ = at_time(timestamp, || apply_event(room.as_mut(), event.clone()));
+            log.push(LogEntry {
+                timestamp,
+                seed: room.seed(),
+                event,
+            });
+        }
+        (room, log)
+    }
+
+    #[test]
+    fn test_replay_rebuilds_room() {
+        let config = test_config();
+        let words = allowed_words(&config);
+        let mut events = start_with(&["alice", "bob"]);
+        events.extend([
+            send("alice", "A", &words[0]),
+            send("bob", "B", &words[1]),
+            RoomEvent::Action {
+                user_id: "alice".to_string(),
+                country: "A".to_string(),
+                action: UserAction::SubmitNotes(Default::default()),
+            },
+            RoomEvent::Tick,
+            RoomEvent::Win,
+        ]);
+        let (live, log) = play(&config, events);
+        assert_eq!(live.phase(), RoomPhase::Finished);
+        assert_eq!(live.messages_after(0).len(), 4);
+
+        let rebuilt = rebuild_room(&config, "room_1".to_string(), &log).unwrap();
+        assert_eq!(rebuilt.record(), live.record());
+        assert_eq!(rebuilt.messages_after(0), live.messages_after(0));
+        assert!(verify_replay(&config, live.as_ref(), &log).is_ok());
+    }
+
+    #[test]
+    fn test_log_round_trips_as_json() {
+        let config = test_config();
+        let words = allowed_words(&config);
+        let (_, log) = play(&config, vec![send("alice", "A", &words[0]), RoomEvent::Win]);
+        let json = serde_json::to_string(&log).unwrap();
+        let parsed: Vec<LogEntry> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, log);
+    }
+
+    #[test]
+    fn test_tampered_log_fails_verification() {
+        let config = test_config();
+        let words = allowed_words(&config);
+        let mut events = start_with(&["alice"]);
+        events.extend([send("alice", "A", &words[0]), send("alice", "A", &words[1])]);
+        let (live, mut log) = play(&config, events);
+        // The pack welcome plus both messages
+        assert_eq!(live.messages_after(0).len(), 3);
+
+        // Changing what was said changes the rebuilt messages
+        log[4].event = send("alice", "A", &words[2]);
+        let result = verify_replay(&config, live.as_ref(), &log);
+        assert!(
+            matches!(result, Err(ReplayError::MessagesDiffer(Some(_)))),
+            "{result:?}"
+        );
+
+        // Dropping the join changes who is in the room
+        log.remove(1);
+        assert!(matches!(
+            verify_replay(&config, live.as_ref(), &log),
+            Err(ReplayError::StateDiffers)
+        ));
+    }
+
+    #[test]
+    fn test_archived_game_replays_from_its_log() {
+        let config = test_config();
+        let words = allowed_words(&config);
+        let mut events = start_with(&["alice", "bob"]);
+        events.extend([send("alice", "A", &words[0]), RoomEvent::Win]);
+        let (live, mut log) = play(&config, events);
+        let messages = live.messages_after(0).to_vec();
+        let game = ArchivedGame::from_room(live.as_ref(), messages.clone(), log.clone(), 0);
+        assert!(verify_archived(&config, &game).is_ok());
+
+        log[3].event = send("alice", "A", &words[1]);
+        let tampered = ArchivedGame::from_room(live.as_ref(), messages, log, 0);
+        assert!(matches!(
+            verify_archived(&config, &tampered),
+            Err(ReplayError::MessagesDiffer(Some(_)))
+        ));
+    }
+
+    #[test]
+    fn test_disconnect_does_not_join() {
+        let config = test_config();
+        let (room, _) = play(
+            &config,
+            vec![
+                system("alice", SystemAction::JoinRoom),
+                system("alice", SystemAction::LeaveRoom),
+                system("alice", SystemAction::Disconnect),
+            ],
+        );
+        assert!(room.participants().is_empty());
+    }
+
+    #[test]
+    fn test_replay_requires_creation() {
+        let config = test_config();
+        let (_, log) = play(&config, vec![send("alice", "A", "hello")]);
+        assert!(matches!(
+            rebuild_room(&config, "room_1".to_string(), &log[1..]),
+            Err(ReplayError::MissingCreation)
+        ));
+        assert!(matches!(
+            rebuild_room(&config, "room_1".to_string(), &[]),
+            Err(ReplayError::MissingCreation)
+        ));
+    }
+}
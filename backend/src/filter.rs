@@ -1,14 +1,56 @@
+use aho_corasick::{AhoCorasick, MatchKind};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::{IndexedRandom, SliceRandom};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::sync::Arc;
 
-use crate::data::{CENSORSHIP_REPLACEMENT, CountryCode, FilterConfig};
+use crate::data::{CENSORSHIP_REPLACEMENT, CensorshipStrategy, CountryCode, FilterConfig};
+
+/// What a viewer is shown of a message once it is censored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Censored {
+    /// The message is shown with this content, and whether any of it was censored.
+    Shown(String, bool),
+    /// `CensorshipStrategy::DropMessage` hides the message from the viewer entirely.
+    Hidden,
+}
 
 pub struct CensorshipFilter {
     pub(crate) config: Arc<FilterConfig>,
+    /// Ban lists of every country, compiled once when the filter is created.
+    matcher: BanMatcher,
+    /// Allowed words that no country bans, used by `CensorshipStrategy::Decoy`.
+    decoys: Vec<String>,
 }
 
 impl CensorshipFilter {
     pub fn new(config: Arc<FilterConfig>) -> Self {
-        Self { config }
+        let matcher = BanMatcher::new(&config);
+        Self {
+            config,
+            matcher,
+            decoys: Vec::new(),
+        }
+    }
+
+    /// Sets the words `CensorshipStrategy::Decoy` may substitute, skipping any
+    /// word banned by some country. Without decoys the strategy falls back to masking.
+    pub fn with_decoys(mut self, allowed_words: &[String]) -> Self {
+        let banned: HashSet<String> = self
+            .config
+            .banned_words
+            .values()
+            .flatten()
+            .map(|word| fold_case(word))
+            .collect();
+        self.decoys = allowed_words
+            .iter()
+            .filter(|word| !banned.contains(&fold_case(word)))
+            .cloned()
+            .collect();
+        self
     }
 
     /// Applies dual-filter censorship: sender's filter + receiver's filter, controlled by flags
@@ -17,72 +59,266 @@ impl CensorshipFilter {
         content: &str,
         sender_country: Option<&CountryCode>,
         receiver_country: Option<&CountryCode>,
-    ) -> (String, bool) {
-        let mut result = content.to_string();
-        let mut was_censored = false;
+    ) -> Censored {
+        self.censor_message_seeded(content, sender_country, receiver_country, None, 0)
+    }
 
-        // Apply sender's country filter if enabled
-        if let (Some(_sender), Some(banned_words)) = (
-            sender_country,
+    /// Like `censor_message`, also applying the `FilterConfig::routes` rule for
+    /// `route` (sender country, receiver country), with `seed` driving the decoy
+    /// and shuffle strategies. Words caught by a route rule are censored with the
+    /// receiver's strategy.
+    ///
+    /// Passing the message id as the seed keeps the censored form of a message
+    /// stable every time the history is censored again.
+    pub fn censor_message_seeded(
+        &self,
+        content: &str,
+        sender_country: Option<&CountryCode>,
+        receiver_country: Option<&CountryCode>,
+        route: Option<(&CountryCode, &CountryCode)>,
+        seed: u64,
+    ) -> Censored {
+        let applies = |country: Option<&CountryCode>| {
+            country.is_some_and(|c| self.config.banned_words.contains_key(c))
+        };
+        let route_applies = route.is_some_and(|(from, to)| {
             self.config
-                .banned_words
-                .get(sender_country.unwrap_or(&"".to_string())),
-        ) {
-            for word in banned_words {
-                if result.to_lowercase().contains(&word.to_lowercase()) {
-                    result = self.replace_word(&result, word);
-                    was_censored = true;
+                .routes
+                .get(from)
+                .is_some_and(|receivers| receivers.contains_key(to))
+        });
+        if !applies(sender_country) && !applies(receiver_country) && !route_applies {
+            return Censored::Shown(content.to_string(), false);
+        }
+
+        let (folded, tokens) = fold_tokens(content);
+        // The country whose filter caught each token; the sender's filter runs first
+        let mut caught_by: Vec<Option<&CountryCode>> = vec![None; tokens.len()];
+
+        // Whole-token matching lets a single scan for the union of both ban lists
+        // stand in for applying the filters in turn. Overlapping matches are needed
+        // because a phrase banned elsewhere may contain a word banned here.
+        for found in self.matcher.automaton.find_overlapping_iter(&folded) {
+            let first = tokens.binary_search_by_key(&found.start(), |t| t.folded.start);
+            let last = tokens.binary_search_by_key(&found.end(), |t| t.folded.end);
+            let (Ok(first), Ok(last)) = (first, last) else {
+                continue;
+            };
+            let scopes = &self.matcher.scopes[found.pattern()];
+            let by_country = |country: &&CountryCode| {
+                scopes
+                    .iter()
+                    .any(|s| matches!(s, BanScope::Country(c) if c == *country))
+            };
+            let by_route = |(from, to): &(&CountryCode, &CountryCode)| {
+                scopes
+                    .iter()
+                    .any(|s| matches!(s, BanScope::Route(f, t) if f == *from && t == *to))
+            };
+            let Some(country) = sender_country
+                .filter(by_country)
+                .or_else(|| receiver_country.filter(by_country))
+                .or_else(|| route.filter(by_route).map(|(_, to)| to))
+            else {
+                continue;
+            };
+            // A phrase censors every one of its tokens
+            for caught in &mut caught_by[first..=last] {
+                if caught.is_none() || Some(country) == sender_country {
+                    *caught = Some(country);
                 }
             }
         }
 
-        // Apply receiver's country filter if enabled
-        if let (Some(_receiver), Some(banned_words)) = (
-            receiver_country,
-            self.config
-                .banned_words
-                .get(receiver_country.unwrap_or(&"".to_string())),
-        ) {
-            for word in banned_words {
-                if result.to_lowercase().contains(&word.to_lowercase()) {
-                    result = self.replace_word(&result, word);
-                    was_censored = true;
+        if caught_by.iter().all(Option::is_none) {
+            return Censored::Shown(content.to_string(), false);
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut shuffle = false;
+        // None marks a token removed from the message
+        let mut replacements: Vec<Option<&str>> = Vec::with_capacity(tokens.len());
+
+        for (token, country) in tokens.iter().zip(caught_by) {
+            let Some(country) = country else {
+                replacements.push(Some(&content[token.original.clone()]));
+                continue;
+            };
+            match self.strategy_for(country) {
+                CensorshipStrategy::Mask => replacements.push(Some(CENSORSHIP_REPLACEMENT)),
+                CensorshipStrategy::DropToken => replacements.push(None),
+                CensorshipStrategy::DropMessage => return Censored::Hidden,
+                CensorshipStrategy::Decoy => {
+                    let decoy = self.decoys.choose(&mut rng).map(String::as_str);
+                    replacements.push(Some(decoy.unwrap_or(CENSORSHIP_REPLACEMENT)));
+                }
+                CensorshipStrategy::Shuffle => {
+                    shuffle = true;
+                    replacements.push(None);
                 }
             }
         }
 
-        (result, was_censored)
+        if shuffle || replacements.contains(&None) {
+            let mut kept: Vec<&str> = replacements.into_iter().flatten().collect();
+            if shuffle {
+                kept.shuffle(&mut rng);
+            }
+            return Censored::Shown(kept.join(" "), true);
+        }
+
+        // Every token kept its place, so keep the original separators too
+        let mut result = String::with_capacity(content.len());
+        let mut last_end = 0;
+        for (token, replacement) in tokens.iter().zip(replacements.into_iter().flatten()) {
+            result.push_str(&content[last_end..token.original.start]);
+            result.push_str(replacement);
+            last_end = token.original.end;
+        }
+        result.push_str(&content[last_end..]);
+
+        Censored::Shown(result, true)
     }
 
-    fn replace_word(&self, content: &str, word: &str) -> String {
-        let lower_content = content.to_lowercase();
-        let lower_word = word.to_lowercase();
+    fn strategy_for(&self, country: &CountryCode) -> CensorshipStrategy {
+        self.config
+            .strategies
+            .get(country)
+            .copied()
+            .unwrap_or_default()
+    }
+}
 
-        let mut result = String::new();
-        let mut last_end = 0;
+/// Where a banned word applies.
+#[derive(Clone, PartialEq)]
+enum BanScope {
+    /// Banned by a country's own filter.
+    Country(CountryCode),
+    /// Banned only on messages from the first country to the second.
+    Route(CountryCode, CountryCode),
+}
+
+/// Every ban list compiled into a single Aho-Corasick automaton, so a message
+/// is scanned once no matter how many words are banned.
+struct BanMatcher {
+    automaton: AhoCorasick,
+    /// Scopes banning each pattern, indexed by pattern id.
+    scopes: Vec<Vec<BanScope>>,
+}
+
+impl BanMatcher {
+    fn new(config: &FilterConfig) -> Self {
+        let country_bans = config
+            .banned_words
+            .iter()
+            .map(|(country, words)| (BanScope::Country(country.clone()), words));
+        let route_bans = config.routes.iter().flat_map(|(from, receivers)| {
+            receivers
+                .iter()
+                .map(|(to, words)| (BanScope::Route(from.clone(), to.clone()), words))
+        });
 
-        for (start, _) in lower_content.match_indices(&lower_word) {
-            result.push_str(&content[last_end..start]);
-            result.push_str(CENSORSHIP_REPLACEMENT);
-            last_end = start + word.len();
+        let mut patterns: Vec<String> = Vec::new();
+        let mut scopes: Vec<Vec<BanScope>> = Vec::new();
+        let mut pattern_ids: HashMap<String, usize> = HashMap::new();
+
+        for (scope, words) in country_bans.chain(route_bans) {
+            for word in words.iter().map(|w| fold_phrase(w)) {
+                if word.is_empty() {
+                    continue;
+                }
+                let id = *pattern_ids.entry(word.clone()).or_insert_with(|| {
+                    patterns.push(word);
+                    scopes.push(Vec::new());
+                    patterns.len() - 1
+                });
+                if !scopes[id].contains(&scope) {
+                    scopes[id].push(scope.clone());
+                }
+            }
         }
-        result.push_str(&content[last_end..]);
 
-        result
+        // Standard semantics allow overlapping searches
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::Standard)
+            .build(&patterns)
+            .expect("banned words should compile into an automaton");
+
+        Self { automaton, scopes }
     }
 }
 
+/// Folds a word for case-insensitive comparison.
+///
+/// Upper-casing first maps characters such as 'ß' to their full expansion
+/// ("SS"), so the result approximates Unicode full case folding.
+pub(crate) fn fold_case(word: &str) -> String {
+    word.to_uppercase().to_lowercase()
+}
+
+/// Folds a banned word or phrase for comparison: each symbol is case-folded
+/// and the symbols of a phrase are joined with single spaces.
+pub(crate) fn fold_phrase(phrase: &str) -> String {
+    phrase
+        .split_whitespace()
+        .map(fold_case)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A whitespace-separated token, located in both the original and the folded text.
+struct Token {
+    original: Range<usize>,
+    folded: Range<usize>,
+}
+
+/// Case-folds every token of `content`, joining them with single spaces.
+///
+/// Folding can change byte lengths, so each token keeps its range in both texts.
+fn fold_tokens(content: &str) -> (String, Vec<Token>) {
+    let mut folded = String::with_capacity(content.len());
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+
+    for piece in content.split_inclusive(char::is_whitespace) {
+        let token = piece.trim_end_matches(char::is_whitespace);
+        if !token.is_empty() {
+            let start = folded.len();
+            folded.push_str(&fold_case(token));
+            tokens.push(Token {
+                original: offset..offset + token.len(),
+                folded: start..folded.len(),
+            });
+            folded.push(' ');
+        }
+        offset += piece.len();
+    }
+
+    (folded, tokens)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::HashMap;
 
+    /// The content a viewer is shown and whether it was censored.
+    fn shown(censored: Censored) -> (String, bool) {
+        match censored {
+            Censored::Shown(content, was_censored) => (content, was_censored),
+            Censored::Hidden => panic!("the message should be shown"),
+        }
+    }
+
     fn make_config() -> Arc<FilterConfig> {
         let mut banned_words = HashMap::new();
         banned_words.insert("A".to_string(), vec!["bad".to_string(), "evil".to_string()]);
         banned_words.insert("B".to_string(), vec!["wrong".to_string()]);
 
-        Arc::new(FilterConfig { banned_words })
+        Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        })
     }
 
     #[test]
@@ -93,7 +329,7 @@ mod tests {
         let sender = "A".to_string();
         let receiver = "C".to_string();
         let (result, censored) =
-            filter.censor_message("This is bad", Some(&sender), Some(&receiver));
+            shown(filter.censor_message("This is bad", Some(&sender), Some(&receiver)));
         assert!(censored);
         assert_eq!(result, "This is ***");
     }
@@ -106,7 +342,7 @@ mod tests {
         let sender = "C".to_string();
         let receiver = "B".to_string();
         let (result, censored) =
-            filter.censor_message("This is wrong", Some(&sender), Some(&receiver));
+            shown(filter.censor_message("This is wrong", Some(&sender), Some(&receiver)));
         assert!(censored);
         assert_eq!(result, "This is ***");
     }
@@ -119,7 +355,7 @@ mod tests {
         let sender = "A".to_string();
         let receiver = "B".to_string();
         let (result, censored) =
-            filter.censor_message("bad and wrong", Some(&sender), Some(&receiver));
+            shown(filter.censor_message("bad and wrong", Some(&sender), Some(&receiver)));
         assert!(censored);
         assert_eq!(result, "*** and ***");
     }
@@ -132,8 +368,336 @@ mod tests {
         let sender = "A".to_string();
         let receiver = "B".to_string();
         let (result, censored) =
-            filter.censor_message("Hello world", Some(&sender), Some(&receiver));
+            shown(filter.censor_message("Hello world", Some(&sender), Some(&receiver)));
         assert!(!censored);
         assert_eq!(result, "Hello world");
     }
+
+    #[test]
+    fn test_substring_not_censored() {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("A".to_string(), vec!["class".to_string()]);
+        let filter = CensorshipFilter::new(Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        }));
+
+        let sender = "A".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("classic subclass", Some(&sender), None));
+        assert!(!censored);
+        assert_eq!(result, "classic subclass");
+
+        let (result, censored) = shown(filter.censor_message("class classic", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "*** classic");
+    }
+
+    #[test]
+    fn test_repeated_words() {
+        let config = make_config();
+        let filter = CensorshipFilter::new(config);
+
+        let sender = "A".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("bad bad evil bad", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "*** *** *** ***");
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let config = make_config();
+        let filter = CensorshipFilter::new(config);
+
+        let sender = "A".to_string();
+        let (result, censored) = shown(filter.censor_message("BAD Evil good", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "*** *** good");
+    }
+
+    #[test]
+    fn test_multibyte_words() {
+        let mut banned_words = HashMap::new();
+        banned_words.insert(
+            "A".to_string(),
+            vec![
+                "Évasion".to_string(),
+                "自由".to_string(),
+                "straße".to_string(),
+            ],
+        );
+        let filter = CensorshipFilter::new(Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        }));
+
+        let sender = "A".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("ÉVASION 自由 自由主義 STRASSE ok", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "*** *** 自由主義 *** ok");
+    }
+
+    #[test]
+    fn test_whitespace_preserved() {
+        let config = make_config();
+        let filter = CensorshipFilter::new(config);
+
+        let sender = "A".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("  bad\tgood  evil ", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "  ***\tgood  *** ");
+    }
+
+    #[test]
+    fn test_overlapping_ban_lists() {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("A".to_string(), vec!["class".to_string()]);
+        banned_words.insert(
+            "C".to_string(),
+            vec!["classic".to_string(), "class".to_string(), "cl".to_string()],
+        );
+        let filter = CensorshipFilter::new(Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        }));
+
+        let sender = "A".to_string();
+        let receiver = "B".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("class classic cl", Some(&sender), Some(&receiver)));
+        assert!(censored);
+        assert_eq!(result, "*** classic cl");
+
+        let sender = "C".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("class classic cl", Some(&sender), Some(&receiver)));
+        assert!(censored);
+        assert_eq!(result, "*** *** ***");
+    }
+
+    fn make_strategy_filter(strategy: CensorshipStrategy) -> CensorshipFilter {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("A".to_string(), vec!["bad".to_string()]);
+        banned_words.insert("B".to_string(), vec!["wrong".to_string()]);
+        let mut strategies = HashMap::new();
+        strategies.insert("A".to_string(), strategy);
+        let allowed_words: Vec<String> = ["bad", "wrong", "hello", "you"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        CensorshipFilter::new(Arc::new(FilterConfig {
+            banned_words,
+            strategies,
+            ..Default::default()
+        }))
+        .with_decoys(&allowed_words)
+    }
+
+    #[test]
+    fn test_strategy_drop_token() {
+        let filter = make_strategy_filter(CensorshipStrategy::DropToken);
+
+        let sender = "A".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("you  bad hello", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "you hello");
+
+        // Dropping every token still shows the message, unlike dropping it
+        assert_eq!(
+            filter.censor_message("bad bad", Some(&sender), None),
+            Censored::Shown(String::new(), true)
+        );
+    }
+
+    #[test]
+    fn test_strategy_drop_message() {
+        let filter = make_strategy_filter(CensorshipStrategy::DropMessage);
+
+        let sender = "A".to_string();
+        assert_eq!(
+            filter.censor_message("you bad hello", Some(&sender), None),
+            Censored::Hidden
+        );
+
+        let (result, censored) = shown(filter.censor_message("you hello", Some(&sender), None));
+        assert!(!censored);
+        assert_eq!(result, "you hello");
+    }
+
+    #[test]
+    fn test_strategy_decoy() {
+        let filter = make_strategy_filter(CensorshipStrategy::Decoy);
+
+        let sender = "A".to_string();
+        let (result, censored) =
+            shown(filter.censor_message_seeded("bad bad", Some(&sender), None, None, 7));
+        assert!(censored);
+        // Decoys are never words banned by any country
+        for word in result.split(' ') {
+            assert!(word == "hello" || word == "you", "unexpected decoy {word}");
+        }
+
+        // The same seed always picks the same decoys
+        let (again, _) =
+            shown(filter.censor_message_seeded("bad bad", Some(&sender), None, None, 7));
+        assert_eq!(result, again);
+    }
+
+    #[test]
+    fn test_strategy_decoy_without_candidates_masks() {
+        let mut strategies = HashMap::new();
+        strategies.insert("A".to_string(), CensorshipStrategy::Decoy);
+        let mut banned_words = HashMap::new();
+        banned_words.insert("A".to_string(), vec!["bad".to_string()]);
+        let filter = CensorshipFilter::new(Arc::new(FilterConfig {
+            banned_words,
+            strategies,
+            ..Default::default()
+        }));
+
+        let sender = "A".to_string();
+        let (result, censored) = shown(filter.censor_message("bad hello", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "*** hello");
+    }
+
+    #[test]
+    fn test_strategy_shuffle() {
+        let filter = make_strategy_filter(CensorshipStrategy::Shuffle);
+
+        let sender = "A".to_string();
+        let content = "one two bad three four five";
+        let (result, censored) =
+            shown(filter.censor_message_seeded(content, Some(&sender), None, None, 3));
+        assert!(censored);
+
+        let mut words: Vec<&str> = result.split(' ').collect();
+        words.sort();
+        assert_eq!(words, vec!["five", "four", "one", "three", "two"]);
+
+        let (again, _) = shown(filter.censor_message_seeded(content, Some(&sender), None, None, 3));
+        assert_eq!(result, again);
+
+        // Nothing banned, nothing shuffled
+        let (result, censored) = shown(filter.censor_message("one two three", Some(&sender), None));
+        assert!(!censored);
+        assert_eq!(result, "one two three");
+    }
+
+    #[test]
+    fn test_strategy_per_country() {
+        let filter = make_strategy_filter(CensorshipStrategy::DropToken);
+
+        // A drops its banned word while B keeps the default mask
+        let sender = "A".to_string();
+        let receiver = "B".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("bad wrong hello", Some(&sender), Some(&receiver)));
+        assert!(censored);
+        assert_eq!(result, "*** hello");
+    }
+
+    #[test]
+    fn test_route_rules() {
+        let mut routes = HashMap::new();
+        routes.insert(
+            "A".to_string(),
+            HashMap::from([("B".to_string(), vec!["freedom".to_string()])]),
+        );
+        let filter = CensorshipFilter::new(Arc::new(FilterConfig {
+            routes,
+            ..Default::default()
+        }));
+
+        let a = "A".to_string();
+        let b = "B".to_string();
+        let c = "C".to_string();
+
+        // A -> B blocks the word
+        let (result, censored) = shown(filter.censor_message_seeded(
+            "want freedom",
+            Some(&a),
+            Some(&b),
+            Some((&a, &b)),
+            0,
+        ));
+        assert!(censored);
+        assert_eq!(result, "want ***");
+
+        // B -> A and A -> C do not
+        let (result, censored) = shown(filter.censor_message_seeded(
+            "want freedom",
+            Some(&b),
+            Some(&a),
+            Some((&b, &a)),
+            0,
+        ));
+        assert!(!censored);
+        assert_eq!(result, "want freedom");
+        let (_, censored) = shown(filter.censor_message_seeded(
+            "want freedom",
+            Some(&a),
+            Some(&c),
+            Some((&a, &c)),
+            0,
+        ));
+        assert!(!censored);
+
+        // Without a route the rule never applies
+        let (_, censored) = shown(filter.censor_message("want freedom", Some(&a), Some(&b)));
+        assert!(!censored);
+    }
+
+    #[test]
+    fn test_phrase_censored_only_together() {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("A".to_string(), vec!["not obey".to_string()]);
+        let filter = CensorshipFilter::new(Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        }));
+
+        let sender = "A".to_string();
+        let (result, censored) =
+            shown(filter.censor_message("you not obey me", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "you *** *** me");
+
+        // Each symbol alone, or out of order, passes
+        let (result, censored) =
+            shown(filter.censor_message("not you obey obey not", Some(&sender), None));
+        assert!(!censored);
+        assert_eq!(result, "not you obey obey not");
+
+        // Case and spacing do not matter
+        let (result, censored) = shown(filter.censor_message("NOT   Obey", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "***   ***");
+    }
+
+    #[test]
+    fn test_phrase_overlapping_word_ban() {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("A".to_string(), vec!["obey".to_string()]);
+        banned_words.insert("C".to_string(), vec!["not obey".to_string()]);
+        let filter = CensorshipFilter::new(Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        }));
+
+        // C's phrase does not hide A's single-word ban
+        let sender = "A".to_string();
+        let (result, censored) = shown(filter.censor_message("not obey", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "not ***");
+
+        let sender = "C".to_string();
+        let (result, censored) = shown(filter.censor_message("not obey", Some(&sender), None));
+        assert!(censored);
+        assert_eq!(result, "*** ***");
+    }
 }
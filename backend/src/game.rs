@@ -1,9 +1,11 @@
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use crate::data::*;
-use crate::filter::CensorshipFilter;
-use crate::words::{generate_allowed_and_banned_words, load_words};
+use crate::filter::{Censored, CensorshipFilter, fold_phrase};
+use crate::packs::WordPack;
+use crate::words::{GenerationError, Words, generate_allowed_and_banned_words};
 
 /// Trait defining game-specific mechanics separate from chat room management.
 ///
@@ -18,9 +20,7 @@ pub trait GameRules: Send + Sync {
     fn is_word_allowed(&self, word: &str) -> bool;
 
     /// Apply censorship to a message for a specific viewer.
-    /// Returns (censored_content, was_censored)
-    fn censor_message_for(&self, message: &Message, viewer_country: &CountryCode)
-    -> (String, bool);
+    fn censor_message_for(&self, message: &Message, viewer_country: &CountryCode) -> Censored;
 
     /// Store player notes (hypotheses about banned words).
     fn submit_player_notes(&mut self, user_id: &UserId, notes: HashMap<CountryCode, Vec<String>>);
@@ -41,11 +41,42 @@ pub trait GameRules: Send + Sync {
     /// Get the current victory state.
     fn get_victory_state(&self, participants: &[Participant]) -> VictoryState;
 
+    /// Start the game clock; time-based mechanics count from here.
+    fn start(&mut self);
+
     /// Mark all countries as allowed (bypass censorship).
     fn unlock_all_countries(&mut self);
 
+    /// Apply scheduled escalations whose trigger has been reached, given the
+    /// number of messages players have sent so far.
+    /// Returns a notification for each escalation applied.
+    fn apply_escalations(&mut self, messages_sent: usize) -> Vec<Notification>;
+
     /// Get the filter config for this game.
     fn filter_config(&self) -> &FilterConfig;
+
+    /// Get the seed the game's words were generated from.
+    fn seed(&self) -> GameSeed;
+
+    /// Capture the state that changes during play.
+    fn snapshot(&self) -> GameSnapshot;
+
+    /// Restore state captured by `snapshot` onto a game generated from the same seed.
+    fn restore(&mut self, snapshot: GameSnapshot);
+}
+
+/// Game state that changes during play, saved so a game can be restored
+/// after a restart. Everything else is regenerated from the seed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    /// Ban lists, including words added by escalations.
+    pub banned_words: HashMap<CountryCode, Vec<String>>,
+    pub player_notes: HashMap<UserId, HashMap<CountryCode, Vec<String>>>,
+    pub allowed_countries: HashSet<CountryCode>,
+    pub victory_achieved: bool,
+    pub victory_timestamp: Option<Timestamp>,
+    pub started_at: Timestamp,
+    pub pending_escalations: Vec<Escalation>,
 }
 
 /// Implementation of censorship-based puzzle game rules.
@@ -73,15 +104,40 @@ pub struct CensorshipGame {
     victory_achieved: bool,
     /// Timestamp when victory was achieved.
     victory_timestamp: Option<Timestamp>,
+    /// Timestamp when the game was created, for time-triggered escalations.
+    started_at: Timestamp,
+    /// Scheduled escalations that have not fired yet.
+    pending_escalations: Vec<Escalation>,
+    /// Seed the allowed and banned words were generated from.
+    seed: GameSeed,
 }
 
 impl CensorshipGame {
     /// Create a new censorship game with generated words and default settings.
+    ///
+    /// Words come from the built-in pack; use `generate` for other packs or
+    /// settings.
     pub fn new(config: &FilterConfig) -> Self {
-        // Load words from words.json
-        let words = load_words("words.json");
-        let country_codes = ["A", "B", "C", "D"];
-        let (allowed_words, banned_map) = generate_allowed_and_banned_words(&words, &country_codes);
+        let seed = rand::random_range(0..=MAX_GENERATED_SEED);
+        Self::generate(
+            config,
+            &WordPack::builtin().words,
+            &RoomSettings::default(),
+            seed,
+        )
+        .expect("the built-in pack should hold the default puzzle")
+    }
+
+    /// Create a new censorship game whose words are drawn from `words` using `seed`.
+    /// The same seed, word list and generation parameters always produce the same puzzle.
+    pub fn generate(
+        config: &FilterConfig,
+        words: &Words,
+        settings: &RoomSettings,
+        seed: GameSeed,
+    ) -> Result<Self, GenerationError> {
+        let (allowed_words, banned_map) =
+            generate_allowed_and_banned_words(words, &settings.generation, seed)?;
 
         // Clone and update the config's banned_words for this game
         let mut config_owned = config.clone();
@@ -90,20 +146,24 @@ impl CensorshipGame {
                 .banned_words
                 .insert(country.clone(), banned.clone());
         }
+        let pending_escalations = config_owned.escalations.clone();
         let config_arc = Arc::new(config_owned);
 
-        Self {
-            filter: CensorshipFilter::new(Arc::clone(&config_arc)),
+        Ok(Self {
+            filter: CensorshipFilter::new(Arc::clone(&config_arc)).with_decoys(&allowed_words),
             config: config_arc,
             allowed_words,
-            sender_censor: true,
-            receiver_censor: true,
-            shadow_ban: false,
+            sender_censor: settings.sender_censor,
+            receiver_censor: settings.receiver_censor,
+            shadow_ban: settings.shadow_ban,
             allowed_countries: HashSet::new(),
             player_notes: HashMap::new(),
             victory_achieved: false,
             victory_timestamp: None,
-        }
+            started_at: Self::current_timestamp(),
+            pending_escalations,
+            seed,
+        })
     }
 
     /// Create a game instance for testing with custom configuration.
@@ -116,7 +176,8 @@ impl CensorshipGame {
         shadow_ban: bool,
     ) -> Self {
         Self {
-            filter: CensorshipFilter::new(Arc::clone(&config)),
+            filter: CensorshipFilter::new(Arc::clone(&config)).with_decoys(&allowed_words),
+            pending_escalations: config.escalations.clone(),
             config,
             allowed_words,
             sender_censor,
@@ -126,14 +187,65 @@ impl CensorshipGame {
             player_notes: HashMap::new(),
             victory_achieved: false,
             victory_timestamp: None,
+            started_at: Self::current_timestamp(),
+            seed: 0,
         }
     }
 
+    /// Count distinct bans named in any player's notes.
+    fn count_discoveries(&self) -> usize {
+        let required = self.answers_among(|_| true);
+        let discovered: HashSet<&(String, String)> = self
+            .player_notes
+            .values()
+            .flat_map(|notes| Self::named_answers(notes, &required))
+            .collect();
+        discovered.len()
+    }
+
+    /// The bans a solution has to name when only `active` countries are in
+    /// the room, as (answer key, folded word) pairs: each country's words
+    /// under its code and each route rule under its `route_key`, as in
+    /// `FilterConfig::answer_key`.
+    fn answers_among(&self, active: impl Fn(&CountryCode) -> bool) -> HashSet<(String, String)> {
+        let routes = self
+            .config
+            .routes
+            .iter()
+            .filter(|(sender, _)| active(sender))
+            .flat_map(|(sender, receivers)| {
+                receivers
+                    .iter()
+                    .filter(|(receiver, _)| active(receiver))
+                    .map(move |(receiver, words)| (route_key(sender, receiver), words))
+            });
+        self.config
+            .banned_words
+            .iter()
+            .filter(|(country, _)| active(country))
+            .map(|(country, words)| (country.clone(), words))
+            .chain(routes)
+            .flat_map(|(key, words)| words.iter().map(move |w| (key.clone(), fold_phrase(w))))
+            .collect()
+    }
+
+    /// The `required` answers a player's notes name under the right key. A
+    /// phrase is a single entry and only counts when written out whole.
+    fn named_answers<'a>(
+        notes: &HashMap<String, Vec<String>>,
+        required: &'a HashSet<(String, String)>,
+    ) -> impl Iterator<Item = &'a (String, String)> {
+        let named: HashSet<(String, String)> = notes
+            .iter()
+            .flat_map(|(key, words)| words.iter().map(move |w| (key.clone(), fold_phrase(w))))
+            .collect();
+        required
+            .iter()
+            .filter(move |answer| named.contains(*answer))
+    }
+
     fn current_timestamp() -> Timestamp {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
+        crate::utils::now()
     }
 }
 
@@ -146,14 +258,10 @@ impl GameRules for CensorshipGame {
         self.allowed_words.contains(&word.to_string())
     }
 
-    fn censor_message_for(
-        &self,
-        message: &Message,
-        viewer_country: &CountryCode,
-    ) -> (String, bool) {
+    fn censor_message_for(&self, message: &Message, viewer_country: &CountryCode) -> Censored {
         // System messages are never censored
         if message.sender_id == "SYSTEM" {
-            return (message.content.clone(), false);
+            return Censored::Shown(message.content.clone(), false);
         }
 
         let sender =
@@ -169,11 +277,14 @@ impl GameRules for CensorshipGame {
             None
         };
 
+        // Route rules are enforced by the receiving firewall
+        let route = receiver.map(|receiver| (&message.sender_country, receiver));
+
         if self.shadow_ban && &message.sender_country == viewer_country {
-            (message.content.clone(), false)
+            Censored::Shown(message.content.clone(), false)
         } else {
             self.filter
-                .censor_message(&message.content, sender, receiver)
+                .censor_message_seeded(&message.content, sender, receiver, route, message.id)
         }
     }
 
@@ -194,32 +305,18 @@ impl GameRules for CensorshipGame {
         let active_countries: HashSet<String> =
             participants.iter().map(|p| p.country.clone()).collect();
 
-        // Get only banned words for countries that are currently in the room
-        let all_banned_words: HashSet<String> = self
-            .config
-            .banned_words
-            .iter()
-            .filter(|(country, _)| active_countries.contains(*country))
-            .flat_map(|(_, words)| words)
-            .map(|s| s.to_lowercase())
-            .collect();
-
-        let total_required = all_banned_words.len();
+        // Only bans of countries that are currently in the room count, and
+        // route rules between two of those countries
+        let required = self.answers_among(|country| active_countries.contains(country));
+        let total_required = required.len();
 
         participants
             .iter()
             .map(|participant| {
-                let discovered_count =
-                    if let Some(notes) = self.player_notes.get(&participant.user_id) {
-                        // Collect all unique words from player's notes
-                        let discovered: HashSet<String> =
-                            notes.values().flatten().map(|s| s.to_lowercase()).collect();
-
-                        // Count how many match actual banned words
-                        discovered.intersection(&all_banned_words).count()
-                    } else {
-                        0
-                    };
+                let discovered_count = self
+                    .player_notes
+                    .get(&participant.user_id)
+                    .map_or(0, |notes| Self::named_answers(notes, &required).count());
 
                 PlayerProgress {
                     user_id: participant.user_id.clone(),
@@ -263,26 +360,125 @@ impl GameRules for CensorshipGame {
         }
     }
 
+    fn start(&mut self) {
+        self.started_at = Self::current_timestamp();
+    }
+
     fn unlock_all_countries(&mut self) {
         for country in self.config.banned_words.keys() {
             self.allowed_countries.insert(country.clone());
         }
+        // The puzzle is over, firewalls stop upgrading
+        self.pending_escalations.clear();
+    }
+
+    fn apply_escalations(&mut self, messages_sent: usize) -> Vec<Notification> {
+        if self.victory_achieved || self.pending_escalations.is_empty() {
+            return Vec::new();
+        }
+
+        let elapsed = Self::current_timestamp().saturating_sub(self.started_at);
+        let discoveries = self.count_discoveries();
+        let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_escalations)
+            .into_iter()
+            .partition(|escalation| match escalation.trigger {
+                EscalationTrigger::ElapsedSecs(secs) => elapsed >= secs,
+                EscalationTrigger::MessagesSent(count) => messages_sent >= count,
+                EscalationTrigger::Discoveries(count) => discoveries >= count,
+            });
+        self.pending_escalations = pending;
+
+        if due.is_empty() {
+            return Vec::new();
+        }
+
+        let mut config = (*self.config).clone();
+        let mut notifications = Vec::new();
+        for escalation in due {
+            let banned = config
+                .banned_words
+                .entry(escalation.country.clone())
+                .or_default();
+            for word in escalation.words {
+                if !banned.contains(&word) {
+                    banned.push(word);
+                }
+            }
+            notifications.push(Notification {
+                message: format!(
+                    "{}'s firewall has been upgraded",
+                    country_name(&escalation.country)
+                ),
+            });
+        }
+
+        // Recompile the filter so censorship, progress and victory follow the new bans
+        let config = Arc::new(config);
+        self.filter = CensorshipFilter::new(Arc::clone(&config)).with_decoys(&self.allowed_words);
+        self.config = config;
+
+        notifications
     }
 
     fn filter_config(&self) -> &FilterConfig {
         &self.config
     }
+
+    fn seed(&self) -> GameSeed {
+        self.seed
+    }
+
+    fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            banned_words: self.config.banned_words.clone(),
+            player_notes: self.player_notes.clone(),
+            allowed_countries: self.allowed_countries.clone(),
+            victory_achieved: self.victory_achieved,
+            victory_timestamp: self.victory_timestamp,
+            started_at: self.started_at,
+            pending_escalations: self.pending_escalations.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: GameSnapshot) {
+        if snapshot.banned_words != self.config.banned_words {
+            let config = Arc::new(FilterConfig {
+                banned_words: snapshot.banned_words,
+                ..(*self.config).clone()
+            });
+            self.filter =
+                CensorshipFilter::new(Arc::clone(&config)).with_decoys(&self.allowed_words);
+            self.config = config;
+        }
+        self.player_notes = snapshot.player_notes;
+        self.allowed_countries = snapshot.allowed_countries;
+        self.victory_achieved = snapshot.victory_achieved;
+        self.victory_timestamp = snapshot.victory_timestamp;
+        self.started_at = snapshot.started_at;
+        self.pending_escalations = snapshot.pending_escalations;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// The content a viewer is shown and whether it was censored.
+    fn shown(censored: Censored) -> (String, bool) {
+        match censored {
+            Censored::Shown(content, was_censored) => (content, was_censored),
+            Censored::Hidden => panic!("the message should be shown"),
+        }
+    }
+
     fn make_test_config() -> Arc<FilterConfig> {
         let mut banned_words = HashMap::new();
         banned_words.insert("A".to_string(), vec!["freedom".to_string()]);
         banned_words.insert("B".to_string(), vec!["monarchy".to_string()]);
-        Arc::new(FilterConfig { banned_words })
+        Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        })
     }
 
     #[test]
@@ -325,6 +521,7 @@ mod tests {
             user_id: "alice".to_string(),
             country: "A".to_string(),
             joined_at: 0,
+            disconnected_at: None,
         }];
 
         // No notes submitted yet
@@ -352,6 +549,7 @@ mod tests {
             user_id: "alice".to_string(),
             country: "A".to_string(),
             joined_at: 0,
+            disconnected_at: None,
         }];
 
         // Victory not achieved yet
@@ -383,12 +581,12 @@ mod tests {
         };
 
         // Viewed by country A (has "freedom" banned)
-        let (content, was_censored) = game.censor_message_for(&message, &"A".to_string());
+        let (content, was_censored) = shown(game.censor_message_for(&message, &"A".to_string()));
         assert!(was_censored);
         assert_eq!(content, "***");
 
         // Viewed by country B (doesn't have "freedom" banned)
-        let (content, was_censored) = game.censor_message_for(&message, &"B".to_string());
+        let (content, was_censored) = shown(game.censor_message_for(&message, &"B".to_string()));
         assert!(!was_censored);
         assert_eq!(content, "freedom");
     }
@@ -407,13 +605,312 @@ mod tests {
         };
 
         // Before unlock, censored
-        let (_, was_censored) = game.censor_message_for(&message, &"B".to_string());
+        let (_, was_censored) = shown(game.censor_message_for(&message, &"B".to_string()));
         assert!(was_censored);
 
         // After unlock, not censored
         game.unlock_all_countries();
-        let (content, was_censored) = game.censor_message_for(&message, &"B".to_string());
+        let (content, was_censored) = shown(game.censor_message_for(&message, &"B".to_string()));
         assert!(!was_censored);
         assert_eq!(content, "freedom");
     }
+
+    #[test]
+    fn test_censor_message_uses_country_strategy() {
+        let mut config = (*make_test_config()).clone();
+        config
+            .strategies
+            .insert("A".to_string(), CensorshipStrategy::DropMessage);
+        let game = CensorshipGame::new_for_test(Arc::new(config), vec![], false, true, false);
+
+        let message = Message {
+            id: 1,
+            sender_id: "alice".to_string(),
+            sender_country: "C".to_string(),
+            content: "hello freedom".to_string(),
+            timestamp: 0,
+        };
+
+        // A's firewall hides the whole message
+        assert_eq!(
+            game.censor_message_for(&message, &"A".to_string()),
+            Censored::Hidden
+        );
+
+        // B's firewall still masks by default
+        let mut message = message;
+        message.content = "hello monarchy".to_string();
+        let (content, was_censored) = shown(game.censor_message_for(&message, &"B".to_string()));
+        assert!(was_censored);
+        assert_eq!(content, "hello ***");
+    }
+
+    fn make_route_config() -> Arc<FilterConfig> {
+        let mut config = (*make_test_config()).clone();
+        config.routes.insert(
+            "A".to_string(),
+            HashMap::from([("B".to_string(), vec!["obey".to_string()])]),
+        );
+        Arc::new(config)
+    }
+
+    #[test]
+    fn test_censor_message_route_rule() {
+        let game = CensorshipGame::new_for_test(make_route_config(), vec![], true, true, false);
+
+        let mut message = Message {
+            id: 1,
+            sender_id: "alice".to_string(),
+            sender_country: "A".to_string(),
+            content: "obey".to_string(),
+            timestamp: 0,
+        };
+
+        // A -> B is censored
+        let (content, was_censored) = shown(game.censor_message_for(&message, &"B".to_string()));
+        assert!(was_censored);
+        assert_eq!(content, "***");
+
+        // A -> C and B -> A are not
+        let (_, was_censored) = shown(game.censor_message_for(&message, &"C".to_string()));
+        assert!(!was_censored);
+        message.sender_country = "B".to_string();
+        let (_, was_censored) = shown(game.censor_message_for(&message, &"A".to_string()));
+        assert!(!was_censored);
+    }
+
+    #[test]
+    fn test_progress_counts_route_rules() {
+        let mut game = CensorshipGame::new_for_test(make_route_config(), vec![], true, true, false);
+
+        let alice = Participant {
+            user_id: "alice".to_string(),
+            country: "A".to_string(),
+            joined_at: 0,
+            disconnected_at: None,
+        };
+        let bob = Participant {
+            user_id: "bob".to_string(),
+            country: "B".to_string(),
+            joined_at: 0,
+            disconnected_at: None,
+        };
+
+        // The route only counts while both of its countries are in the room
+        let progress = game.calculate_player_progress(std::slice::from_ref(&alice));
+        assert_eq!(progress[0].total_required, 1);
+        let participants = vec![alice, bob];
+        let progress = game.calculate_player_progress(&participants);
+        assert_eq!(progress[0].total_required, 3);
+
+        let mut notes = HashMap::new();
+        notes.insert("A".to_string(), vec!["freedom".to_string()]);
+        notes.insert("B".to_string(), vec!["monarchy".to_string()]);
+        game.submit_player_notes(&"alice".to_string(), notes.clone());
+        let progress = game.calculate_player_progress(&participants);
+        assert_eq!(progress[0].discovered_count, 2);
+        assert!(!progress[0].completed);
+
+        notes.insert(
+            route_key(&"A".to_string(), &"B".to_string()),
+            vec!["obey".to_string()],
+        );
+        game.submit_player_notes(&"alice".to_string(), notes);
+        let progress = game.calculate_player_progress(&participants);
+        assert!(progress[0].completed);
+    }
+
+    #[test]
+    fn test_progress_counts_answers_per_key() {
+        let mut config = (*make_test_config()).clone();
+        config
+            .banned_words
+            .get_mut("B")
+            .unwrap()
+            .push("freedom".to_string());
+        let mut game = CensorshipGame::new_for_test(Arc::new(config), vec![], true, true, false);
+        let participants: Vec<Participant> = [("alice", "A"), ("bob", "B")]
+            .iter()
+            .map(|(user_id, country)| Participant {
+                user_id: user_id.to_string(),
+                country: country.to_string(),
+                joined_at: 0,
+                disconnected_at: None,
+            })
+            .collect();
+        // A word banned in two countries is two answers
+        assert_eq!(
+            game.calculate_player_progress(&participants)[0].total_required,
+            3
+        );
+
+        // A guess filed under the wrong country does not count
+        let mut notes = HashMap::new();
+        notes.insert("A".to_string(), vec!["monarchy".to_string()]);
+        notes.insert("B".to_string(), vec!["freedom".to_string()]);
+        game.submit_player_notes(&"alice".to_string(), notes.clone());
+        let progress = game.calculate_player_progress(&participants);
+        assert_eq!(progress[0].discovered_count, 1);
+
+        notes.insert(
+            "A".to_string(),
+            vec!["freedom".to_string(), "monarchy".to_string()],
+        );
+        notes.insert(
+            "B".to_string(),
+            vec!["freedom".to_string(), "monarchy".to_string()],
+        );
+        game.submit_player_notes(&"alice".to_string(), notes);
+        let progress = game.calculate_player_progress(&participants);
+        assert_eq!(progress[0].discovered_count, 3);
+        assert!(progress[0].completed);
+    }
+
+    #[test]
+    fn test_answer_key_includes_routes() {
+        let config = make_route_config();
+        let answer_key = config.answer_key();
+        assert_eq!(answer_key.len(), 3);
+        assert_eq!(answer_key["A->B"], vec!["obey".to_string()]);
+        assert_eq!(answer_key["A"], vec!["freedom".to_string()]);
+    }
+
+    #[test]
+    fn test_progress_counts_phrase_as_one_unit() {
+        let mut banned_words = HashMap::new();
+        banned_words.insert(
+            "A".to_string(),
+            vec!["freedom".to_string(), "want power".to_string()],
+        );
+        let config = Arc::new(FilterConfig {
+            banned_words,
+            ..Default::default()
+        });
+        let mut game = CensorshipGame::new_for_test(config, vec![], true, true, false);
+
+        let participants = vec![Participant {
+            user_id: "alice".to_string(),
+            country: "A".to_string(),
+            joined_at: 0,
+            disconnected_at: None,
+        }];
+        assert_eq!(
+            game.calculate_player_progress(&participants)[0].total_required,
+            2
+        );
+
+        // The phrase's symbols on their own do not count
+        let mut notes = HashMap::new();
+        notes.insert(
+            "A".to_string(),
+            vec![
+                "freedom".to_string(),
+                "want".to_string(),
+                "power".to_string(),
+            ],
+        );
+        game.submit_player_notes(&"alice".to_string(), notes);
+        let progress = game.calculate_player_progress(&participants);
+        assert_eq!(progress[0].discovered_count, 1);
+
+        let mut notes = HashMap::new();
+        notes.insert(
+            "A".to_string(),
+            vec!["freedom".to_string(), "Want  Power".to_string()],
+        );
+        game.submit_player_notes(&"alice".to_string(), notes);
+        let progress = game.calculate_player_progress(&participants);
+        assert_eq!(progress[0].discovered_count, 2);
+        assert!(progress[0].completed);
+    }
+
+    fn make_escalation_config(trigger: EscalationTrigger) -> Arc<FilterConfig> {
+        let mut config = (*make_test_config()).clone();
+        config.escalations.push(Escalation {
+            trigger,
+            country: "A".to_string(),
+            words: vec!["obey".to_string()],
+        });
+        Arc::new(config)
+    }
+
+    fn obey_message() -> Message {
+        Message {
+            id: 1,
+            sender_id: "bob".to_string(),
+            sender_country: "C".to_string(),
+            content: "obey".to_string(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_escalation_by_messages_sent() {
+        let config = make_escalation_config(EscalationTrigger::MessagesSent(3));
+        let mut game = CensorshipGame::new_for_test(config, vec![], false, true, false);
+        let viewer = "A".to_string();
+
+        assert!(game.apply_escalations(2).is_empty());
+        let (_, was_censored) = shown(game.censor_message_for(&obey_message(), &viewer));
+        assert!(!was_censored);
+
+        let notifications = game.apply_escalations(3);
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(
+            notifications[0].message,
+            "Alveria's firewall has been upgraded"
+        );
+        let (_, was_censored) = shown(game.censor_message_for(&obey_message(), &viewer));
+        assert!(was_censored);
+        assert_eq!(
+            game.filter_config().banned_words["A"],
+            vec!["freedom".to_string(), "obey".to_string()]
+        );
+
+        // Each escalation fires once
+        assert!(game.apply_escalations(10).is_empty());
+    }
+
+    #[test]
+    fn test_escalation_by_elapsed_time() {
+        let config = make_escalation_config(EscalationTrigger::ElapsedSecs(60));
+        let mut game = CensorshipGame::new_for_test(config, vec![], false, true, false);
+
+        assert!(game.apply_escalations(0).is_empty());
+        game.started_at -= 60;
+        assert_eq!(game.apply_escalations(0).len(), 1);
+    }
+
+    #[test]
+    fn test_escalation_by_discoveries_updates_progress() {
+        let config = make_escalation_config(EscalationTrigger::Discoveries(1));
+        let mut game = CensorshipGame::new_for_test(config, vec![], false, true, false);
+
+        let participants = vec![Participant {
+            user_id: "alice".to_string(),
+            country: "A".to_string(),
+            joined_at: 0,
+            disconnected_at: None,
+        }];
+        let mut notes = HashMap::new();
+        notes.insert("A".to_string(), vec!["freedom".to_string()]);
+        game.submit_player_notes(&"alice".to_string(), notes);
+        assert!(game.calculate_player_progress(&participants)[0].completed);
+
+        // The discovery upgrades A's firewall, so the victory is no longer reached
+        assert_eq!(game.apply_escalations(0).len(), 1);
+        let progress = game.calculate_player_progress(&participants);
+        assert_eq!(progress[0].total_required, 2);
+        assert!(!progress[0].completed);
+        assert!(!game.check_victory(&participants));
+    }
+
+    #[test]
+    fn test_no_escalation_after_unlock() {
+        let config = make_escalation_config(EscalationTrigger::MessagesSent(1));
+        let mut game = CensorshipGame::new_for_test(config, vec![], false, true, false);
+
+        game.unlock_all_countries();
+        assert!(game.apply_escalations(5).is_empty());
+    }
 }
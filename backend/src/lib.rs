@@ -1,8 +1,16 @@
+pub mod accounts;
+pub mod archive;
 pub mod data;
+pub mod events;
 pub mod filter;
 pub mod game;
 pub mod manager;
+pub mod packs;
 pub mod room;
 pub mod server;
+pub mod store;
+pub mod tickets;
+pub mod tokens;
 pub mod utils;
+pub mod views;
 pub mod words;
@@ -1,25 +1,53 @@
+use babel::accounts::{Accounts, DEFAULT_TOKEN_TTL, FIXTURES_ENV, TOKEN_TTL_ENV};
 use babel::data::*;
 use babel::manager::RoomManager;
+use babel::packs::{DEFAULT_PACKS_DIR, PACKS_DIR_ENV, WordPack, WordPackRegistry};
 use babel::room::ChatRoom;
 use babel::server::{AppState, build_router};
-use dashmap::DashMap;
+use babel::store::{DB_PATH_ENV, DEFAULT_DB_PATH, SqliteRoomStore, StoredRoom};
+use babel::tickets::ConnectTickets;
+use babel::tokens::{TOKEN_KEY_ENV, TokenSigner};
+use babel::words::GenerationError;
 use once_cell::sync::Lazy;
+use std::env;
+use std::path::Path;
+use std::process;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tracing::info;
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 static FILTER_CONFIG: Lazy<FilterConfig> = Lazy::new(FilterConfig::default);
 
-pub struct DefaultRoomConfig;
+pub struct DefaultRoomConfig {
+    word_packs: WordPackRegistry,
+}
 
 impl RoomConfig for DefaultRoomConfig {
     fn get_filter_config(&self) -> &FilterConfig {
         &FILTER_CONFIG
     }
 
-    fn init_room(&self, room_id: RoomId) -> Box<dyn Room> {
-        Box::new(ChatRoom::new(room_id, &FILTER_CONFIG))
+    fn word_packs(&self) -> &WordPackRegistry {
+        &self.word_packs
+    }
+
+    fn init_room(
+        &self,
+        room_id: RoomId,
+        pack: &WordPack,
+        settings: &RoomSettings,
+    ) -> Result<Box<dyn Room>, GenerationError> {
+        let room = ChatRoom::generate(room_id, &FILTER_CONFIG, pack, settings)?;
+        Ok(Box::new(room))
+    }
+
+    fn restore_room(
+        &self,
+        pack: &WordPack,
+        stored: StoredRoom,
+    ) -> Result<Box<dyn Room>, GenerationError> {
+        Ok(Box::new(ChatRoom::restore(&FILTER_CONFIG, pack, stored)?))
     }
 }
 
@@ -35,11 +63,59 @@ async fn main() {
 
     info!("Initializing server");
 
-    let room_manager = RoomManager::from_config(DefaultRoomConfig);
+    let packs_dir = env::var(PACKS_DIR_ENV).unwrap_or_else(|_| DEFAULT_PACKS_DIR.to_string());
+    let word_packs = WordPackRegistry::load_dir(Path::new(&packs_dir)).unwrap_or_else(|err| {
+        error!(%err, "Failed to load word packs");
+        process::exit(1);
+    });
+    info!(packs_dir, packs = ?word_packs.list().iter().map(|p| &p.id).collect::<Vec<_>>(), "Loaded word packs");
+
+    let db_path = env::var(DB_PATH_ENV).unwrap_or_else(|_| DEFAULT_DB_PATH.to_string());
+    let store = SqliteRoomStore::open(&db_path).unwrap_or_else(|err| {
+        error!(db_path, %err, "Failed to open room database");
+        process::exit(1);
+    });
+
+    let store = Arc::new(store);
+    let room_manager = RoomManager::with_store(DefaultRoomConfig { word_packs }, store.clone());
+    match room_manager.restore_rooms().await {
+        Ok(restored) => info!(db_path, restored, "Restored saved rooms"),
+        Err(err) => {
+            error!(db_path, %err, "Failed to load saved rooms");
+            process::exit(1);
+        }
+    }
+
+    let token_ttl = match env::var(TOKEN_TTL_ENV) {
+        Ok(ttl) => ttl.parse().unwrap_or_else(|err| {
+            error!(ttl, %err, "Invalid token lifetime");
+            process::exit(1);
+        }),
+        Err(_) => DEFAULT_TOKEN_TTL,
+    };
+    let signer = match env::var(TOKEN_KEY_ENV) {
+        Ok(key) => TokenSigner::new(key).unwrap_or_else(|err| {
+            error!(%err, "Invalid token signing key");
+            process::exit(1);
+        }),
+        Err(_) => {
+            warn!("{TOKEN_KEY_ENV} is not set; tokens will not survive a restart");
+            TokenSigner::random()
+        }
+    };
+    let mut accounts = Accounts::load(store, signer, token_ttl).unwrap_or_else(|err| {
+        error!(db_path, %err, "Failed to load accounts");
+        process::exit(1);
+    });
+    if let Ok(fixtures_path) = env::var(FIXTURES_ENV) {
+        let loaded = accounts.load_fixtures(&fixtures_path);
+        info!(fixtures_path, loaded, "Loaded fixture accounts");
+    }
 
     let state = AppState {
         room_manager,
-        tokens_map: Arc::new(DashMap::new()),
+        accounts: Arc::new(accounts),
+        tickets: Arc::new(ConnectTickets::default()),
     };
 
     let app = build_router(state);
@@ -1,28 +1,141 @@
 use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
 use rand::distr::{Alphanumeric, SampleString};
-use std::sync::{Arc, Mutex};
-use tokio::sync::{mpsc, watch};
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, info, warn};
 
+use crate::archive::{ArchivedGame, GameId, GameSummary};
 use crate::data::*;
+use crate::events::{
+    LogEntry, ReplayError, RoomEvent, apply_event, verify_archived, verify_replay,
+};
+use crate::packs::{PackId, WordPackRegistry};
+use crate::store::{
+    ArchiveStore, MemoryRoomStore, MessageRead, RoomStore, StoreError, StoreWorker, Stores,
+};
+use crate::utils::{self, at_time};
+use crate::views::CensoredViews;
+use crate::words::GenerationError;
+
+/// Why a room could not be created.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CreateRoomError {
+    /// The room would admit no players.
+    NoPlayers,
+    /// No word pack has the requested id.
+    UnknownPack(PackId),
+    /// The pack cannot hold a puzzle with the requested parameters.
+    Generation(GenerationError),
+    /// A setting is outside the range rooms can be created with.
+    OutOfRange {
+        setting: &'static str,
+        min: u64,
+        max: u64,
+    },
+}
+
+impl fmt::Display for CreateRoomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoPlayers => write!(f, "a room must admit at least one player"),
+            Self::UnknownPack(id) => write!(f, "unknown word pack '{id}'"),
+            Self::Generation(err) => err.fmt(f),
+            Self::OutOfRange { setting, min, max } => {
+                write!(f, "{setting} must be between {min} and {max}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CreateRoomError {}
+
+impl From<GenerationError> for CreateRoomError {
+    fn from(err: GenerationError) -> Self {
+        Self::Generation(err)
+    }
+}
+
+/// How often a room runs its time-based mechanics when no actions arrive.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Updates a socket can fall behind by before it misses some.
+pub const UPDATE_BUFFER: usize = 256;
+
+/// Delivers a room's updates to its sockets. Every subscriber receives every
+/// update in order, unless it falls more than `UPDATE_BUFFER` updates behind.
+/// Alongside, it keeps the room's messages censored for each country, so
+/// sockets share the censoring work instead of each repeating it.
+#[derive(Clone)]
+pub struct RoomUpdates {
+    sender: broadcast::Sender<RoomUpdate>,
+    /// Version of the last update published. Publishing holds the lock, so
+    /// updates are sent in version order.
+    version: Arc<Mutex<u64>>,
+    /// The room as of the last update published.
+    views: Arc<RwLock<CensoredViews>>,
+}
+
+impl RoomUpdates {
+    fn new(room: &dyn Room) -> Self {
+        Self {
+            sender: broadcast::channel(UPDATE_BUFFER).0,
+            version: Arc::new(Mutex::new(0)),
+            views: Arc::new(RwLock::new(CensoredViews::new(room))),
+        }
+    }
+
+    /// Publish an update, numbered after the last one, once the censored
+    /// views have caught up with `room`.
+    pub fn publish(&self, room: &dyn Room, mut update: RoomUpdate) {
+        let mut version = self.version.lock().unwrap();
+        self.views.write().unwrap().refresh(room);
+        *version += 1;
+        update.version = *version;
+        // Sending only fails when no socket is subscribed
+        let _ = self.sender.send(update);
+    }
+
+    /// Receive the updates published from now on. Also returns the version
+    /// of the last update published before.
+    pub fn subscribe(&self) -> (u64, broadcast::Receiver<RoomUpdate>) {
+        let version = self.version.lock().unwrap();
+        (*version, self.sender.subscribe())
+    }
+
+    /// The room's messages censored for each country. The views may already
+    /// include updates a subscriber has not received yet.
+    pub fn views(&self) -> RwLockReadGuard<'_, CensoredViews> {
+        self.views.read().unwrap()
+    }
+}
 
 pub struct RoomRunner {
     room: Arc<Mutex<Box<dyn Room>>>,
     action_receiver: mpsc::Receiver<UserMessage>,
-    update_sender: watch::Sender<RoomUpdate>,
+    updates: RoomUpdates,
     room_manager: Arc<RoomManager>,
 }
 
 #[derive(Clone)]
 pub struct RoomConnector {
     pub action_sender: mpsc::Sender<UserMessage>,
-    pub update_receiver: watch::Receiver<RoomUpdate>,
-    pub update_sender: watch::Sender<RoomUpdate>,
+    pub updates: RoomUpdates,
     pub room: Arc<Mutex<Box<dyn Room>>>,
 }
 
 pub struct RoomManager {
     active_rooms: DashMap<RoomId, RoomConnector>,
     config: Box<dyn RoomConfig>,
+    store: StoreWorker,
+    /// Id of the last message saved for each room. The store worker updates
+    /// it once a save went through.
+    saved_messages: Arc<DashMap<RoomId, MessageId>>,
+    /// Id of the archived game of each open room whose game finished. The
+    /// store worker updates it once the game is archived.
+    archived_games: Arc<DashMap<RoomId, GameId>>,
 }
 
 impl RoomRunner {
@@ -37,32 +150,23 @@ impl RoomRunner {
 
         let mut new_messages = Vec::new();
         let mut notifications = Vec::new();
-        let mut room_closed = false;
+        let mut rejections = Vec::new();
 
         for user_message in user_messages {
             let mut room = self.room.lock().unwrap();
-            // Handle join if user not in room
-            if !room
-                .participants()
-                .iter()
-                .any(|p| p.user_id == user_message.user_id)
+            let user_id = user_message.user_id.clone();
+            match self
+                .room_manager
+                .apply_logged(room.as_mut(), RoomEvent::UserMessage(user_message))
             {
-                room.add_participant(user_message.user_id.clone(), user_message.country.clone());
-                notifications.push(Notification {
-                    message: format!("{} joined the room", user_message.user_id),
-                });
-            }
-
-            let (message, action_notifications) = room.process_action(
-                &user_message.user_id,
-                &user_message.country,
-                user_message.action,
-            );
-
-            if let Some(msg) = message {
-                new_messages.push(msg);
+                Ok((message, action_notifications)) => {
+                    if let Some(msg) = message {
+                        new_messages.push(msg);
+                    }
+                    notifications.extend(action_notifications);
+                }
+                Err(error) => rejections.push(ActionRejection { user_id, error }),
             }
-            notifications.extend(action_notifications);
         }
 
         // Check for victory condition after processing all actions
@@ -75,58 +179,371 @@ impl RoomRunner {
             }
         };
 
-        // Check if room should close
-        {
-            let room = self.room.lock().unwrap();
-            if room.is_empty() && !new_messages.is_empty() {
-                room_closed = true;
-            }
+        let mut room = self.room.lock().unwrap();
+        let room_closed = close_if_abandoned(room.as_mut());
+        if !room_closed {
+            self.room_manager.save_room(room.as_mut());
         }
 
-        // Create update for each connected participant's country
-        let room_state = {
-            let room = self.room.lock().unwrap();
-            room.get_censored_state_for(&"".to_string())
-        };
-
         let update = RoomUpdate {
-            room_state,
+            version: 0,
             new_messages,
             notifications,
             room_closed,
             victory: victory_state,
+            rejections,
+        };
+
+        self.updates.publish(room.as_ref(), update);
+        room_closed
+    }
+
+    /// Runs time-based mechanics and broadcasts their notifications, if any.
+    /// Returns whether the room closed because the last players timed out.
+    fn process_tick(&mut self) -> bool {
+        let mut room = self.room.lock().unwrap();
+        let Ok((_, notifications)) = self.room_manager.apply_logged_if(
+            room.as_mut(),
+            RoomEvent::Tick,
+            |result| matches!(result, Ok((_, notifications)) if !notifications.is_empty()),
+        ) else {
+            return false;
         };
+        if notifications.is_empty() {
+            return false;
+        }
+        let room_closed = close_if_abandoned(room.as_mut());
+        if !room_closed {
+            self.room_manager.save_room(room.as_mut());
+        }
 
-        self.update_sender.send_replace(update);
+        self.updates.publish(
+            room.as_ref(),
+            RoomUpdate {
+                version: 0,
+                new_messages: vec![],
+                notifications,
+                room_closed,
+                victory: None,
+                rejections: vec![],
+            },
+        );
         room_closed
     }
 
     fn run_in_background(mut self) {
         let room_id = self.room.lock().unwrap().room_id().clone();
         tokio::spawn(async move {
+            let mut tick = tokio::time::interval(TICK_INTERVAL);
             loop {
-                // Event-driven: wait for actions instead of tick-based loop
+                // Event-driven: wait for actions, with a periodic tick for timed mechanics
                 if self.action_receiver.is_closed() {
                     break;
                 }
 
-                if self.process_actions().await {
+                // process_actions only awaits while receiving, which is cancel-safe
+                let room_closed = tokio::select! {
+                    room_closed = self.process_actions() => room_closed,
+                    _ = tick.tick() => self.process_tick(),
+                };
+                if room_closed {
+                    self.room_manager.archive_room(&self.room, true).await;
                     self.room_manager.remove_room(&room_id);
                     break;
                 }
+                // Solve endpoints finish the game outside the runner, so
+                // look for a finished game after each round
+                let finished = self.room.lock().unwrap().phase() == RoomPhase::Finished;
+                if finished {
+                    self.room_manager.archive_room(&self.room, false).await;
+                }
             }
         });
     }
 }
 
+/// Closes the room once everyone who joined it has left or timed out. Rooms
+/// nobody joined yet stay open for their first players.
+fn close_if_abandoned(room: &mut dyn Room) -> bool {
+    let abandoned = room.is_empty() && !room.players().is_empty();
+    if abandoned {
+        room.close();
+    }
+    abandoned
+}
+
 impl RoomManager {
     pub fn from_config(config: impl RoomConfig + 'static) -> Arc<Self> {
+        Self::with_store(config, Arc::new(MemoryRoomStore::default()))
+    }
+
+    /// Create a manager that saves its rooms and archives their games in
+    /// `store`. Call `restore_rooms` to restart the rooms saved there.
+    pub fn with_store<S: RoomStore + ArchiveStore + 'static>(
+        config: impl RoomConfig + 'static,
+        store: Arc<S>,
+    ) -> Arc<Self> {
+        let stores = Stores {
+            rooms: store.clone(),
+            archive: store,
+        };
         Arc::new(RoomManager {
             active_rooms: Default::default(),
             config: Box::new(config),
+            store: StoreWorker::spawn(stores),
+            saved_messages: Default::default(),
+            archived_games: Default::default(),
         })
     }
 
+    /// Restarts every room saved in the store. Rooms whose word pack is gone
+    /// or whose puzzle can no longer be generated are skipped.
+    /// Returns the number of rooms restored.
+    pub async fn restore_rooms(self: &Arc<Self>) -> Result<usize, StoreError> {
+        let mut restored = 0;
+        let mut games = None;
+        for stored in self.store.run(|stores| stores.rooms.load_rooms()).await? {
+            let room_id = stored.record.room_id.clone();
+            let Some(pack) = self.config.word_packs().get(&stored.record.settings.pack) else {
+                warn!(room_id, pack = %stored.record.settings.pack, "Cannot restore room: unknown word pack");
+                continue;
+            };
+            let last_saved = stored.messages.last().map(|m| m.id);
+            // Finished games were archived when they finished, so find them
+            // to replace them when the room closes
+            let mut archived = None;
+            if stored.record.phase == RoomPhase::Finished {
+                if games.is_none() {
+                    games = Some(self.list_games().await?);
+                }
+                let started_at = stored.record.game.started_at;
+                archived = games
+                    .iter()
+                    .flatten()
+                    .rev()
+                    .find(|game| game.room_id == room_id && game.started_at == started_at);
+            }
+            match self.config.restore_room(&pack, stored) {
+                Ok(mut room) => {
+                    if let Some(id) = last_saved {
+                        self.saved_messages.insert(room_id.clone(), id);
+                        room.drop_saved_messages(id);
+                    }
+                    if let Some(game) = archived {
+                        self.archived_games.insert(room_id.clone(), game.id);
+                    }
+                    let connector = self.start_room(room);
+                    self.active_rooms.insert(room_id.clone(), connector);
+                    info!(room_id, "Restored room");
+                    restored += 1;
+                }
+                Err(err) => warn!(room_id, error = %err, "Cannot restore room"),
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Applies an event to a room and appends it to the room's log. Refused
+    /// actions are logged too: a join may have happened before the refusal.
+    pub fn apply_logged(
+        &self,
+        room: &mut dyn Room,
+        event: RoomEvent,
+    ) -> Result<(Option<Message>, Vec<Notification>), ActionError> {
+        self.apply_logged_if(room, event, |_| true)
+    }
+
+    /// Applies an event to a room, and appends it to the room's log only when
+    /// `changed` says it had an effect. Events without effect need no replay.
+    fn apply_logged_if(
+        &self,
+        room: &mut dyn Room,
+        event: RoomEvent,
+        changed: impl FnOnce(&Result<(Option<Message>, Vec<Notification>), ActionError>) -> bool,
+    ) -> Result<(Option<Message>, Vec<Notification>), ActionError> {
+        let timestamp = utils::now();
+        let result = at_time(timestamp, || apply_event(room, event.clone()));
+        if changed(&result) {
+            let entry = LogEntry {
+                timestamp,
+                seed: room.seed(),
+                event,
+            };
+            self.append_log(room.room_id(), entry);
+        }
+        result
+    }
+
+    /// Queues an entry for the room's log. Entries are appended in the
+    /// order they are queued.
+    fn append_log(&self, room_id: &RoomId, entry: LogEntry) {
+        let room_id = room_id.clone();
+        self.store.queue(move |stores| {
+            if let Err(err) = stores.rooms.append_log(&room_id, &entry) {
+                error!(room_id, error = %err, "Failed to log event");
+            }
+        });
+    }
+
+    /// Checks that replaying an active room's log rebuilds its current state.
+    /// Returns `None` when the room is not active. A room that changes while
+    /// its log is read fails the check, so check rooms nobody is playing in.
+    pub async fn verify_room(&self, room_id: &RoomId) -> Option<Result<(), ReplayError>> {
+        let connector = self.connect_to_room(room_id)?;
+        let log = {
+            // Queued under the lock, so the log ends with the room's last event
+            let _room = connector.room.lock().unwrap();
+            let room_id = room_id.clone();
+            self.store
+                .run(move |stores| stores.rooms.load_log(&room_id))
+        };
+        let result = log.await.map_err(ReplayError::Store).and_then(|log| {
+            let room = connector.room.lock().unwrap();
+            verify_replay(self.config.as_ref(), room.as_ref(), &log)
+        });
+        Some(result)
+    }
+
+    /// Queues the room's current state and any messages not saved yet for
+    /// saving, and lets the room drop the messages beyond its history cap
+    /// that earlier saves went through with.
+    /// Failures are logged so a broken store does not stop the game.
+    pub fn save_room(&self, room: &mut dyn Room) {
+        let room_id = room.room_id().clone();
+        let saved_through = self.saved_messages.get(&room_id).map_or(0, |id| *id);
+        room.drop_saved_messages(saved_through);
+
+        // Messages queued before but not saved yet are sent again; saving a
+        // message twice keeps one copy
+        let record = room.record();
+        let new_messages = room.messages_after(saved_through).to_vec();
+        let saved_messages = Arc::clone(&self.saved_messages);
+        self.store.queue(move |stores| {
+            if let Err(err) = stores.rooms.save_room(&record, &new_messages) {
+                error!(room_id, error = %err, "Failed to save room");
+                return;
+            }
+            if let Some(last) = new_messages.last() {
+                let mut saved = saved_messages.entry(room_id).or_default();
+                *saved = (*saved).max(last.id);
+            }
+        });
+    }
+
+    /// Reads a range of the room's messages, including those it no longer
+    /// keeps in memory. The room is not locked while the store reads.
+    pub async fn read_messages(
+        &self,
+        room: &Mutex<Box<dyn Room>>,
+        range: MessageRange,
+    ) -> Result<Vec<Message>, StoreError> {
+        let (read, stored) = {
+            let room = room.lock().unwrap();
+            let read = MessageRead::new(room.as_ref(), range);
+            // Queued under the lock, so every message the room let go of is
+            // saved by the time the read runs
+            let stored = read.stored.map(|older| {
+                let room_id = room.room_id().clone();
+                self.store
+                    .run(move |stores| stores.rooms.load_messages(&room_id, older))
+            });
+            (read, stored)
+        };
+        let stored = match stored {
+            Some(stored) => stored.await?,
+            None => Vec::new(),
+        };
+        Ok(read.finish(stored))
+    }
+
+    /// Archives the game of a room that finished or closed. A finished game is
+    /// archived once, then replaced when its room closes, so the archive keeps
+    /// the messages sent after the win and the time the room closed.
+    /// Failures are logged, and the game is then lost.
+    async fn archive_room(&self, room: &Mutex<Box<dyn Room>>, closing: bool) {
+        let all = MessageRange {
+            after: 0,
+            before: MessageId::MAX,
+            limit: usize::MAX,
+            from_oldest: true,
+        };
+        let (room_id, read, stored, log) = {
+            let room = room.lock().unwrap();
+            let room_id = room.room_id().clone();
+            if !closing && self.archived_games.contains_key(&room_id) {
+                return;
+            }
+            // Both queued under the lock, so the log ends with the event that
+            // added the last message read
+            let read = MessageRead::new(room.as_ref(), all);
+            let stored = {
+                let room_id = room_id.clone();
+                let older = read.stored;
+                self.store.run(move |stores| match older {
+                    Some(older) => stores.rooms.load_messages(&room_id, older),
+                    None => Ok(Vec::new()),
+                })
+            };
+            let log = {
+                let room_id = room_id.clone();
+                self.store
+                    .run(move |stores| stores.rooms.load_log(&room_id))
+            };
+            (room_id, read, stored, log)
+        };
+        let (messages, log) = match (stored.await, log.await) {
+            (Ok(stored), Ok(log)) => (read.finish(stored), log),
+            (Err(err), _) | (_, Err(err)) => {
+                error!(room_id, error = %err, "Failed to archive room");
+                return;
+            }
+        };
+        let game = {
+            let room = room.lock().unwrap();
+            ArchivedGame::from_room(room.as_ref(), messages, log, utils::now())
+        };
+        let archived_games = Arc::clone(&self.archived_games);
+        let archived = self.store.run(move |stores| {
+            let archived = archived_games.get(&game.summary.room_id).map(|id| *id);
+            match archived {
+                Some(game_id) => stores
+                    .archive
+                    .replace_game(game_id, &game)
+                    .map(|()| game_id),
+                None => {
+                    let game_id = stores.archive.archive_game(&game)?;
+                    archived_games.insert(game.summary.room_id.clone(), game_id);
+                    Ok(game_id)
+                }
+            }
+        });
+        match archived.await {
+            Ok(game_id) => info!(room_id, game_id, "Room archived"),
+            Err(err) => error!(room_id, error = %err, "Failed to archive room"),
+        }
+    }
+
+    /// Checks that replaying an archived game's log rebuilds its players and
+    /// messages. Returns `None` when there is no game with this id.
+    pub async fn verify_game(&self, id: GameId) -> Option<Result<(), ReplayError>> {
+        match self.load_game(id).await {
+            Ok(game) => game.map(|game| verify_archived(self.config.as_ref(), &game)),
+            Err(err) => Some(Err(ReplayError::Store(err))),
+        }
+    }
+
+    /// Summaries of the finished games in the archive.
+    pub async fn list_games(&self) -> Result<Vec<GameSummary>, StoreError> {
+        self.store.run(|stores| stores.archive.list_games()).await
+    }
+
+    /// A finished game from the archive, with its messages.
+    pub async fn load_game(&self, id: GameId) -> Result<Option<ArchivedGame>, StoreError> {
+        self.store
+            .run(move |stores| stores.archive.load_game(id))
+            .await
+    }
+
     pub fn get_filter_config(&self) -> &FilterConfig {
         self.config.get_filter_config()
     }
@@ -143,51 +560,441 @@ impl RoomManager {
         Some(self.active_rooms.get(&room_id)?.value().clone())
     }
 
-    pub fn create_and_run_room(self: Arc<Self>) -> RoomId {
+    /// Word packs new rooms can choose from.
+    pub fn word_packs(&self) -> &WordPackRegistry {
+        self.config.word_packs()
+    }
+
+    /// Creates and starts a room. Without a seed, a random one is generated.
+    pub fn create_and_run_room(
+        self: Arc<Self>,
+        settings: &RoomSettings,
+    ) -> Result<RoomId, CreateRoomError> {
         let room_id: RoomId = Alphanumeric.sample_string(&mut rand::rng(), 16);
-        self.create_room_with_id(room_id)
+        self.create_room_with_id(room_id, settings)
     }
 
-    pub fn create_room_with_id(self: Arc<Self>, room_id: RoomId) -> RoomId {
-        // Check if room already exists
-        if self.active_rooms.contains_key(&room_id) {
-            eprintln!("Room {} already exists, returning existing room", &room_id);
-            return room_id;
+    pub fn create_room_with_id(
+        self: Arc<Self>,
+        room_id: RoomId,
+        settings: &RoomSettings,
+    ) -> Result<RoomId, CreateRoomError> {
+        // The id stays reserved until the room runs, so rooms created with
+        // the same id at once end up as one room
+        let entry = match self.active_rooms.entry(room_id.clone()) {
+            Entry::Occupied(_) => {
+                debug!(room_id, "Room already exists, returning existing room");
+                return Ok(room_id);
+            }
+            Entry::Vacant(entry) => entry,
+        };
+
+        if settings.max_players == Some(0) {
+            return Err(CreateRoomError::NoPlayers);
+        }
+        // A room keeps at least one message, so snapshots are never empty
+        if !(1..=MAX_HISTORY_CAP).contains(&settings.history_cap) {
+            return Err(CreateRoomError::OutOfRange {
+                setting: "history_cap",
+                min: 1,
+                max: MAX_HISTORY_CAP as u64,
+            });
+        }
+        if settings.reconnect_grace > MAX_RECONNECT_GRACE {
+            return Err(CreateRoomError::OutOfRange {
+                setting: "reconnect_grace",
+                min: 0,
+                max: MAX_RECONNECT_GRACE,
+            });
         }
+        let pack = self
+            .config
+            .word_packs()
+            .get(&settings.pack)
+            .ok_or_else(|| CreateRoomError::UnknownPack(settings.pack.clone()))?;
+        let timestamp = utils::now();
+        let mut room = at_time(timestamp, || {
+            self.config.init_room(room_id.clone(), &pack, settings)
+        })?;
+        self.append_log(
+            &room_id,
+            LogEntry {
+                timestamp,
+                seed: room.seed(),
+                event: RoomEvent::Created {
+                    settings: room.settings().clone(),
+                },
+            },
+        );
+        info!(room_id, pack = %pack.id, seed = room.seed(), "Created room");
+        self.save_room(room.as_mut());
+        entry.insert(self.start_room(room));
+        Ok(room_id)
+    }
 
-        let room = Arc::new(Mutex::new(self.config.init_room(room_id.clone())));
+    /// Starts the runner for a room. The room can be connected to once its
+    /// connector is in `active_rooms`.
+    fn start_room(self: &Arc<Self>, room: Box<dyn Room>) -> RoomConnector {
+        let updates = RoomUpdates::new(room.as_ref());
+        let room = Arc::new(Mutex::new(room));
 
         let (action_sender, action_receiver) = mpsc::channel(MAX_USER_ACTIONS);
-        let (update_sender, update_receiver) = watch::channel(RoomUpdate {
-            room_state: room.lock().unwrap().get_censored_state_for(&"".to_string()),
-            new_messages: vec![],
-            notifications: vec![],
-            room_closed: false,
-            victory: None,
-        });
-
-        eprintln!("Created room {}", &room_id);
 
         let room_runner = RoomRunner {
             room: Arc::clone(&room),
             action_receiver,
-            update_sender: update_sender.clone(),
-            room_manager: Arc::clone(&self),
+            updates: updates.clone(),
+            room_manager: Arc::clone(self),
         };
         room_runner.run_in_background();
 
-        let room_connector = RoomConnector {
+        RoomConnector {
             action_sender,
-            update_receiver: update_receiver.clone(),
-            update_sender,
+            updates,
             room: Arc::clone(&room),
-        };
-        self.active_rooms.insert(room_id.clone(), room_connector);
-        room_id
+        }
     }
 
     fn remove_room(&self, room_id: &RoomId) {
-        eprintln!("Room {} closed", room_id);
+        info!(room_id, "Room closed");
         self.active_rooms.remove(room_id);
+        self.archived_games.remove(room_id);
+        // Saves still queued would mark the room saved again, so forget it
+        // once they ran
+        let room_id = room_id.clone();
+        let saved_messages = Arc::clone(&self.saved_messages);
+        self.store.queue(move |stores| {
+            saved_messages.remove(&room_id);
+            if let Err(err) = stores.rooms.delete_room(&room_id) {
+                error!(room_id, error = %err, "Failed to delete saved room");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::WordPack;
+    use crate::room::ChatRoom;
+    use crate::store::StoredRoom;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    struct TestConfig {
+        filter_config: FilterConfig,
+        word_packs: WordPackRegistry,
+    }
+
+    impl RoomConfig for TestConfig {
+        fn get_filter_config(&self) -> &FilterConfig {
+            &self.filter_config
+        }
+
+        fn word_packs(&self) -> &WordPackRegistry {
+            &self.word_packs
+        }
+
+        fn init_room(
+            &self,
+            room_id: RoomId,
+            pack: &WordPack,
+            settings: &RoomSettings,
+        ) -> Result<Box<dyn Room>, GenerationError> {
+            let room = ChatRoom::generate(room_id, &self.filter_config, pack, settings)?;
+            Ok(Box::new(room))
+        }
+
+        fn restore_room(
+            &self,
+            pack: &WordPack,
+            stored: StoredRoom,
+        ) -> Result<Box<dyn Room>, GenerationError> {
+            Ok(Box::new(ChatRoom::restore(
+                &self.filter_config,
+                pack,
+                stored,
+            )?))
+        }
+    }
+
+    fn make_manager() -> Arc<RoomManager> {
+        RoomManager::from_config(TestConfig {
+            filter_config: FilterConfig::default(),
+            word_packs: WordPackRegistry::default(),
+        })
+    }
+
+    fn make_room() -> ChatRoom {
+        ChatRoom::new("test_room".to_string(), &FilterConfig::default())
+    }
+
+    async fn act(connector: &RoomConnector, user_id: &str, action: SystemAction) {
+        let message = UserMessage {
+            user_id: user_id.to_string(),
+            country: String::new(),
+            action: UserAction::System(action),
+        };
+        connector.action_sender.send(message).await.unwrap();
+    }
+
+    /// Waits for the room's runner to stop and remove it.
+    async fn wait_for_removal(manager: &RoomManager, room_id: &RoomId) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while manager.connect_to_room(room_id).is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("room was not removed");
+    }
+
+    fn update(notification: &str) -> RoomUpdate {
+        RoomUpdate {
+            version: 0,
+            new_messages: vec![],
+            notifications: vec![Notification {
+                message: notification.to_string(),
+            }],
+            room_closed: false,
+            victory: None,
+            rejections: vec![],
+        }
+    }
+
+    #[test]
+    fn test_subscribers_receive_every_update_in_order() {
+        let room = make_room();
+        let updates = RoomUpdates::new(&room);
+        updates.publish(&room, update("before"));
+        let (version, mut receiver) = updates.subscribe();
+        assert_eq!(version, 1);
+
+        updates.publish(&room, update("first"));
+        updates.publish(&room, update("second"));
+        let first = receiver.try_recv().unwrap();
+        let second = receiver.try_recv().unwrap();
+        assert_eq!((first.version, second.version), (2, 3));
+        assert_eq!(first.notifications[0].message, "first");
+        assert_eq!(second.notifications[0].message, "second");
+    }
+
+    #[test]
+    fn test_subscriber_too_far_behind_is_told_what_it_missed() {
+        let room = make_room();
+        let updates = RoomUpdates::new(&room);
+        let (_, mut receiver) = updates.subscribe();
+        for _ in 0..UPDATE_BUFFER + 2 {
+            updates.publish(&room, update("hello"));
+        }
+        assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Lagged(2));
+        assert_eq!(receiver.try_recv().unwrap().version, 3);
+    }
+
+    #[test]
+    fn test_publishing_catches_views_up_with_the_room() {
+        let mut room = make_room();
+        let updates = RoomUpdates::new(&room);
+        let (_, mut receiver) = updates.subscribe();
+
+        room.win();
+        assert_eq!(updates.views().roster().phase, RoomPhase::Lobby);
+        updates.publish(&room, update("won"));
+        receiver.try_recv().unwrap();
+        let views = updates.views();
+        assert_eq!(views.roster().phase, RoomPhase::Finished);
+        let finished = views.messages_after(&"A".to_string(), 1);
+        assert_eq!(
+            finished[0].content,
+            "[SYSTEM] Censorship puzzle is finished!"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rooms_created_with_one_id_at_once_are_one_room() {
+        let manager = make_manager();
+        let room_id = "shared".to_string();
+        let creations: Vec<_> = (0..8)
+            .map(|_| {
+                let manager = Arc::clone(&manager);
+                let room_id = room_id.clone();
+                tokio::task::spawn_blocking(move || {
+                    manager.create_room_with_id(room_id, &RoomSettings::default())
+                })
+            })
+            .collect();
+        for creation in creations {
+            assert_eq!(creation.await.unwrap().unwrap(), room_id);
+        }
+
+        let log = {
+            let room_id = room_id.clone();
+            manager
+                .store
+                .run(move |stores| stores.rooms.load_log(&room_id))
+                .await
+                .unwrap()
+        };
+        let created = log
+            .iter()
+            .filter(|entry| matches!(entry.event, RoomEvent::Created { .. }))
+            .count();
+        assert_eq!(created, 1);
+    }
+
+    #[tokio::test]
+    async fn test_settings_out_of_range_are_rejected() {
+        let manager = make_manager();
+        let create = |settings: RoomSettings| Arc::clone(&manager).create_and_run_room(&settings);
+
+        let error = create(RoomSettings {
+            history_cap: 0,
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            CreateRoomError::OutOfRange {
+                setting: "history_cap",
+                ..
+            }
+        ));
+        assert!(
+            create(RoomSettings {
+                history_cap: MAX_HISTORY_CAP + 1,
+                ..Default::default()
+            })
+            .is_err()
+        );
+        let error = create(RoomSettings {
+            reconnect_grace: MAX_RECONNECT_GRACE + 1,
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "reconnect_grace must be between 0 and 3600"
+        );
+
+        assert!(
+            create(RoomSettings {
+                history_cap: MAX_HISTORY_CAP,
+                reconnect_grace: 0,
+                ..Default::default()
+            })
+            .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_room_closes_once_its_last_player_times_out() {
+        let manager = make_manager();
+        let settings = RoomSettings {
+            reconnect_grace: 1,
+            ..Default::default()
+        };
+        let room_id = Arc::clone(&manager).create_and_run_room(&settings).unwrap();
+        let connector = manager.connect_to_room(&room_id).unwrap();
+        let (_, mut receiver) = connector.updates.subscribe();
+        act(&connector, "alice", SystemAction::JoinRoom).await;
+        act(&connector, "alice", SystemAction::Disconnect).await;
+
+        // The seat is given up on a tick once the grace period ends, which
+        // closes the room
+        let closed = loop {
+            let update = receiver.recv().await.unwrap();
+            if update.room_closed {
+                break update;
+            }
+        };
+        assert_eq!(closed.notifications.len(), 1);
+        assert!(closed.notifications[0].message.contains("alice left"));
+        wait_for_removal(&manager, &room_id).await;
+        assert_eq!(connector.room.lock().unwrap().phase(), RoomPhase::Closed);
+
+        let games = manager.list_games().await.unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].room_id, room_id);
+    }
+
+    #[tokio::test]
+    async fn test_room_everyone_left_is_archived() {
+        let manager = make_manager();
+        let room_id = Arc::clone(&manager)
+            .create_and_run_room(&RoomSettings::default())
+            .unwrap();
+        let connector = manager.connect_to_room(&room_id).unwrap();
+        act(&connector, "alice", SystemAction::JoinRoom).await;
+        act(&connector, "alice", SystemAction::LeaveRoom).await;
+        wait_for_removal(&manager, &room_id).await;
+
+        let games = manager.list_games().await.unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].room_id, room_id);
+        assert!(!games[0].victory);
+        assert_eq!(games[0].players[0].user_id, "alice");
+        // The room's log lives on in the archive
+        assert!(matches!(
+            manager.verify_game(games[0].id).await,
+            Some(Ok(()))
+        ));
+        assert!(manager.verify_game(games[0].id + 1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_finished_game_is_archived_once() {
+        let manager = make_manager();
+        let room_id = Arc::clone(&manager)
+            .create_and_run_room(&RoomSettings::default())
+            .unwrap();
+        let connector = manager.connect_to_room(&room_id).unwrap();
+        let (_, mut receiver) = connector.updates.subscribe();
+        act(&connector, "alice", SystemAction::JoinRoom).await;
+        act(&connector, "alice", SystemAction::SetReady(true)).await;
+        act(&connector, "alice", SystemAction::StartGame).await;
+        while connector.updates.views().roster().phase != RoomPhase::InProgress {
+            receiver.recv().await.unwrap();
+        }
+        {
+            let mut room = connector.room.lock().unwrap();
+            manager.apply_logged(room.as_mut(), RoomEvent::Win).unwrap();
+            manager.save_room(room.as_mut());
+        }
+
+        // The game is listed while its players are still in the room
+        let games = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let games = manager.list_games().await.unwrap();
+                if !games.is_empty() {
+                    break games;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("game was not archived");
+        assert_eq!(games.len(), 1);
+        assert!(games[0].victory);
+
+        // Messages sent after the win are archived when the room closes
+        let word = connector.room.lock().unwrap().allowed_words()[0].clone();
+        let message = UserMessage {
+            user_id: "alice".to_string(),
+            country: String::new(),
+            action: UserAction::SendMessage(word.clone()),
+        };
+        connector.action_sender.send(message).await.unwrap();
+        act(&connector, "alice", SystemAction::LeaveRoom).await;
+        wait_for_removal(&manager, &room_id).await;
+        assert_eq!(manager.list_games().await.unwrap().len(), 1);
+        let game = manager.load_game(games[0].id).await.unwrap().unwrap();
+        assert_eq!(game.summary.room_id, room_id);
+        assert_eq!(game.summary.id, games[0].id);
+        assert_eq!(game.summary.message_count, games[0].message_count + 1);
+        let export = game.to_ndjson().unwrap();
+        let last_raw = export
+            .lines()
+            .rfind(|line| line.contains(r#""type":"message""#))
+            .unwrap();
+        assert!(last_raw.contains(&format!(r#""content":"{}""#, word)));
     }
 }
@@ -0,0 +1,408 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::filter::fold_case;
+use crate::words::{DEFAULT_COUNTRY_COUNT, Words};
+
+pub type PackId = String;
+
+/// Pack used when a room does not ask for one.
+pub const DEFAULT_PACK_ID: &str = "default";
+
+/// Environment variable naming the directory word packs are loaded from.
+pub const PACKS_DIR_ENV: &str = "BABEL_PACKS_DIR";
+
+/// Directory word packs are loaded from when `PACKS_DIR_ENV` is unset.
+pub const DEFAULT_PACKS_DIR: &str = "packs";
+
+/// Fewest non-empty censored groups a pack needs to hold a default game.
+pub const MIN_PACK_GROUPS: usize = DEFAULT_COUNTRY_COUNT;
+
+/// The pack shipped in `packs/default.json`, compiled into the binary.
+const BUILTIN_PACK: &str = include_str!("../packs/default.json");
+
+/// On-disk layout of a pack; the id comes from the file name.
+#[derive(Deserialize)]
+struct PackFile {
+    name: String,
+    language: String,
+    welcome: String,
+    #[serde(flatten)]
+    words: Words,
+}
+
+/// A validated word list together with its presentation text.
+#[derive(Clone, Debug)]
+pub struct WordPack {
+    pub id: PackId,
+    pub name: String,
+    /// Language tag of the words, e.g. "en".
+    pub language: String,
+    /// First system message shown in rooms using this pack.
+    pub welcome: String,
+    pub words: Words,
+}
+
+/// Public description of a pack, without its words.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PackInfo {
+    pub id: PackId,
+    pub name: String,
+    pub language: String,
+    pub welcome: String,
+}
+
+/// Why a pack could not be loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PackError {
+    /// The pack directory or a pack file could not be read.
+    Io { path: String, message: String },
+    /// A pack file is not a valid word pack.
+    Parse { pack: PackId, message: String },
+    /// A word is empty or contains whitespace.
+    InvalidWord { pack: PackId, word: String },
+    /// A word is listed more than once, ignoring case.
+    DuplicateWord { pack: PackId, word: String },
+    /// A censored word is also listed as a normal word.
+    CensoredWordIsNormal { pack: PackId, word: String },
+    /// The pack has too few non-empty censored groups for a default game.
+    NotEnoughGroups {
+        pack: PackId,
+        required: usize,
+        available: usize,
+    },
+    /// The directory holds no `DEFAULT_PACK_ID` pack.
+    MissingDefaultPack { dir: String },
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, message } => write!(f, "failed to read {path}: {message}"),
+            Self::Parse { pack, message } => write!(f, "pack '{pack}' is malformed: {message}"),
+            Self::InvalidWord { pack, word } => {
+                write!(f, "pack '{pack}' has an empty or multi-word entry '{word}'")
+            }
+            Self::DuplicateWord { pack, word } => {
+                write!(f, "pack '{pack}' lists '{word}' more than once")
+            }
+            Self::CensoredWordIsNormal { pack, word } => {
+                write!(
+                    f,
+                    "pack '{pack}' lists '{word}' as both normal and censored"
+                )
+            }
+            Self::NotEnoughGroups {
+                pack,
+                required,
+                available,
+            } => write!(
+                f,
+                "pack '{pack}' needs {required} censored groups but only has {available}"
+            ),
+            Self::MissingDefaultPack { dir } => {
+                write!(f, "{dir} has no '{DEFAULT_PACK_ID}' pack")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackError {}
+
+impl WordPack {
+    /// Parse and validate a pack from its JSON text.
+    pub fn parse(id: &str, json: &str) -> Result<Self, PackError> {
+        let file: PackFile = serde_json::from_str(json).map_err(|err| PackError::Parse {
+            pack: id.to_string(),
+            message: err.to_string(),
+        })?;
+        let pack = Self {
+            id: id.to_string(),
+            name: file.name,
+            language: file.language,
+            welcome: file.welcome,
+            words: file.words,
+        };
+        pack.validate()?;
+        Ok(pack)
+    }
+
+    /// Load a pack file, using its file name without extension as the id.
+    pub fn load(path: &Path) -> Result<Self, PackError> {
+        let id = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let json = fs::read_to_string(path).map_err(|err| PackError::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        })?;
+        Self::parse(&id, &json)
+    }
+
+    /// The default pack as compiled into the binary.
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT_PACK_ID, BUILTIN_PACK).expect("built-in word pack should be valid")
+    }
+
+    /// Check that every word is a single unique token and that there are
+    /// enough censored groups for a default game.
+    pub fn validate(&self) -> Result<(), PackError> {
+        let pack = || self.id.clone();
+        let mut normal = HashSet::new();
+        for word in &self.words.normal {
+            if !is_single_token(word) {
+                return Err(PackError::InvalidWord {
+                    pack: pack(),
+                    word: word.clone(),
+                });
+            }
+            if !normal.insert(fold_case(word)) {
+                return Err(PackError::DuplicateWord {
+                    pack: pack(),
+                    word: word.clone(),
+                });
+            }
+        }
+
+        let mut censored = HashSet::new();
+        for word in self.words.censored.iter().flatten() {
+            if !is_single_token(word) {
+                return Err(PackError::InvalidWord {
+                    pack: pack(),
+                    word: word.clone(),
+                });
+            }
+            let folded = fold_case(word);
+            if normal.contains(&folded) {
+                return Err(PackError::CensoredWordIsNormal {
+                    pack: pack(),
+                    word: word.clone(),
+                });
+            }
+            if !censored.insert(folded) {
+                return Err(PackError::DuplicateWord {
+                    pack: pack(),
+                    word: word.clone(),
+                });
+            }
+        }
+
+        let available = self
+            .words
+            .censored
+            .iter()
+            .filter(|group| !group.is_empty())
+            .count();
+        if available < MIN_PACK_GROUPS {
+            return Err(PackError::NotEnoughGroups {
+                pack: pack(),
+                required: MIN_PACK_GROUPS,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn info(&self) -> PackInfo {
+        PackInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            language: self.language.clone(),
+            welcome: self.welcome.clone(),
+        }
+    }
+}
+
+fn is_single_token(word: &str) -> bool {
+    !word.is_empty() && !word.contains(char::is_whitespace)
+}
+
+/// Every word pack available to new rooms, keyed by id.
+#[derive(Clone, Debug)]
+pub struct WordPackRegistry {
+    packs: HashMap<PackId, Arc<WordPack>>,
+}
+
+impl WordPackRegistry {
+    /// Load and validate every `*.json` pack in `dir`. Fails on the first
+    /// invalid pack, or when there is no default pack.
+    pub fn load_dir(dir: &Path) -> Result<Self, PackError> {
+        let io_error = |err: std::io::Error| PackError::Io {
+            path: dir.display().to_string(),
+            message: err.to_string(),
+        };
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        // Report errors in a stable order
+        paths.sort();
+
+        let packs = paths
+            .iter()
+            .map(|path| WordPack::load(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let registry = Self::from_packs(packs);
+        if registry.get(DEFAULT_PACK_ID).is_none() {
+            return Err(PackError::MissingDefaultPack {
+                dir: dir.display().to_string(),
+            });
+        }
+        Ok(registry)
+    }
+
+    /// Build a registry from packs that are already validated.
+    pub fn from_packs(packs: impl IntoIterator<Item = WordPack>) -> Self {
+        Self {
+            packs: packs
+                .into_iter()
+                .map(|pack| (pack.id.clone(), Arc::new(pack)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<WordPack>> {
+        self.packs.get(id).cloned()
+    }
+
+    /// Descriptions of all packs, sorted by id.
+    pub fn list(&self) -> Vec<PackInfo> {
+        let mut infos: Vec<PackInfo> = self.packs.values().map(|pack| pack.info()).collect();
+        infos.sort_by(|a, b| a.id.cmp(&b.id));
+        infos
+    }
+}
+
+impl Default for WordPackRegistry {
+    /// A registry holding only the built-in default pack.
+    fn default() -> Self {
+        Self::from_packs([WordPack::builtin()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack_json(normal: &str, censored: &str) -> String {
+        format!(
+            r#"{{"name": "Test", "language": "en", "welcome": "Hi",
+                "normal": {normal}, "censored": {censored}}}"#
+        )
+    }
+
+    const GROUPS: &str = r#"[["a1", "a2"], ["b1"], ["c1"], ["d1"]]"#;
+
+    #[test]
+    fn test_builtin_pack_is_valid() {
+        let pack = WordPack::builtin();
+        assert_eq!(pack.id, DEFAULT_PACK_ID);
+        assert!(!pack.welcome.is_empty());
+        assert!(pack.words.censored.len() >= MIN_PACK_GROUPS);
+    }
+
+    #[test]
+    fn test_parse_valid_pack() {
+        let pack = WordPack::parse("test", &pack_json(r#"["you", "me"]"#, GROUPS)).unwrap();
+        assert_eq!(pack.info().name, "Test");
+        assert_eq!(pack.info().language, "en");
+        assert_eq!(pack.words.normal, vec!["you", "me"]);
+    }
+
+    #[test]
+    fn test_malformed_pack_is_error() {
+        let result = WordPack::parse("test", r#"{"name": "Test"}"#);
+        assert!(matches!(result, Err(PackError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_duplicate_words_are_rejected() {
+        let result = WordPack::parse("test", &pack_json(r#"["you", "YOU"]"#, GROUPS));
+        assert!(matches!(result, Err(PackError::DuplicateWord { .. })));
+
+        let groups = r#"[["a1"], ["a1"], ["c1"], ["d1"]]"#;
+        let result = WordPack::parse("test", &pack_json(r#"["you"]"#, groups));
+        assert!(matches!(result, Err(PackError::DuplicateWord { .. })));
+    }
+
+    #[test]
+    fn test_censored_word_in_normal_is_rejected() {
+        let result = WordPack::parse("test", &pack_json(r#"["you", "b1"]"#, GROUPS));
+        assert_eq!(
+            result.unwrap_err(),
+            PackError::CensoredWordIsNormal {
+                pack: "test".to_string(),
+                word: "b1".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_words_are_rejected() {
+        let result = WordPack::parse("test", &pack_json(r#"["you", ""]"#, GROUPS));
+        assert!(matches!(result, Err(PackError::InvalidWord { .. })));
+
+        let groups = r#"[["a 1"], ["b1"], ["c1"], ["d1"]]"#;
+        let result = WordPack::parse("test", &pack_json(r#"["you"]"#, groups));
+        assert!(matches!(result, Err(PackError::InvalidWord { .. })));
+    }
+
+    #[test]
+    fn test_not_enough_groups_is_rejected() {
+        let groups = r#"[["a1"], ["b1"], [], ["d1"]]"#;
+        let result = WordPack::parse("test", &pack_json(r#"["you"]"#, groups));
+        assert_eq!(
+            result.unwrap_err(),
+            PackError::NotEnoughGroups {
+                pack: "test".to_string(),
+                required: MIN_PACK_GROUPS,
+                available: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("babel_packs_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("default.json"), BUILTIN_PACK).unwrap();
+        fs::write(dir.join("small.json"), pack_json(r#"["you"]"#, GROUPS)).unwrap();
+        fs::write(dir.join("notes.txt"), "not a pack").unwrap();
+
+        let registry = WordPackRegistry::load_dir(&dir).unwrap();
+        let ids: Vec<PackId> = registry.list().into_iter().map(|info| info.id).collect();
+        assert_eq!(ids, vec!["default", "small"]);
+        assert_eq!(registry.get("small").unwrap().words.normal, vec!["you"]);
+
+        fs::write(
+            dir.join("broken.json"),
+            pack_json(r#"["you", "you"]"#, GROUPS),
+        )
+        .unwrap();
+        let result = WordPackRegistry::load_dir(&dir);
+        assert!(matches!(result, Err(PackError::DuplicateWord { pack, .. }) if pack == "broken"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_dir_requires_default_pack() {
+        let dir = std::env::temp_dir().join(format!("babel_no_default_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("small.json"), pack_json(r#"["you"]"#, GROUPS)).unwrap();
+
+        let result = WordPackRegistry::load_dir(&dir);
+        assert!(matches!(result, Err(PackError::MissingDefaultPack { .. })));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
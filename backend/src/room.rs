@@ -1,13 +1,12 @@
 use crate::data::*;
 use crate::game::{CensorshipGame, GameRules};
+use crate::packs::{PackInfo, WordPack};
+use crate::store::{RoomRecord, StoredRoom};
+use crate::words::GenerationError;
 
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use tracing::{debug, trace};
 
-/// Game instructions message displayed when a room is created
-const GAME_INSTRUCTIONS: &str = "Welcome to Project Babel! You are trying to communicate across a censorship firewall. Each country has different words that are banned. Work together to discover which words are censored for each country using the allowed symbols. Good luck!";
-
 /// A chat room that manages participants, messages, and delegates game mechanics.
 ///
 /// `ChatRoom` handles real-time communication between players while delegating
@@ -27,37 +26,131 @@ pub struct ChatRoom {
     room_id: RoomId,
     /// List of participants currently in the room.
     participants: Vec<Participant>,
-    /// All messages sent in this room.
+    /// Everyone who has joined the room, in join order, including those who
+    /// have left since. Players who rejoin are listed once.
+    players: Vec<Participant>,
+    /// The newest messages, up to the history cap, and any not saved yet.
+    /// Older messages are only in the store.
     pub(crate) messages: Vec<Message>,
     /// Counter for generating unique message IDs.
     pub(crate) message_counter: MessageId,
+    /// Number of messages players sent, including those no longer kept in
+    /// memory. Escalations are triggered by it.
+    messages_sent: usize,
     /// Game rules implementation that handles game-specific logic.
     game: Box<dyn GameRules>,
+    /// Word pack the game's words were drawn from.
+    pack: PackInfo,
+    /// Settings the room was created with, including the seed used.
+    settings: RoomSettings,
+    /// Where the room is in its lifecycle.
+    phase: RoomPhase,
+    /// Participants who are ready for the host to start the game.
+    ready: BTreeSet<UserId>,
+    /// Pending seat swaps, from the player asking to the player asked.
+    swap_requests: HashMap<UserId, UserId>,
+    /// Number of times the censorship rules changed since the room started.
+    censorship_epoch: u64,
+    /// Open connections of each user. A player only counts as disconnected
+    /// once the last one closes. Sockets do not outlive the server, so this
+    /// is not saved.
+    connections: HashMap<UserId, usize>,
 }
 
 impl ChatRoom {
     pub fn new(room_id: RoomId, config: &FilterConfig) -> Self {
-        let game = CensorshipGame::new(config);
+        Self::from_game(
+            room_id,
+            CensorshipGame::new(config),
+            WordPack::builtin().info(),
+            RoomSettings::default(),
+        )
+    }
+
+    /// Create a room whose game is generated from `pack` according to `settings`.
+    /// Without a seed in `settings`, a random one is generated. Reusing a seed
+    /// with the same pack and settings replays the same puzzle.
+    pub fn generate(
+        room_id: RoomId,
+        config: &FilterConfig,
+        pack: &WordPack,
+        settings: &RoomSettings,
+    ) -> Result<Self, GenerationError> {
+        let seed = settings
+            .seed
+            .unwrap_or_else(|| rand::random_range(0..=MAX_GENERATED_SEED));
+        let game = CensorshipGame::generate(config, &pack.words, settings, seed)?;
+        Ok(Self::from_game(
+            room_id,
+            game,
+            pack.info(),
+            settings.clone(),
+        ))
+    }
 
-        // Create initial game instructions message
+    /// Rebuild a saved room: regenerate its game from the saved settings, then
+    /// put back the participants, messages and game state that changed in play.
+    pub fn restore(
+        config: &FilterConfig,
+        pack: &WordPack,
+        stored: StoredRoom,
+    ) -> Result<Self, GenerationError> {
+        let StoredRoom { record, messages } = stored;
+        let mut room = Self::generate(record.room_id, config, pack, &record.settings)?;
+        room.participants = record.participants;
+        room.players = record.players;
+        room.phase = record.phase;
+        room.ready = record.ready.into_iter().collect();
+        if !messages.is_empty() {
+            room.messages = messages;
+        }
+        room.message_counter = record.message_counter;
+        room.messages_sent = record.messages_sent;
+        room.game.restore(record.game);
+        Ok(room)
+    }
+
+    fn from_game(
+        room_id: RoomId,
+        game: CensorshipGame,
+        pack: PackInfo,
+        settings: RoomSettings,
+    ) -> Self {
+        // Open with the pack's welcome text as game instructions
         let game_instructions = Message {
             id: 1,
             sender_id: "SYSTEM".to_string(),
             sender_country: "".to_string(),
-            content: GAME_INSTRUCTIONS.to_string(),
+            content: pack.welcome.clone(),
             timestamp: Self::current_timestamp(),
         };
 
         Self {
             room_id,
             participants: Vec::new(),
+            players: Vec::new(),
             messages: vec![game_instructions],
             message_counter: 1,
+            messages_sent: 0,
+            settings: RoomSettings {
+                pack: pack.id.clone(),
+                seed: Some(game.seed()),
+                ..settings
+            },
             game: Box::new(game),
+            pack,
+            phase: RoomPhase::Lobby,
+            ready: BTreeSet::new(),
+            swap_requests: HashMap::new(),
+            censorship_epoch: 0,
+            connections: HashMap::new(),
         }
     }
 
     pub fn win(&mut self) {
+        if self.phase != RoomPhase::Closed {
+            self.phase = RoomPhase::Finished;
+        }
         let msg = "[SYSTEM] Censorship puzzle is finished!".to_string();
         self.message_counter += 1;
         self.messages.push(Message {
@@ -68,41 +161,235 @@ impl ChatRoom {
             timestamp: Self::current_timestamp(),
         });
         self.game.unlock_all_countries();
+        self.censorship_epoch += 1;
     }
 
     pub fn current_timestamp() -> Timestamp {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
+        crate::utils::now()
     }
 
-    pub fn add_participant(&mut self, user_id: UserId, country: CountryCode) -> bool {
-        if self.participants.iter().any(|p| p.user_id == user_id) {
+    /// Add a player, seated in one of the game's countries. Players who
+    /// rejoin get back the country they had. Others get `preference` if no
+    /// country has fewer players, otherwise the first country with the
+    /// fewest players. Once the game has started, only players who were in
+    /// it can join.
+    pub fn add_participant(&mut self, user_id: UserId, preference: CountryCode) -> bool {
+        let returning = self.players.iter().any(|p| p.user_id == user_id);
+        if self.participants.iter().any(|p| p.user_id == user_id)
+            || !self.settings.admits(self.participants.len())
+            || self.phase == RoomPhase::Closed
+            || (self.phase != RoomPhase::Lobby && !returning)
+        {
             return false;
         }
 
-        self.participants.push(Participant {
+        let country = self.assign_country(&user_id, preference);
+        let participant = Participant {
             user_id,
             country,
             joined_at: Self::current_timestamp(),
-        });
+            disconnected_at: None,
+        };
+        if !self
+            .players
+            .iter()
+            .any(|p| p.user_id == participant.user_id)
+        {
+            self.players.push(participant.clone());
+        }
+        self.participants.push(participant);
         true
     }
 
+    /// Everyone who has joined the room, including those who have left.
+    pub fn players(&self) -> &[Participant] {
+        &self.players
+    }
+
     pub fn remove_participant(&mut self, user_id: &UserId) -> bool {
         let initial_len = self.participants.len();
         self.participants.retain(|p| &p.user_id != user_id);
+        self.ready.remove(user_id);
+        self.drop_swap_requests(user_id);
         self.participants.len() < initial_len
     }
 
+    /// The participant who can start the game: the one who has been in the
+    /// room the longest.
+    pub fn host(&self) -> Option<&UserId> {
+        self.participants.first().map(|p| &p.user_id)
+    }
+
+    pub fn phase(&self) -> RoomPhase {
+        self.phase
+    }
+
+    /// Start the game without a ready check, for tests about play.
+    #[cfg(test)]
+    pub(crate) fn skip_lobby(&mut self) {
+        self.phase = RoomPhase::InProgress;
+    }
+
+    fn participant_mut(&mut self, user_id: &UserId) -> Option<&mut Participant> {
+        self.participants.iter_mut().find(|p| &p.user_id == user_id)
+    }
+
+    /// Country a current participant is seated in.
+    pub fn country_of(&self, user_id: &UserId) -> Option<&CountryCode> {
+        self.participants
+            .iter()
+            .find(|p| &p.user_id == user_id)
+            .map(|p| &p.country)
+    }
+
+    /// Number of current participants in each of the game's countries.
+    fn seats(&self) -> BTreeMap<CountryCode, usize> {
+        let mut seats: BTreeMap<CountryCode, usize> = self
+            .settings
+            .generation
+            .country_codes()
+            .into_iter()
+            .map(|country| (country, 0))
+            .collect();
+        for participant in &self.participants {
+            if let Some(count) = seats.get_mut(&participant.country) {
+                *count += 1;
+            }
+        }
+        seats
+    }
+
+    fn assign_country(&self, user_id: &UserId, preference: CountryCode) -> CountryCode {
+        if let Some(player) = self.players.iter().find(|p| &p.user_id == user_id) {
+            return player.country.clone();
+        }
+        let seats = self.seats();
+        let fewest = seats.values().min().copied().unwrap_or_default();
+        if seats.get(&preference) == Some(&fewest) {
+            return preference;
+        }
+        seats
+            .into_iter()
+            .find(|(_, count)| *count == fewest)
+            .map(|(country, _)| country)
+            .expect("games have at least one country")
+    }
+
+    /// Seat a participant in another country, keeping their entry in the
+    /// player list in step.
+    fn seat(&mut self, user_id: &UserId, country: &CountryCode) {
+        for player in self
+            .participants
+            .iter_mut()
+            .chain(self.players.iter_mut())
+            .filter(|p| &p.user_id == user_id)
+        {
+            player.country = country.clone();
+        }
+        self.drop_swap_requests(user_id);
+    }
+
+    fn drop_swap_requests(&mut self, user_id: &UserId) {
+        self.swap_requests
+            .retain(|from, to| from != user_id && to != user_id);
+    }
+
+    fn choose_country(
+        &mut self,
+        user_id: &UserId,
+        country: CountryCode,
+    ) -> Result<Vec<Notification>, ActionError> {
+        let seats = self.seats();
+        let current = self
+            .country_of(user_id)
+            .ok_or(ActionError::NotParticipant)?;
+        match (seats.get(current), seats.get(&country)) {
+            (Some(current), Some(target)) if target < current => {}
+            _ => return Err(ActionError::CountryUnavailable),
+        }
+        self.seat(user_id, &country);
+        Ok(vec![Notification {
+            message: format!("{} moved to country {}", user_id, country),
+        }])
+    }
+
+    fn request_swap(
+        &mut self,
+        user_id: &UserId,
+        other_id: UserId,
+    ) -> Result<Vec<Notification>, ActionError> {
+        let own = self
+            .country_of(user_id)
+            .ok_or(ActionError::NotParticipant)?
+            .clone();
+        let other = match self.country_of(&other_id) {
+            Some(other) if *other != own => other.clone(),
+            _ => return Err(ActionError::InvalidSwap),
+        };
+
+        if self.swap_requests.get(&other_id) == Some(user_id) {
+            self.seat(user_id, &other);
+            self.seat(&other_id, &own);
+            return Ok(vec![Notification {
+                message: format!("{} and {} swapped countries", user_id, other_id),
+            }]);
+        }
+        self.swap_requests.insert(user_id.clone(), other_id.clone());
+        Ok(vec![Notification {
+            message: format!("{} asked {} to swap countries", user_id, other_id),
+        }])
+    }
+
+    fn set_ready(
+        &mut self,
+        user_id: &UserId,
+        ready: bool,
+    ) -> Result<Vec<Notification>, ActionError> {
+        if self.country_of(user_id).is_none() {
+            return Err(ActionError::NotParticipant);
+        }
+        let changed = if ready {
+            self.ready.insert(user_id.clone())
+        } else {
+            self.ready.remove(user_id)
+        };
+        if !changed {
+            return Ok(Vec::new());
+        }
+        let status = if ready { "is ready" } else { "is not ready" };
+        Ok(vec![Notification {
+            message: format!("{} {}", user_id, status),
+        }])
+    }
+
+    fn start_game(&mut self, user_id: &UserId) -> Result<Vec<Notification>, ActionError> {
+        if self.host() != Some(user_id) {
+            return Err(ActionError::NotHost);
+        }
+        if self
+            .participants
+            .iter()
+            .any(|p| !self.ready.contains(&p.user_id))
+        {
+            return Err(ActionError::PlayersNotReady);
+        }
+        self.phase = RoomPhase::InProgress;
+        self.ready.clear();
+        self.swap_requests.clear();
+        // Time-based mechanics count from the start, not from the lobby
+        self.game.start();
+        Ok(vec![Notification {
+            message: format!("{} started the game", user_id),
+        }])
+    }
+
     /// Process a system action (messages, room management)
     fn process_system_action(
         &mut self,
         user_id: &UserId,
         country: &CountryCode,
         action: SystemAction,
-    ) -> (Option<Message>, Vec<Notification>) {
+    ) -> Result<(Option<Message>, Vec<Notification>), ActionError> {
         let mut notifications = Vec::new();
 
         match action {
@@ -114,9 +401,10 @@ impl ChatRoom {
                     .collect();
                 let content = filtered.join(" ");
                 if content.is_empty() {
-                    return (None, notifications);
+                    return Ok((None, notifications));
                 }
                 self.message_counter += 1;
+                self.messages_sent += 1;
                 let message = Message {
                     id: self.message_counter,
                     sender_id: user_id.clone(),
@@ -125,7 +413,7 @@ impl ChatRoom {
                     timestamp: Self::current_timestamp(),
                 };
                 self.messages.push(message.clone());
-                (Some(message), notifications)
+                Ok((Some(message), notifications))
             }
             SystemAction::SendMessage(content) => {
                 // split and filter
@@ -136,9 +424,10 @@ impl ChatRoom {
                     .collect();
                 let content = filtered.join(" ");
                 if content.is_empty() {
-                    return (None, notifications);
+                    return Ok((None, notifications));
                 }
                 self.message_counter += 1;
+                self.messages_sent += 1;
                 let message = Message {
                     id: self.message_counter,
                     sender_id: user_id.clone(),
@@ -147,7 +436,7 @@ impl ChatRoom {
                     timestamp: Self::current_timestamp(),
                 };
                 self.messages.push(message.clone());
-                (Some(message), notifications)
+                Ok((Some(message), notifications))
             }
             SystemAction::LeaveRoom => {
                 if self.remove_participant(user_id) {
@@ -155,8 +444,46 @@ impl ChatRoom {
                         message: format!("{} left the room", user_id),
                     });
                 }
-                (None, notifications)
+                Ok((None, notifications))
+            }
+            // Joining happens before the action is processed, so only a
+            // dropped connection is left to resume
+            SystemAction::JoinRoom => {
+                *self.connections.entry(user_id.clone()).or_default() += 1;
+                if let Some(participant) = self.participant_mut(user_id)
+                    && participant.disconnected_at.take().is_some()
+                {
+                    notifications.push(Notification {
+                        message: format!("{} reconnected", user_id),
+                    });
+                }
+                Ok((None, notifications))
+            }
+            // Players with another connection open are still there
+            SystemAction::Disconnect => {
+                let open = self.connections.remove(user_id).unwrap_or(0);
+                if open > 1 {
+                    self.connections.insert(user_id.clone(), open - 1);
+                    return Ok((None, notifications));
+                }
+                if let Some(participant) = self.participant_mut(user_id)
+                    && participant.disconnected_at.is_none()
+                {
+                    participant.disconnected_at = Some(Self::current_timestamp());
+                    notifications.push(Notification {
+                        message: format!("{} disconnected", user_id),
+                    });
+                }
+                Ok((None, notifications))
+            }
+            SystemAction::ChooseCountry(country) => {
+                Ok((None, self.choose_country(user_id, country)?))
+            }
+            SystemAction::RequestSwap(other_id) => {
+                Ok((None, self.request_swap(user_id, other_id)?))
             }
+            SystemAction::SetReady(ready) => Ok((None, self.set_ready(user_id, ready)?)),
+            SystemAction::StartGame => Ok((None, self.start_game(user_id)?)),
         }
     }
 
@@ -194,19 +521,41 @@ impl ChatRoom {
         }
     }
 
-    /// Pure function - processes action and returns results without I/O
+    /// Pure function - processes action and returns results without I/O.
+    /// Fails without changing the room if the action is not allowed.
     pub fn process_action(
         &mut self,
         user_id: &UserId,
         country: &CountryCode,
         action: UserAction,
-    ) -> (Option<Message>, Vec<Notification>) {
+    ) -> Result<(Option<Message>, Vec<Notification>), ActionError> {
+        if !self.phase.allows(&action) {
+            return Err(ActionError::WrongPhase(self.phase));
+        }
+        if action.is_play() && self.country_of(user_id).is_none() {
+            return Err(ActionError::NotParticipant);
+        }
+        // Participants act for the country they are seated in, not the one
+        // they asked for
+        let country = self.country_of(user_id).unwrap_or(country).clone();
+        let (message, mut notifications) = self.dispatch_action(user_id, &country, action)?;
+        // Messages sent and notes shared may trigger scheduled escalations
+        notifications.extend(self.tick());
+        Ok((message, notifications))
+    }
+
+    fn dispatch_action(
+        &mut self,
+        user_id: &UserId,
+        country: &CountryCode,
+        action: UserAction,
+    ) -> Result<(Option<Message>, Vec<Notification>), ActionError> {
         match action {
             // New structured actions
             UserAction::System(sys_action) => {
                 self.process_system_action(user_id, country, sys_action)
             }
-            UserAction::Game(game_action) => self.process_game_action(user_id, game_action),
+            UserAction::Game(game_action) => Ok(self.process_game_action(user_id, game_action)),
             // Legacy actions - convert to new format
             UserAction::SendMessageArray(words) => {
                 self.process_system_action(user_id, country, SystemAction::SendMessageArray(words))
@@ -218,11 +567,48 @@ impl ChatRoom {
                 self.process_system_action(user_id, country, SystemAction::LeaveRoom)
             }
             UserAction::SubmitNotes(note_map) => {
-                self.process_game_action(user_id, GameAction::SubmitNotes(note_map))
+                Ok(self.process_game_action(user_id, GameAction::SubmitNotes(note_map)))
             }
         }
     }
 
+    /// Remove participants whose reconnect grace period has ended, then
+    /// apply scheduled escalations that are due. Firewalls only escalate
+    /// while the game is in progress.
+    pub fn tick(&mut self) -> Vec<Notification> {
+        let mut notifications = self.expire_disconnected();
+        if self.phase != RoomPhase::InProgress {
+            return notifications;
+        }
+        let escalations = self.game.apply_escalations(self.messages_sent);
+        if !escalations.is_empty() {
+            self.censorship_epoch += 1;
+        }
+        notifications.extend(escalations);
+        notifications
+    }
+
+    fn expire_disconnected(&mut self) -> Vec<Notification> {
+        let now = Self::current_timestamp();
+        let grace = self.settings.reconnect_grace;
+        let expired: Vec<UserId> = self
+            .participants
+            .iter()
+            .filter(|p| {
+                p.disconnected_at
+                    .is_some_and(|at| now >= at.saturating_add(grace))
+            })
+            .map(|p| p.user_id.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter(|user_id| self.remove_participant(user_id))
+            .map(|user_id| Notification {
+                message: format!("{} left the room", user_id),
+            })
+            .collect()
+    }
+
     /// Get censored room state for a specific country
     pub fn get_censored_state_for(&self, country: &CountryCode) -> RoomState {
         debug!(
@@ -236,6 +622,8 @@ impl ChatRoom {
             .messages
             .iter()
             .map(|msg| self.censor_message_for(msg, country))
+            // Messages dropped by a firewall are hidden from the viewer entirely
+            .filter(|msg| !msg.hidden)
             .collect();
 
         let censored_count = censored_messages.iter().filter(|m| m.was_censored).count();
@@ -249,7 +637,7 @@ impl ChatRoom {
 
         RoomState {
             room_id: self.room_id.clone(),
-            participants: self.participants.clone(),
+            roster: self.roster(),
             recent_messages: censored_messages,
         }
     }
@@ -265,23 +653,20 @@ impl ChatRoom {
             "Processing message censorship"
         );
 
-        let (content, was_censored) = self.game.censor_message_for(message, country);
+        let censored =
+            CensoredMessage::new(message, self.game.censor_message_for(message, country));
 
         debug!(
             message_id = message.id,
             viewer_country = %country,
-            was_censored,
+            was_censored = censored.was_censored,
+            hidden = censored.hidden,
             original = %message.content,
-            result = %content,
+            result = %censored.content,
             "Censorship decision complete"
         );
 
-        CensoredMessage {
-            id: message.id,
-            sender_id: message.sender_id.clone(),
-            content,
-            was_censored,
-        }
+        censored
     }
 
     pub fn is_empty(&self) -> bool {
@@ -317,7 +702,11 @@ impl ChatRoom {
 
     /// Check if all players have discovered all banned words
     pub fn check_victory(&mut self) -> bool {
-        self.game.check_victory(&self.participants)
+        let won = self.game.check_victory(&self.participants);
+        if won && self.phase == RoomPhase::InProgress {
+            self.phase = RoomPhase::Finished;
+        }
+        won
     }
 
     /// Get current victory state
@@ -334,6 +723,21 @@ impl ChatRoom {
     pub fn allowed_words(&self) -> &[String] {
         self.game.allowed_words()
     }
+
+    /// Get the seed this room's game was generated from
+    pub fn seed(&self) -> GameSeed {
+        self.game.seed()
+    }
+
+    /// Get the word pack this room's game was generated from
+    pub fn pack(&self) -> &PackInfo {
+        &self.pack
+    }
+
+    /// Get the settings this room was created with
+    pub fn settings(&self) -> &RoomSettings {
+        &self.settings
+    }
 }
 
 impl Room for ChatRoom {
@@ -350,22 +754,19 @@ impl Room for ChatRoom {
     }
 
     fn add_participant(&mut self, user_id: UserId, country: CountryCode) -> bool {
-        if self.participants.iter().any(|p| p.user_id == user_id) {
-            return false;
-        }
+        ChatRoom::add_participant(self, user_id, country)
+    }
 
-        self.participants.push(Participant {
-            user_id,
-            country,
-            joined_at: Self::current_timestamp(),
-        });
-        true
+    fn country_of(&self, user_id: &UserId) -> Option<&CountryCode> {
+        ChatRoom::country_of(self, user_id)
+    }
+
+    fn players(&self) -> &[Participant] {
+        &self.players
     }
 
     fn remove_participant(&mut self, user_id: &UserId) -> bool {
-        let initial_len = self.participants.len();
-        self.participants.retain(|p| &p.user_id != user_id);
-        self.participants.len() < initial_len
+        ChatRoom::remove_participant(self, user_id)
     }
 
     fn process_action(
@@ -373,11 +774,32 @@ impl Room for ChatRoom {
         user_id: &UserId,
         country: &CountryCode,
         action: UserAction,
-    ) -> (Option<Message>, Vec<Notification>) {
+    ) -> Result<(Option<Message>, Vec<Notification>), ActionError> {
         // Delegate to the ChatRoom implementation
         ChatRoom::process_action(self, user_id, country, action)
     }
 
+    fn phase(&self) -> RoomPhase {
+        self.phase
+    }
+
+    fn close(&mut self) {
+        self.phase = RoomPhase::Closed;
+    }
+
+    fn roster(&self) -> RoomRoster {
+        RoomRoster {
+            phase: self.phase,
+            host: self.host().cloned(),
+            ready: self.ready.iter().cloned().collect(),
+            participants: self.participants.clone(),
+        }
+    }
+
+    fn censorship_epoch(&self) -> u64 {
+        self.censorship_epoch
+    }
+
     fn get_censored_state_for(&self, country: &CountryCode) -> RoomState {
         debug!(
             room_id = %self.room_id,
@@ -400,24 +822,23 @@ impl Room for ChatRoom {
                     "Processing message censorship"
                 );
 
-                let (content, was_censored) = self.game.censor_message_for(msg, country);
+                let censored =
+                    CensoredMessage::new(msg, self.game.censor_message_for(msg, country));
 
                 debug!(
                     message_id = msg.id,
                     viewer_country = %country,
-                    was_censored,
+                    was_censored = censored.was_censored,
+                    hidden = censored.hidden,
                     original = %msg.content,
-                    result = %content,
+                    result = %censored.content,
                     "Censorship decision complete"
                 );
 
-                CensoredMessage {
-                    id: msg.id,
-                    sender_id: msg.sender_id.clone(),
-                    content,
-                    was_censored,
-                }
+                censored
             })
+            // Messages dropped by a firewall are hidden from the viewer entirely
+            .filter(|msg| !msg.hidden)
             .collect();
 
         let censored_count = censored_messages.iter().filter(|m| m.was_censored).count();
@@ -431,7 +852,7 @@ impl Room for ChatRoom {
 
         RoomState {
             room_id: self.room_id.clone(),
-            participants: self.participants.clone(),
+            roster: self.roster(),
             recent_messages: censored_messages,
         }
     }
@@ -446,36 +867,28 @@ impl Room for ChatRoom {
             "Processing message censorship"
         );
 
-        let (content, was_censored) = self.game.censor_message_for(message, country);
+        let censored =
+            CensoredMessage::new(message, self.game.censor_message_for(message, country));
 
         debug!(
             message_id = message.id,
             viewer_country = %country,
-            was_censored,
+            was_censored = censored.was_censored,
+            hidden = censored.hidden,
             original = %message.content,
-            result = %content,
+            result = %censored.content,
             "Censorship decision complete"
         );
 
-        CensoredMessage {
-            id: message.id,
-            sender_id: message.sender_id.clone(),
-            content,
-            was_censored,
-        }
+        censored
     }
 
     fn win(&mut self) {
-        let msg = "[SYSTEM] Censorship puzzle is finished!".to_string();
-        self.message_counter += 1;
-        self.messages.push(Message {
-            id: self.message_counter,
-            sender_id: "SYSTEM".to_string(),
-            sender_country: "".to_string(),
-            content: msg,
-            timestamp: Self::current_timestamp(),
-        });
-        self.game.unlock_all_countries();
+        ChatRoom::win(self)
+    }
+
+    fn tick(&mut self) -> Vec<Notification> {
+        ChatRoom::tick(self)
     }
 
     fn get_player_notes(&self) -> &HashMap<UserId, HashMap<CountryCode, Vec<String>>> {
@@ -487,7 +900,7 @@ impl Room for ChatRoom {
     }
 
     fn check_victory(&mut self) -> bool {
-        self.game.check_victory(&self.participants)
+        ChatRoom::check_victory(self)
     }
 
     fn get_victory_state(&self) -> VictoryState {
@@ -501,18 +914,62 @@ impl Room for ChatRoom {
     fn allowed_words(&self) -> &[String] {
         self.game.allowed_words()
     }
+
+    fn seed(&self) -> GameSeed {
+        self.game.seed()
+    }
+
+    fn pack(&self) -> &PackInfo {
+        &self.pack
+    }
+
+    fn settings(&self) -> &RoomSettings {
+        &self.settings
+    }
+
+    fn record(&self) -> RoomRecord {
+        RoomRecord {
+            room_id: self.room_id.clone(),
+            settings: self.settings.clone(),
+            participants: self.participants.clone(),
+            players: self.players.clone(),
+            phase: self.phase,
+            ready: self.ready.iter().cloned().collect(),
+            message_counter: self.message_counter,
+            messages_sent: self.messages_sent,
+            game: self.game.snapshot(),
+        }
+    }
+
+    fn messages_after(&self, id: MessageId) -> &[Message] {
+        let start = self.messages.partition_point(|m| m.id <= id);
+        &self.messages[start..]
+    }
+
+    fn drop_saved_messages(&mut self, saved_through: MessageId) {
+        let excess = self
+            .messages
+            .len()
+            .saturating_sub(self.settings.history_cap);
+        let saved = self.messages.partition_point(|m| m.id <= saved_through);
+        self.messages.drain(..excess.min(saved));
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::words::GenerationParams;
     use std::collections::HashMap;
 
     fn make_test_config() -> FilterConfig {
         let mut banned_words = HashMap::new();
         banned_words.insert("A".to_string(), vec!["freedom".to_string()]);
         banned_words.insert("B".to_string(), vec!["monarchy".to_string()]);
-        FilterConfig { banned_words }
+        FilterConfig {
+            banned_words,
+            ..Default::default()
+        }
     }
 
     #[test]
@@ -523,6 +980,7 @@ mod tests {
         let user_id = "alice".to_string();
         let country = "A".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Create a note with suspected banned words
         let mut note_map = HashMap::new();
@@ -533,7 +991,7 @@ mod tests {
         note_map.insert("B".to_string(), vec!["monarchy".to_string()]);
 
         let action = UserAction::SubmitNotes(note_map.clone());
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         // Should not create a message
         assert!(message.is_none());
@@ -559,11 +1017,12 @@ mod tests {
         let user_id = "bob".to_string();
         let country = "B".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Send an empty note
         let note_map = HashMap::new();
         let action = UserAction::SubmitNotes(note_map);
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         // Should not create a message
         assert!(message.is_none());
@@ -583,6 +1042,7 @@ mod tests {
         let user_id = "charlie".to_string();
         let country = "C".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Send note for single country
         let mut note_map = HashMap::new();
@@ -596,7 +1056,7 @@ mod tests {
         );
 
         let action = UserAction::SubmitNotes(note_map);
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         assert!(message.is_none());
         assert_eq!(notifications.len(), 1);
@@ -613,13 +1073,14 @@ mod tests {
         let user_id = "diana".to_string();
         let country = "D".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Send note with single word
         let mut note_map = HashMap::new();
         note_map.insert("A".to_string(), vec!["freedom".to_string()]);
 
         let action = UserAction::SubmitNotes(note_map);
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         assert!(message.is_none());
         assert_eq!(notifications.len(), 1);
@@ -636,12 +1097,13 @@ mod tests {
         let user_id = "alice".to_string();
         let country = "A".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Send first note
         let mut note_map1 = HashMap::new();
         note_map1.insert("A".to_string(), vec!["freedom".to_string()]);
         let action1 = UserAction::SubmitNotes(note_map1.clone());
-        room.process_action(&user_id, &country, action1);
+        room.process_action(&user_id, &country, action1).unwrap();
 
         // Verify first note is stored
         assert_eq!(room.get_player_note(&user_id).unwrap(), &note_map1);
@@ -653,7 +1115,7 @@ mod tests {
             vec!["monarchy".to_string(), "tradition".to_string()],
         );
         let action2 = UserAction::SubmitNotes(note_map2.clone());
-        room.process_action(&user_id, &country, action2);
+        room.process_action(&user_id, &country, action2).unwrap();
 
         // Verify only latest note is stored
         let stored_note = room.get_player_note(&user_id).unwrap();
@@ -672,11 +1134,12 @@ mod tests {
         let user_id = "alice".to_string();
         let country = "A".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Test new SystemAction::SendMessage wrapped in UserAction::System
         // Use a word from the allowed words list
         let action = UserAction::System(SystemAction::SendMessage("hello".to_string()));
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         // Should create a message
         assert!(message.is_some());
@@ -692,6 +1155,7 @@ mod tests {
         let user_id = "bob".to_string();
         let country = "B".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Test new SystemAction::SendMessageArray wrapped in UserAction::System
         // Use words from the allowed words list
@@ -699,7 +1163,7 @@ mod tests {
             "hello".to_string(),
             "you".to_string(),
         ]));
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         // Should create a message
         assert!(message.is_some());
@@ -715,13 +1179,14 @@ mod tests {
         let user_id = "charlie".to_string();
         let country = "C".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Verify user is in room
         assert_eq!(room.participants.len(), 1);
 
         // Test new SystemAction::LeaveRoom wrapped in UserAction::System
         let action = UserAction::System(SystemAction::LeaveRoom);
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         // Should not create a message but should create notification
         assert!(message.is_none());
@@ -738,6 +1203,7 @@ mod tests {
         let user_id = "diana".to_string();
         let country = "D".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Create a note with suspected banned words
         let mut note_map = HashMap::new();
@@ -745,7 +1211,7 @@ mod tests {
 
         // Test new GameAction::SubmitNotes wrapped in UserAction::Game
         let action = UserAction::Game(GameAction::SubmitNotes(note_map.clone()));
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
 
         // Should not create a message
         assert!(message.is_none());
@@ -769,18 +1235,19 @@ mod tests {
         let user_id = "eve".to_string();
         let country = "E".to_string();
         room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
 
         // Test legacy UserAction::SendMessage still works
         // Use a word from the allowed words list
         let action = UserAction::SendMessage("hello".to_string());
-        let (message, _) = room.process_action(&user_id, &country, action);
+        let (message, _) = room.process_action(&user_id, &country, action).unwrap();
         assert!(message.is_some());
         assert_eq!(message.unwrap().content, "hello");
 
         // Test legacy UserAction::SendMessageArray still works
         // Use words from the allowed words list
         let action = UserAction::SendMessageArray(vec!["you".to_string(), "me".to_string()]);
-        let (message, _) = room.process_action(&user_id, &country, action);
+        let (message, _) = room.process_action(&user_id, &country, action).unwrap();
         assert!(message.is_some());
         assert_eq!(message.unwrap().content, "you me");
 
@@ -788,15 +1255,561 @@ mod tests {
         let mut note_map = HashMap::new();
         note_map.insert("A".to_string(), vec!["freedom".to_string()]);
         let action = UserAction::SubmitNotes(note_map.clone());
-        let (message, notifications) = room.process_action(&user_id, &country, action);
+        let (message, notifications) = room.process_action(&user_id, &country, action).unwrap();
         assert!(message.is_none());
         assert_eq!(notifications.len(), 1);
         assert_eq!(room.get_player_note(&user_id).unwrap(), &note_map);
 
         // Test legacy UserAction::LeaveRoom still works
         let action = UserAction::LeaveRoom;
-        let (_, notifications) = room.process_action(&user_id, &country, action);
+        let (_, notifications) = room.process_action(&user_id, &country, action).unwrap();
         assert_eq!(notifications.len(), 1);
         assert!(notifications[0].message.contains("eve left the room"));
     }
+
+    #[test]
+    fn test_escalation_notification_on_message() {
+        let mut config = make_test_config();
+        config.escalations.push(Escalation {
+            trigger: EscalationTrigger::MessagesSent(2),
+            country: "B".to_string(),
+            words: vec!["hello".to_string()],
+        });
+        let mut room = ChatRoom::new("test_room".to_string(), &config);
+
+        let user_id = "alice".to_string();
+        let country = "E".to_string();
+        room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
+
+        let action = UserAction::SendMessage("hello".to_string());
+        let (_, notifications) = room
+            .process_action(&user_id, &country, action.clone())
+            .unwrap();
+        assert!(notifications.is_empty());
+
+        let (_, notifications) = room.process_action(&user_id, &country, action).unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert!(notifications[0].message.contains("Brezna's firewall"));
+
+        // Earlier messages are censored under the upgraded ban list too
+        let state = room.get_censored_state_for(&"B".to_string());
+        assert!(state.recent_messages.iter().skip(1).all(|m| m.was_censored));
+    }
+
+    #[test]
+    fn test_only_participants_play() {
+        let config = make_test_config();
+        let mut room = ChatRoom::new("test_room".to_string(), &config);
+        room.add_participant("alice".to_string(), "A".to_string());
+        room.skip_lobby();
+
+        let mallory = "mallory".to_string();
+        let country = "A".to_string();
+        for action in [
+            UserAction::SendMessage("hello".to_string()),
+            UserAction::SendMessageArray(vec!["hello".to_string()]),
+            UserAction::SubmitNotes(HashMap::new()),
+        ] {
+            let error = room.process_action(&mallory, &country, action).unwrap_err();
+            assert_eq!(error, ActionError::NotParticipant);
+        }
+        assert_eq!(room.messages_after(0).len(), 1);
+        assert!(room.get_player_note(&mallory).is_none());
+    }
+
+    #[test]
+    fn test_escalation_counts_messages_past_history_cap() {
+        let mut config = make_test_config();
+        config.escalations.push(Escalation {
+            trigger: EscalationTrigger::MessagesSent(3),
+            country: "B".to_string(),
+            words: vec!["hello".to_string()],
+        });
+        let pack = WordPack::builtin();
+        let settings = RoomSettings {
+            history_cap: 1,
+            ..seeded_settings(5)
+        };
+        let mut room =
+            ChatRoom::generate("test_room".to_string(), &config, &pack, &settings).unwrap();
+
+        let user_id = "alice".to_string();
+        let country = "E".to_string();
+        room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
+
+        let action = UserAction::SendMessage("hello".to_string());
+        for _ in 0..2 {
+            let (_, notifications) = room
+                .process_action(&user_id, &country, action.clone())
+                .unwrap();
+            assert!(notifications.is_empty());
+            room.drop_saved_messages(room.message_counter);
+        }
+
+        // The count survives a restore from the saved record
+        let stored = StoredRoom {
+            record: room.record(),
+            messages: room.messages_after(0).to_vec(),
+        };
+        let mut restored = ChatRoom::restore(&config, &pack, stored).unwrap();
+        let (_, notifications) = restored.process_action(&user_id, &country, action).unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert!(notifications[0].message.contains("Brezna's firewall"));
+    }
+
+    fn seeded_settings(seed: GameSeed) -> RoomSettings {
+        RoomSettings {
+            seed: Some(seed),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_same_seed_replays_puzzle() {
+        let config = make_test_config();
+        let pack = WordPack::builtin();
+        let settings = seeded_settings(1234);
+        let first = ChatRoom::generate("room_1".to_string(), &config, &pack, &settings).unwrap();
+        let second = ChatRoom::generate("room_2".to_string(), &config, &pack, &settings).unwrap();
+
+        assert_eq!(first.seed(), 1234);
+        assert_eq!(first.allowed_words(), second.allowed_words());
+        assert_eq!(
+            first.filter_config().banned_words,
+            second.filter_config().banned_words
+        );
+    }
+
+    #[test]
+    fn test_generate_with_params() {
+        let config = make_test_config();
+        let pack = WordPack::builtin();
+        let mut settings = seeded_settings(1);
+        settings.generation = GenerationParams {
+            country_count: 2,
+            bans_per_country: 3,
+            allow_overlap: false,
+        };
+        let room = ChatRoom::generate("test_room".to_string(), &config, &pack, &settings).unwrap();
+        assert_eq!(room.filter_config().banned_words["A"].len(), 3);
+        assert_eq!(room.filter_config().banned_words["B"].len(), 3);
+
+        settings.generation = GenerationParams {
+            country_count: 8,
+            bans_per_country: 1,
+            allow_overlap: false,
+        };
+        let result = ChatRoom::generate("test_room".to_string(), &config, &pack, &settings);
+        assert!(matches!(
+            result,
+            Err(GenerationError::NotEnoughGroups { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pack_welcome_opens_room() {
+        let config = make_test_config();
+        let mut pack = WordPack::builtin();
+        pack.id = "custom".to_string();
+        pack.welcome = "Custom welcome".to_string();
+        let settings = seeded_settings(1);
+        let room = ChatRoom::generate("test_room".to_string(), &config, &pack, &settings).unwrap();
+
+        assert_eq!(room.pack().id, "custom");
+        assert_eq!(room.messages[0].sender_id, "SYSTEM");
+        assert_eq!(room.messages[0].content, "Custom welcome");
+    }
+
+    fn two_country_room() -> ChatRoom {
+        let settings = RoomSettings {
+            generation: GenerationParams {
+                country_count: 2,
+                ..Default::default()
+            },
+            ..seeded_settings(1)
+        };
+        ChatRoom::generate(
+            "test_room".to_string(),
+            &make_test_config(),
+            &WordPack::builtin(),
+            &settings,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_join_balances_countries() {
+        let mut room = two_country_room();
+        let join = |room: &mut ChatRoom, user: &str, preference: &str| {
+            let user_id = user.to_string();
+            room.add_participant(user_id.clone(), preference.to_string());
+            room.country_of(&user_id).cloned().unwrap()
+        };
+
+        // Countries the game does not have are never handed out
+        assert_eq!(join(&mut room, "alice", "Z"), "A");
+        // A preference for a fuller country gives way to balance
+        assert_eq!(join(&mut room, "bob", "A"), "B");
+        assert_eq!(join(&mut room, "carol", "B"), "B");
+        assert_eq!(join(&mut room, "dave", ""), "A");
+
+        // Messages are sent from the seat, whatever country is claimed
+        room.skip_lobby();
+        let (message, _) = room
+            .process_action(
+                &"bob".to_string(),
+                &"A".to_string(),
+                UserAction::SendMessage(room.allowed_words()[0].clone()),
+            )
+            .unwrap();
+        assert_eq!(message.unwrap().sender_country, "B");
+
+        // Rejoining players get their seat back
+        room.remove_participant(&"bob".to_string());
+        assert_eq!(join(&mut room, "bob", "A"), "B");
+    }
+
+    fn act(
+        room: &mut ChatRoom,
+        user: &str,
+        action: SystemAction,
+    ) -> Result<Vec<Notification>, ActionError> {
+        room.process_action(
+            &user.to_string(),
+            &String::new(),
+            UserAction::System(action),
+        )
+        .map(|(_, notifications)| notifications)
+    }
+
+    #[test]
+    fn test_lobby_seat_changes() {
+        let mut room = two_country_room();
+        for user in ["alice", "bob", "carol"] {
+            room.add_participant(user.to_string(), String::new());
+        }
+        let country = |room: &ChatRoom, user: &str| room.country_of(&user.to_string()).cloned();
+        assert_eq!(country(&room, "alice").as_deref(), Some("A"));
+        assert_eq!(country(&room, "bob").as_deref(), Some("B"));
+        assert_eq!(country(&room, "carol").as_deref(), Some("A"));
+
+        // Moving must not leave the new country with more players
+        assert_eq!(
+            act(&mut room, "bob", SystemAction::ChooseCountry("A".into())).unwrap_err(),
+            ActionError::CountryUnavailable
+        );
+        assert_eq!(
+            act(&mut room, "alice", SystemAction::ChooseCountry("Z".into())).unwrap_err(),
+            ActionError::CountryUnavailable
+        );
+        assert_eq!(
+            act(&mut room, "alice", SystemAction::ChooseCountry("B".into()))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(country(&room, "alice").as_deref(), Some("B"));
+        assert_eq!(room.players()[0].country, "B");
+
+        // A swap happens once both players have asked for it
+        assert_eq!(
+            act(&mut room, "alice", SystemAction::RequestSwap("bob".into())).unwrap_err(),
+            ActionError::InvalidSwap
+        );
+        let asked = act(&mut room, "carol", SystemAction::RequestSwap("bob".into())).unwrap();
+        assert!(asked[0].message.contains("asked"));
+        assert_eq!(country(&room, "carol").as_deref(), Some("A"));
+        let swapped = act(&mut room, "bob", SystemAction::RequestSwap("carol".into())).unwrap();
+        assert!(swapped[0].message.contains("swapped"));
+        assert_eq!(country(&room, "carol").as_deref(), Some("B"));
+        assert_eq!(country(&room, "bob").as_deref(), Some("A"));
+
+        // Seats are fixed once play has started
+        room.skip_lobby();
+        assert_eq!(
+            act(&mut room, "bob", SystemAction::RequestSwap("alice".into())).unwrap_err(),
+            ActionError::WrongPhase(RoomPhase::InProgress)
+        );
+    }
+
+    #[test]
+    fn test_host_starts_game_when_everyone_is_ready() {
+        let mut room = two_country_room();
+        room.add_participant("alice".to_string(), String::new());
+        room.add_participant("bob".to_string(), String::new());
+        let word = room.allowed_words()[0].clone();
+        assert_eq!(room.host().map(String::as_str), Some("alice"));
+
+        // Nothing is sent before the game starts
+        assert_eq!(
+            act(&mut room, "bob", SystemAction::SendMessage(word.clone())).unwrap_err(),
+            ActionError::WrongPhase(RoomPhase::Lobby)
+        );
+        assert_eq!(
+            act(&mut room, "bob", SystemAction::StartGame).unwrap_err(),
+            ActionError::NotHost
+        );
+        act(&mut room, "alice", SystemAction::SetReady(true)).unwrap();
+        assert_eq!(
+            act(&mut room, "alice", SystemAction::StartGame).unwrap_err(),
+            ActionError::PlayersNotReady
+        );
+        act(&mut room, "bob", SystemAction::SetReady(true)).unwrap();
+        assert_eq!(
+            room.roster().ready,
+            vec!["alice".to_string(), "bob".to_string()]
+        );
+
+        act(&mut room, "alice", SystemAction::StartGame).unwrap();
+        let roster = room.roster();
+        assert_eq!(roster.phase, RoomPhase::InProgress);
+        assert!(roster.ready.is_empty());
+        assert!(act(&mut room, "bob", SystemAction::SendMessage(word.clone())).is_ok());
+        assert_eq!(
+            act(&mut room, "bob", SystemAction::SetReady(false)).unwrap_err(),
+            ActionError::WrongPhase(RoomPhase::InProgress)
+        );
+
+        // Only the game's players can come back once it has started
+        assert!(!room.add_participant("carol".to_string(), String::new()));
+        room.remove_participant(&"alice".to_string());
+        assert_eq!(room.host().map(String::as_str), Some("bob"));
+        assert!(room.add_participant("alice".to_string(), String::new()));
+
+        room.win();
+        assert_eq!(room.phase(), RoomPhase::Finished);
+        assert!(act(&mut room, "bob", SystemAction::SendMessage(word)).is_ok());
+        assert_eq!(
+            room.process_action(
+                &"bob".to_string(),
+                &String::new(),
+                UserAction::SubmitNotes(HashMap::new())
+            )
+            .unwrap_err(),
+            ActionError::WrongPhase(RoomPhase::Finished)
+        );
+
+        Room::close(&mut room);
+        assert_eq!(
+            act(&mut room, "bob", SystemAction::LeaveRoom).unwrap_err(),
+            ActionError::WrongPhase(RoomPhase::Closed)
+        );
+    }
+
+    #[test]
+    fn test_disconnected_player_keeps_seat_for_grace_period() {
+        use crate::utils::at_time;
+
+        let mut room = two_country_room();
+        let grace = room.settings().reconnect_grace;
+        let alice = "alice".to_string();
+        at_time(1000, || {
+            room.add_participant(alice.clone(), String::new());
+            room.add_participant("bob".to_string(), String::new());
+            room.skip_lobby();
+            act(&mut room, "alice", SystemAction::Disconnect).unwrap()
+        });
+        assert_eq!(room.participants()[0].disconnected_at, Some(1000));
+
+        // Reconnecting within the grace period resumes the seat
+        let resumed = at_time(1010, || {
+            act(&mut room, "alice", SystemAction::JoinRoom).unwrap()
+        });
+        assert!(resumed[0].message.contains("reconnected"));
+        assert_eq!(room.participants()[0].disconnected_at, None);
+
+        at_time(1020, || {
+            act(&mut room, "alice", SystemAction::Disconnect).unwrap()
+        });
+        assert!(at_time(1020 + grace - 1, || room.tick()).is_empty());
+        assert_eq!(room.participants().len(), 2);
+        let expired = at_time(1020 + grace, || room.tick());
+        assert!(expired[0].message.contains("alice left"));
+        assert_eq!(room.country_of(&alice), None);
+
+        // Players who were in the game can still come back to their country
+        assert!(room.add_participant(alice.clone(), "B".to_string()));
+        assert_eq!(room.country_of(&alice).map(String::as_str), Some("A"));
+    }
+
+    #[test]
+    fn test_player_disconnects_when_last_connection_closes() {
+        let mut room = two_country_room();
+        room.add_participant("alice".to_string(), String::new());
+        room.skip_lobby();
+        act(&mut room, "alice", SystemAction::JoinRoom).unwrap();
+        act(&mut room, "alice", SystemAction::JoinRoom).unwrap();
+
+        // Closing one of two tabs leaves the player connected
+        assert!(
+            act(&mut room, "alice", SystemAction::Disconnect)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(room.participants()[0].disconnected_at, None);
+
+        let dropped = act(&mut room, "alice", SystemAction::Disconnect).unwrap();
+        assert!(dropped[0].message.contains("disconnected"));
+        assert!(room.participants()[0].disconnected_at.is_some());
+    }
+
+    #[test]
+    fn test_settings_record_resolved_seed() {
+        let config = make_test_config();
+        let pack = WordPack::builtin();
+        let room = ChatRoom::generate(
+            "test_room".to_string(),
+            &config,
+            &pack,
+            &RoomSettings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(room.settings().seed, Some(room.seed()));
+        assert_eq!(room.settings().pack, pack.id);
+    }
+
+    #[test]
+    fn test_max_players() {
+        let config = make_test_config();
+        let pack = WordPack::builtin();
+        let settings = RoomSettings {
+            max_players: Some(2),
+            ..seeded_settings(1)
+        };
+        let mut room =
+            ChatRoom::generate("test_room".to_string(), &config, &pack, &settings).unwrap();
+
+        assert!(room.add_participant("alice".to_string(), "A".to_string()));
+        assert!(room.add_participant("bob".to_string(), "B".to_string()));
+        assert!(!room.add_participant("carol".to_string(), "C".to_string()));
+
+        room.remove_participant(&"bob".to_string());
+        assert!(room.add_participant("carol".to_string(), "C".to_string()));
+    }
+
+    #[test]
+    fn test_settings_control_censorship_modes() {
+        let config = make_test_config();
+        let pack = WordPack::builtin();
+        let settings = RoomSettings {
+            sender_censor: false,
+            receiver_censor: false,
+            ..seeded_settings(1)
+        };
+        let mut room =
+            ChatRoom::generate("test_room".to_string(), &config, &pack, &settings).unwrap();
+        let banned = room.filter_config().banned_words["A"][0].clone();
+        room.add_participant("alice".to_string(), "A".to_string());
+        room.add_participant("bob".to_string(), "B".to_string());
+        room.messages.push(Message {
+            id: 2,
+            sender_id: "alice".to_string(),
+            sender_country: "A".to_string(),
+            content: banned.clone(),
+            timestamp: 0,
+        });
+
+        let state = room.get_censored_state_for(&"B".to_string());
+        let message = state.recent_messages.last().unwrap();
+        assert!(!message.was_censored);
+        assert_eq!(message.content, banned);
+    }
+
+    #[test]
+    fn test_restore_from_record() {
+        let mut config = make_test_config();
+        config.escalations.push(Escalation {
+            trigger: EscalationTrigger::MessagesSent(1),
+            country: "B".to_string(),
+            words: vec!["hello".to_string()],
+        });
+        let pack = WordPack::builtin();
+        let mut room =
+            ChatRoom::generate("test_room".to_string(), &config, &pack, &seeded_settings(9))
+                .unwrap();
+        let user_id = "alice".to_string();
+        let country = "A".to_string();
+        room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
+        room.process_action(
+            &user_id,
+            &country,
+            UserAction::SendMessage("hello you".to_string()),
+        )
+        .unwrap();
+        let mut notes = HashMap::new();
+        notes.insert("B".to_string(), vec!["hello".to_string()]);
+        room.process_action(&user_id, &country, UserAction::SubmitNotes(notes))
+            .unwrap();
+
+        let stored = StoredRoom {
+            record: room.record(),
+            messages: room.messages_after(0).to_vec(),
+        };
+        let restored = ChatRoom::restore(&config, &pack, stored).unwrap();
+
+        assert_eq!(restored.record(), room.record());
+        assert_eq!(restored.messages, room.messages);
+        assert_eq!(restored.allowed_words(), room.allowed_words());
+        assert!(restored.filter_config().banned_words["B"].contains(&"hello".to_string()));
+        let contents = |room: &ChatRoom| -> Vec<String> {
+            room.get_censored_state_for(&"B".to_string())
+                .recent_messages
+                .into_iter()
+                .map(|m| m.content)
+                .collect()
+        };
+        assert_eq!(contents(&restored), contents(&room));
+        assert_eq!(restored.get_player_notes(), room.get_player_notes());
+    }
+
+    #[test]
+    fn test_messages_after() {
+        let config = make_test_config();
+        let mut room = ChatRoom::new("test_room".to_string(), &config);
+        let user_id = "alice".to_string();
+        let country = "A".to_string();
+        room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
+        room.process_action(&user_id, &country, UserAction::SendMessage("hello".into()))
+            .unwrap();
+        room.process_action(&user_id, &country, UserAction::SendMessage("you".into()))
+            .unwrap();
+
+        assert_eq!(room.messages_after(0).len(), 3);
+        let ids: Vec<MessageId> = room.messages_after(1).iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert!(room.messages_after(3).is_empty());
+    }
+
+    #[test]
+    fn test_drop_saved_messages_keeps_history_cap() {
+        let config = make_test_config();
+        let mut room = ChatRoom::new("test_room".to_string(), &config);
+        room.settings.history_cap = 2;
+        let user_id = "alice".to_string();
+        let country = "A".to_string();
+        room.add_participant(user_id.clone(), country.clone());
+        room.skip_lobby();
+        for _ in 0..3 {
+            room.process_action(&user_id, &country, UserAction::SendMessage("hello".into()))
+                .unwrap();
+        }
+        let ids = |room: &ChatRoom| -> Vec<MessageId> {
+            room.messages_after(0).iter().map(|m| m.id).collect()
+        };
+
+        // Only saved messages are let go of
+        room.drop_saved_messages(1);
+        assert_eq!(ids(&room), vec![2, 3, 4]);
+        room.drop_saved_messages(4);
+        assert_eq!(ids(&room), vec![3, 4]);
+        assert_eq!(
+            room.get_censored_state_for(&country).recent_messages.len(),
+            2
+        );
+    }
 }
@@ -5,12 +5,15 @@ async fn solve_room_with_note(
     headers: HeaderMap,
 ) -> Result<Json<SolveResponse>, StatusCode> {
     let _user =
-        extract_user_from_headers(&headers, &state.tokens_map).ok_or(StatusCode::FORBIDDEN)?;
+        extract_user_from_headers(&headers, &state.accounts).ok_or(StatusCode::FORBIDDEN)?;
     let connector = state
         .room_manager
         .connect_to_room(&room_id)
         .ok_or(StatusCode::NOT_FOUND)?;
     let mut room = connector.room.lock().unwrap();
+    if room.phase() != RoomPhase::InProgress {
+        return Err(StatusCode::CONFLICT);
+    }
     // 所有玩家的 player note 都要答對才算勝利
     let notes: Vec<_> = room.get_player_notes().values().cloned().collect();
     let mut all_correct = true;
@@ -21,23 +24,31 @@ async fn solve_room_with_note(
         }
     }
     if all_correct {
-        room.win();
+        // Wins are never refused
+        let _ = state
+            .room_manager
+            .apply_logged(room.as_mut(), RoomEvent::Win);
+        state.room_manager.save_room(room.as_mut());
     }
     Ok(Json(SolveResponse {
         solved: all_correct,
     }))
 }
 // Helper for answer checking and success action, used by both solve_room and solve_room_with_note
+// Answers name each country's list by its code and each route rule by its route key ("A->B")
 fn solve_answer(room: &mut Box<dyn Room>, answer: HashMap<String, Vec<String>>) -> bool {
-    let banned_words = &room.filter_config().banned_words;
+    let banned_words = room.filter_config().answer_key();
     if answer.len() != banned_words.len() {
         return false;
     }
     for (country, submitted) in &answer {
         match banned_words.get(country) {
             Some(expected) => {
-                let s1: std::collections::HashSet<_> = submitted.iter().collect();
-                let s2: std::collections::HashSet<_> = expected.iter().collect();
+                // Phrases are compared whole, so "not obey" never matches "not" + "obey"
+                let s1: std::collections::HashSet<_> =
+                    submitted.iter().map(|w| fold_phrase(w)).collect();
+                let s2: std::collections::HashSet<_> =
+                    expected.iter().map(|w| fold_phrase(w)).collect();
                 if s1 != s2 {
                     return false;
                 }
@@ -86,7 +97,8 @@ struct SubmitNotesResponse {
     responses(
         (status = 200, description = "Solution check result", body = SolveResponse),
         (status = 403, description = "Forbidden (invalid token)"),
-        (status = 404, description = "Room not found")
+        (status = 404, description = "Room not found"),
+        (status = 409, description = "The game is not in progress")
     ),
     security(
         ("api_key" = [])
@@ -99,15 +111,22 @@ async fn solve_room(
     AxumJson(payload): AxumJson<SolveRequest>,
 ) -> Result<Json<SolveResponse>, StatusCode> {
     let _user =
-        extract_user_from_headers(&headers, &state.tokens_map).ok_or(StatusCode::FORBIDDEN)?;
+        extract_user_from_headers(&headers, &state.accounts).ok_or(StatusCode::FORBIDDEN)?;
     let connector = state
         .room_manager
         .connect_to_room(&room_id)
         .ok_or(StatusCode::NOT_FOUND)?;
     let mut room = connector.room.lock().unwrap();
+    if room.phase() != RoomPhase::InProgress {
+        return Err(StatusCode::CONFLICT);
+    }
     let result = solve_answer(&mut room, payload.answer);
     if result {
-        room.win();
+        // Wins are never refused
+        let _ = state
+            .room_manager
+            .apply_logged(room.as_mut(), RoomEvent::Win);
+        state.room_manager.save_room(room.as_mut());
     }
     Ok(Json(SolveResponse { solved: result }))
 }
@@ -119,8 +138,7 @@ async fn submit_notes(
     headers: HeaderMap,
     AxumJson(payload): AxumJson<SubmitNotesRequest>,
 ) -> Result<Json<SubmitNotesResponse>, StatusCode> {
-    let user =
-        extract_user_from_headers(&headers, &state.tokens_map).ok_or(StatusCode::FORBIDDEN)?;
+    let user = extract_user_from_headers(&headers, &state.accounts).ok_or(StatusCode::FORBIDDEN)?;
 
     let connector = state
         .room_manager
@@ -134,7 +152,20 @@ async fn submit_notes(
     let country = user.country.clone();
 
     // Process the action directly to ensure synchronous completion
-    let (_, _) = room.process_action(&user_id, &country, UserAction::SubmitNotes(payload.notes));
+    if let Err(err) = state.room_manager.apply_logged(
+        room.as_mut(),
+        RoomEvent::Action {
+            user_id: user_id.clone(),
+            country,
+            action: UserAction::SubmitNotes(payload.notes),
+        },
+    ) {
+        warn!(room_id, user_id, error = %err, "Notes refused");
+        return Err(match err {
+            ActionError::NotParticipant => StatusCode::FORBIDDEN,
+            _ => StatusCode::CONFLICT,
+        });
+    }
 
     eprintln!("[SubmitNotes] User {} submitted notes", user_id);
 
@@ -145,7 +176,7 @@ async fn submit_notes(
     let discovered_count = user_progress.map(|p| p.discovered_count).unwrap_or(0);
     let total_required = room
         .filter_config()
-        .banned_words
+        .answer_key()
         .values()
         .map(|words| words.len())
         .sum();
@@ -157,6 +188,7 @@ async fn submit_notes(
 
     // Check victory
     let victory_achieved = room.check_victory();
+    state.room_manager.save_room(room.as_mut());
 
     eprintln!("[SubmitNotes] Victory check result: {}", victory_achieved);
     eprintln!("[SubmitNotes] All progress: {:?}", all_progress);
@@ -166,23 +198,19 @@ async fn submit_notes(
         eprintln!("[SubmitNotes] Broadcasting victory state!");
         // Create and send victory update
         let victory_state = room.get_victory_state();
-        let room_state = room.get_censored_state_for(&"".to_string());
-
-        drop(room); // Release lock before sending
 
         let update = RoomUpdate {
-            room_state,
+            version: 0,
             new_messages: vec![],
             notifications: vec![Notification {
                 message: "🎉 Victory! All players discovered all banned words!".to_string(),
             }],
             room_closed: false,
             victory: Some(victory_state),
+            rejections: vec![],
         };
 
-        connector.update_sender.send_replace(update);
-    } else {
-        drop(room); // Release lock
+        connector.updates.publish(room.as_ref(), update);
     }
 
     Ok(Json(SubmitNotesResponse {
@@ -200,6 +228,10 @@ use serde::Serialize;
 struct RoomWordsInfo {
     allowed_words: Vec<String>,
     banned_words: std::collections::HashMap<String, Vec<String>>,
+    /// Seed the room's words were generated from; pass it to `POST /api/rooms` to replay the puzzle.
+    seed: GameSeed,
+    /// Word pack the room's words were drawn from.
+    pack: PackInfo,
 }
 
 // GET /api/rooms/{id}/info - Return allowed and banned words for the room
@@ -231,84 +263,416 @@ async fn get_room_words_info(
     Ok(Json(RoomWordsInfo {
         allowed_words,
         banned_words,
+        seed: room.seed(),
+        pack: room.pack().clone(),
     }))
 }
 use axum::{
     Json as AxumJson, Router,
+    body::Bytes,
     extract::{
         Path, Query, State, WebSocketUpgrade,
         ws::{Message as WsMessage, WebSocket},
     },
-    http::{HeaderMap, StatusCode},
-    response::{Json, Response},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
 };
-use dashmap::DashMap;
-use rand::distr::{Alphanumeric, SampleString};
 use serde::Deserialize;
 use serde_json::{from_str, to_string};
-use std::sync::Arc;
-use tokio_stream::wrappers::WatchStream;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing::{debug, info, warn};
 
+use crate::accounts::{AccountError, Accounts, Session};
+use crate::archive::{GameId, GameSummary};
 use crate::data::*;
-use crate::manager::{RoomConnector, RoomManager};
+use crate::events::RoomEvent;
+use crate::filter::fold_phrase;
+use crate::manager::{RoomConnector, RoomManager, RoomUpdates};
+use crate::packs::PackInfo;
+use crate::store::StoreError;
+use crate::tickets::ConnectTickets;
+use crate::views::CensoredViews;
 
-/// Update sent to clients with messages censored for their specific country.
+/// Update sent to a client. Apart from snapshots it carries only what
+/// changed since the client's previous update: the messages after the last
+/// one it was sent, censored for its country, and the roster if it changed.
 #[derive(Clone, Debug, Serialize, ToSchema)]
 struct ClientRoomUpdate {
-    room_state: RoomState,
+    /// Version of the room update this brings the client to.
+    version: u64,
+    /// The whole room state censored for the client's country, sent instead
+    /// of new messages. It replaces everything the client holds.
+    snapshot: Option<RoomState>,
+    /// Who is in the room, when it changed since the client's last update.
+    roster: Option<RoomRoster>,
     new_messages: Vec<CensoredMessage>,
     notifications: Vec<Notification>,
     room_closed: bool,
     victory: Option<VictoryState>,
+    /// Why the player's own actions since the last update were refused.
+    errors: Vec<String>,
+}
+
+/// Requests a client sends about its own updates; its socket answers them
+/// without involving the room.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum SyncRequest {
+    /// The client has every message up to this id.
+    Ack(MessageId),
+    /// Send the whole room state with the next update.
+    Snapshot,
+}
+
+/// Messages a client may leave unacknowledged before it is sent a snapshot
+/// instead of the messages it is missing.
+const MAX_UNACKED_MESSAGES: usize = 200;
+
+/// What a socket does when its client falls more than `UPDATE_BUFFER`
+/// updates behind and misses some.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LagPolicy {
+    /// Skip the missed updates and send a snapshot with the next one. The
+    /// missed notifications are lost.
+    Resync,
+    /// Close the connection. The client reconnects and starts from a snapshot.
+    Disconnect,
+}
+
+impl LagPolicy {
+    /// Applies the policy to a client that missed updates. Returns whether
+    /// to keep the connection.
+    fn keep_up(self, sync: &mut ClientSync) -> bool {
+        match self {
+            Self::Resync => {
+                sync.handle(SyncRequest::Snapshot);
+                true
+            }
+            Self::Disconnect => false,
+        }
+    }
+}
+
+/// Players keep their seat through a lag, so they are resynced.
+const PLAYER_LAG_POLICY: LagPolicy = LagPolicy::Resync;
+/// Spectators hold nothing worth keeping, so they reconnect.
+const SPECTATOR_LAG_POLICY: LagPolicy = LagPolicy::Disconnect;
+
+/// What a socket has sent its client, so each update carries only what
+/// changed.
+struct ClientSync {
+    /// Id of the last message the client was sent.
+    sent: MessageId,
+    /// Id of the last message the client acknowledged.
+    acked: MessageId,
+    /// Country the client's messages were censored for.
+    country: Option<CountryCode>,
+    /// Roster the client was last sent.
+    roster: Option<RoomRoster>,
+    snapshot_requested: bool,
+}
+
+impl ClientSync {
+    /// Sync for a client that has the messages up to `last_message_id`. A
+    /// client without one starts from a snapshot.
+    fn new(last_message_id: Option<MessageId>) -> Self {
+        Self {
+            sent: last_message_id.unwrap_or(0),
+            acked: last_message_id.unwrap_or(0),
+            country: None,
+            roster: None,
+            snapshot_requested: last_message_id.is_none(),
+        }
+    }
+
+    fn handle(&mut self, request: SyncRequest) {
+        match request {
+            SyncRequest::Ack(id) => self.acked = self.acked.max(id.min(self.sent)),
+            SyncRequest::Snapshot => self.snapshot_requested = true,
+        }
+    }
+
+    /// Builds the update a client receives: what it is missing, censored for
+    /// its country, and only its own refused actions. A client is sent a
+    /// snapshot instead when it asked for one, when its country changed, when
+    /// it fell too far behind or when it misses messages the room no longer
+    /// keeps in memory. Messages come from the room's shared censored views,
+    /// so building an update censors nothing.
+    fn next_update(
+        &mut self,
+        views: &CensoredViews,
+        update: RoomUpdate,
+        user_id: &UserId,
+        country: &CountryCode,
+    ) -> ClientRoomUpdate {
+        let missing = views.messages_after(country, self.sent);
+        let latest = missing.last().map_or(self.sent, |msg| msg.id);
+        // Message ids have no gaps, so a gap means the room no longer keeps
+        // messages the client has not seen
+        let lost = missing.first().is_some_and(|msg| msg.id > self.sent + 1);
+        let behind = latest.saturating_sub(self.acked) > MAX_UNACKED_MESSAGES as MessageId;
+        let recensor = self.country.as_ref().is_some_and(|seen| seen != country);
+        self.country = Some(country.clone());
+
+        let roster = views.roster();
+        let (snapshot, new_messages, roster) =
+            if self.snapshot_requested || lost || behind || recensor {
+                self.snapshot_requested = false;
+                self.acked = latest;
+                self.roster = Some(roster.clone());
+                (Some(views.state_for(country)), vec![], None)
+            } else {
+                let new_messages = missing.iter().filter(|msg| !msg.hidden).cloned().collect();
+                let changed = self.roster.as_ref() != Some(roster);
+                if changed {
+                    self.roster = Some(roster.clone());
+                }
+                (None, new_messages, changed.then(|| roster.clone()))
+            };
+        self.sent = latest;
+
+        ClientRoomUpdate {
+            version: update.version,
+            snapshot,
+            roster,
+            new_messages,
+            notifications: update.notifications,
+            room_closed: update.room_closed,
+            victory: update.victory,
+            errors: update
+                .rejections
+                .iter()
+                .filter(|rejection| &rejection.user_id == user_id)
+                .map(|rejection| rejection.error.to_string())
+                .collect(),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub room_manager: Arc<RoomManager>,
-    pub tokens_map: Arc<DashMap<String, (UserId, CountryCode)>>,
+    pub accounts: Arc<Accounts>,
+    pub tickets: Arc<ConnectTickets>,
 }
 
 #[derive(Deserialize, ToSchema)]
 pub struct ConnectQuery {
-    token: String,
+    /// Single-use ticket from `POST /api/rooms/{id}/ticket`.
+    ticket: String,
+    /// Id of the last message the client received. The client is sent only
+    /// the messages after it, so a client resuming a session misses none;
+    /// without it the client starts from a snapshot.
+    #[serde(default)]
+    last_message_id: Option<MessageId>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ConnectTicketResponse {
+    ticket: String,
+    /// Unix time in seconds from which the ticket is refused.
+    expires_at: Timestamp,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RegisterRequest {
+    username: String,
+    password: String,
 }
 
 #[derive(Deserialize, ToSchema)]
 struct LoginRequest {
     username: String,
+    /// Preferred country. Rooms seat players to keep countries balanced, so
+    /// the player may be seated elsewhere.
+    #[serde(default)]
     country: String,
+    /// Password of a registered account; without one, the login is a guest login.
+    password: Option<String>,
 }
 
 #[derive(serde::Serialize, ToSchema)]
 struct LoginResponse {
     token: String,
+    /// Unix time in seconds from which the token is refused; refresh it before then.
+    expires_at: Timestamp,
+}
+
+fn account_error_status(err: &AccountError) -> StatusCode {
+    match err {
+        AccountError::InvalidUsername | AccountError::WeakPassword => StatusCode::BAD_REQUEST,
+        AccountError::UsernameTaken => StatusCode::CONFLICT,
+        AccountError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+        AccountError::InvalidToken(_) | AccountError::SessionEnded => StatusCode::FORBIDDEN,
+        AccountError::Hashing(_) | AccountError::Store(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Runs account work that hashes or verifies a password on the blocking
+/// pool, so a slow hash does not hold up the async workers.
+async fn run_hashing<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, (StatusCode, String)> {
+    tokio::task::spawn_blocking(work).await.map_err(|err| {
+        warn!(error = %err, "Password hashing failed");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "the request could not be completed".to_string(),
+        )
+    })
 }
+
+// POST /api/register - Create a registered account
+#[utoipa::path(
+    post,
+    path = "/api/register",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Account created"),
+        (status = 400, description = "Invalid username or password too short", body = String),
+        (status = 409, description = "Username taken", body = String)
+    )
+)]
+async fn register(
+    State(state): State<AppState>,
+    AxumJson(payload): AxumJson<RegisterRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let accounts = Arc::clone(&state.accounts);
+    let username = payload.username.clone();
+    run_hashing(move || accounts.register(&username, &payload.password))
+        .await?
+        .map_err(|err| {
+            warn!(username = %payload.username, error = %err, "Registration refused");
+            (account_error_status(&err), err.to_string())
+        })?;
+    info!(username = %payload.username, "Account registered");
+    Ok(StatusCode::CREATED)
+}
+
 // POST /api/login - Login and get token
 #[utoipa::path(
     post,
     path = "/api/login",
     request_body = LoginRequest,
     responses(
-        (status = 200, description = "Login successful", body = LoginResponse)
+        (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 400, description = "Invalid guest username", body = String),
+        (status = 401, description = "Wrong username or password", body = String),
+        (status = 409, description = "Username taken by another account", body = String)
     )
 )]
 async fn login(
     State(state): State<AppState>,
     AxumJson(payload): AxumJson<LoginRequest>,
-) -> Result<AxumJson<LoginResponse>, StatusCode> {
-    // Generate a random token
-    let token: String = Alphanumeric.sample_string(&mut rand::rng(), 16);
+) -> Result<AxumJson<LoginResponse>, (StatusCode, String)> {
+    let accounts = Arc::clone(&state.accounts);
+    let LoginRequest {
+        username,
+        country,
+        password,
+    } = payload;
+    let name = username.clone();
+    let issued = run_hashing(move || match password {
+        Some(password) => accounts.login(&name, &password, country),
+        None => accounts.login_guest(&name, country),
+    })
+    .await?
+    .map_err(|err| {
+        warn!(username = %username, error = %err, "Login refused");
+        (account_error_status(&err), err.to_string())
+    })?;
+
+    Ok(AxumJson(LoginResponse {
+        token: issued.token,
+        expires_at: issued.expires_at,
+    }))
+}
+
+// POST /api/logout - End the session of the token used
+#[utoipa::path(
+    post,
+    path = "/api/logout",
+    responses(
+        (status = 204, description = "Logged out"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn logout(State(state): State<AppState>, headers: HeaderMap) -> StatusCode {
+    let token = token_from_headers(&headers).unwrap_or_default();
+    match state.accounts.logout(token) {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::FORBIDDEN,
+    }
+}
 
-    // Insert into tokens_map (DashMap)
-    state
-        .tokens_map
-        .insert(token.clone(), (payload.username, payload.country));
+// POST /api/token/refresh - Exchange a live token for one with a later expiry
+#[utoipa::path(
+    post,
+    path = "/api/token/refresh",
+    responses(
+        (status = 200, description = "New token for the same session", body = LoginResponse),
+        (status = 403, description = "Invalid or expired token, or the session has ended", body = String)
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn refresh_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<AxumJson<LoginResponse>, (StatusCode, String)> {
+    let token = token_from_headers(&headers).unwrap_or_default();
+    let issued = state.accounts.refresh(token).map_err(|err| {
+        warn!(error = %err, "Token refresh refused");
+        (account_error_status(&err), err.to_string())
+    })?;
+    Ok(AxumJson(LoginResponse {
+        token: issued.token,
+        expires_at: issued.expires_at,
+    }))
+}
 
-    Ok(AxumJson(LoginResponse { token }))
+// POST /api/logout/all - Revoke every token of the caller's account
+#[utoipa::path(
+    post,
+    path = "/api/logout/all",
+    responses(
+        (status = 204, description = "All sessions ended"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "The revocation could not be saved")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn logout_all(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let user = extract_user_from_headers(&headers, &state.accounts).ok_or(StatusCode::FORBIDDEN)?;
+    // Revoking saves the account, which blocks
+    let accounts = Arc::clone(&state.accounts);
+    let user_id = user.user_id.clone();
+    tokio::task::spawn_blocking(move || accounts.revoke_all(&user_id))
+        .await
+        .map_err(|err| {
+            warn!(user_id = %user.user_id, error = %err, "Failed to revoke sessions");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map_err(|err| {
+            warn!(user_id = %user.user_id, error = %err, "Failed to revoke sessions");
+            account_error_status(&err)
+        })?;
+    info!(user_id = %user.user_id, "Revoked all sessions");
+    Ok(StatusCode::NO_CONTENT)
 }
 
 pub struct AuthenticatedUser {
@@ -316,17 +680,25 @@ pub struct AuthenticatedUser {
     pub country: CountryCode,
 }
 
+impl From<Session> for AuthenticatedUser {
+    fn from(session: Session) -> Self {
+        Self {
+            user_id: session.user_id,
+            country: session.country,
+        }
+    }
+}
+
+fn token_from_headers(headers: &HeaderMap) -> Option<&str> {
+    headers.get("X-User-Token")?.to_str().ok()
+}
+
 fn extract_user_from_headers(
     headers: &HeaderMap,
-    tokens_map: &DashMap<String, (UserId, CountryCode)>,
+    accounts: &Accounts,
 ) -> Option<AuthenticatedUser> {
-    let token = headers.get("X-User-Token")?.to_str().ok()?;
-    let pair = tokens_map.get(token)?;
-    let (user_id, country) = pair.value();
-    Some(AuthenticatedUser {
-        user_id: user_id.clone(),
-        country: country.clone(),
-    })
+    let token = token_from_headers(headers)?;
+    accounts.authenticate(token).map(AuthenticatedUser::from)
 }
 
 // GET /api/info - Filter config
@@ -344,6 +716,18 @@ async fn get_info(State(state): State<AppState>) -> Json<RoomInfo> {
     })
 }
 
+// GET /api/packs - List word packs rooms can be created from
+#[utoipa::path(
+    get,
+    path = "/api/packs",
+    responses(
+        (status = 200, description = "List available word packs", body = Vec<PackInfo>)
+    )
+)]
+async fn list_packs(State(state): State<AppState>) -> Json<Vec<PackInfo>> {
+    Json(state.room_manager.word_packs().list())
+}
+
 // GET /api/rooms - List room IDs
 #[utoipa::path(
     get,
@@ -360,8 +744,10 @@ async fn list_rooms(State(state): State<AppState>) -> Json<Vec<RoomId>> {
 #[utoipa::path(
     post,
     path = "/api/rooms",
+    request_body(content = Option<RoomSettings>, description = "Room settings; an empty body uses the defaults"),
     responses(
         (status = 200, description = "Room created", body = RoomId),
+        (status = 400, description = "Malformed or invalid room settings", body = String),
         (status = 403, description = "Forbidden")
     ),
     security(
@@ -371,17 +757,277 @@ async fn list_rooms(State(state): State<AppState>) -> Json<Vec<RoomId>> {
 async fn create_room(
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Result<Json<RoomId>, StatusCode> {
-    let _user = extract_user_from_headers(&headers, &state.tokens_map).ok_or_else(|| {
+    body: Bytes,
+) -> Result<Json<RoomId>, (StatusCode, String)> {
+    let _user = extract_user_from_headers(&headers, &state.accounts).ok_or_else(|| {
         warn!("Unauthorized room creation attempt");
-        StatusCode::FORBIDDEN
+        (StatusCode::FORBIDDEN, "Forbidden".to_string())
     })?;
 
-    let room_id = Arc::clone(&state.room_manager).create_and_run_room();
-    info!(room_id, "Room created");
+    let settings = parse_room_settings(&body).map_err(|err| {
+        warn!(error = %err, "Malformed room settings");
+        (
+            StatusCode::BAD_REQUEST,
+            format!("invalid room settings: {err}"),
+        )
+    })?;
+    let room_id = Arc::clone(&state.room_manager)
+        .create_and_run_room(&settings)
+        .map_err(|err| {
+            warn!(error = %err, "Rejected room settings");
+            (StatusCode::BAD_REQUEST, err.to_string())
+        })?;
+    info!(room_id, ?settings, "Room created");
     Ok(Json(room_id))
 }
 
+// Clients may post no body at all, which means default settings
+fn parse_room_settings(body: &[u8]) -> Result<RoomSettings, serde_json::Error> {
+    if body.trim_ascii().is_empty() {
+        Ok(RoomSettings::default())
+    } else {
+        serde_json::from_slice(body)
+    }
+}
+
+/// Messages returned when a history query sets no limit.
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+/// Most messages a history query returns.
+const MAX_HISTORY_LIMIT: usize = 200;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+struct HistoryQuery {
+    before: Option<MessageId>,
+    after: Option<MessageId>,
+    limit: Option<usize>,
+}
+
+/// A page of a room's history, oldest message first.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+struct MessagePage {
+    messages: Vec<CensoredMessage>,
+    /// Pass as `before` to get the older messages, if there are any.
+    older: Option<MessageId>,
+    /// Pass as `after` to get the newer messages, if there are any.
+    newer: Option<MessageId>,
+}
+
+/// Country a player reads the room's history as: their seat, or the seat they
+/// last had if they left. Anyone else reads it as a spectator.
+fn history_country(room: &dyn Room, user_id: &UserId) -> CountryCode {
+    room.country_of(user_id)
+        .or_else(|| {
+            room.players()
+                .iter()
+                .rev()
+                .find(|p| &p.user_id == user_id)
+                .map(|p| &p.country)
+        })
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Reads a page of the room's history censored for `country`, with `read`
+/// reading ranges of the room's messages. With `after` the page starts right
+/// after it, otherwise it ends right before `before`, or at the newest message.
+/// The room is only locked to censor the page, not while reading.
+async fn history_page<F>(
+    room: &Mutex<Box<dyn Room>>,
+    query: HistoryQuery,
+    country: &CountryCode,
+    read: impl Fn(MessageRange) -> F,
+) -> Result<MessagePage, StoreError>
+where
+    F: Future<Output = Result<Vec<Message>, StoreError>>,
+{
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .clamp(1, MAX_HISTORY_LIMIT);
+    let page = read(MessageRange {
+        after: query.after.unwrap_or(0),
+        before: query.before.unwrap_or(MessageId::MAX),
+        limit,
+        from_oldest: query.after.is_some(),
+    })
+    .await?;
+    let any_between = |after, before| {
+        let range = MessageRange {
+            after,
+            before,
+            limit: 1,
+            from_oldest: true,
+        };
+        let read = read(range);
+        async move { read.await.map(|messages| !messages.is_empty()) }
+    };
+
+    let older = match page.first() {
+        Some(first) if any_between(0, first.id).await? => Some(first.id),
+        _ => None,
+    };
+    let newer = match page.last() {
+        Some(last) if any_between(last.id, MessageId::MAX).await? => Some(last.id),
+        _ => None,
+    };
+    let room = room.lock().unwrap();
+    let messages = page
+        .iter()
+        .map(|msg| room.censor_message_for(msg, country))
+        // Messages dropped by a firewall are hidden from the viewer entirely
+        .filter(|msg| !msg.hidden)
+        .collect();
+    Ok(MessagePage {
+        messages,
+        older,
+        newer,
+    })
+}
+
+// GET /api/rooms/{id}/messages - Page through the room's history
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{id}/messages",
+    params(
+        ("id" = String, Path, description = "Room ID"),
+        ("before" = Option<u64>, Query, description = "Return messages with ids below this one, newest page first"),
+        ("after" = Option<u64>, Query, description = "Return messages with ids above this one, oldest page first"),
+        ("limit" = Option<usize>, Query, description = "Most messages to return, 50 by default and at most 200")
+    ),
+    responses(
+        (status = 200, description = "Messages censored for the caller's country, with cursors to the neighbouring pages", body = MessagePage),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Room not found"),
+        (status = 500, description = "The saved history could not be read")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn get_room_messages(
+    State(state): State<AppState>,
+    Path(room_id): Path<RoomId>,
+    Query(query): Query<HistoryQuery>,
+    headers: HeaderMap,
+) -> Result<Json<MessagePage>, StatusCode> {
+    let user = extract_user_from_headers(&headers, &state.accounts).ok_or(StatusCode::FORBIDDEN)?;
+    let connector = state
+        .room_manager
+        .connect_to_room(&room_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let country = history_country(connector.room.lock().unwrap().as_ref(), &user.user_id);
+    let page = history_page(&connector.room, query, &country, |range| {
+        state.room_manager.read_messages(&connector.room, range)
+    })
+    .await
+    .map_err(|err| {
+        warn!(room_id, error = %err, "Failed to read room history");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(page))
+}
+
+// GET /api/rooms/{id}/rules - Return the settings the room was created with
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{id}/rules",
+    params(
+        ("id" = String, Path, description = "Room ID")
+    ),
+    responses(
+        (status = 200, description = "Room settings, including the seed used", body = RoomSettings),
+        (status = 404, description = "Room not found")
+    )
+)]
+async fn get_room_rules(
+    State(state): State<AppState>,
+    Path(room_id): Path<RoomId>,
+) -> Result<Json<RoomSettings>, StatusCode> {
+    let connector = state
+        .room_manager
+        .connect_to_room(&room_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let settings = connector.room.lock().unwrap().settings().clone();
+    Ok(Json(settings))
+}
+
+// GET /api/games - List finished games
+#[utoipa::path(
+    get,
+    path = "/api/games",
+    responses(
+        (status = 200, description = "Summaries of finished games, oldest first", body = Vec<GameSummary>),
+        (status = 500, description = "The archive could not be read")
+    )
+)]
+async fn list_games(State(state): State<AppState>) -> Result<Json<Vec<GameSummary>>, StatusCode> {
+    let games = state.room_manager.list_games().await.map_err(|err| {
+        warn!(error = %err, "Failed to list archived games");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(games))
+}
+
+// GET /api/games/{id}/export - Export a finished game as NDJSON
+#[utoipa::path(
+    get,
+    path = "/api/games/{id}/export",
+    params(
+        ("id" = u64, Path, description = "Game ID")
+    ),
+    responses(
+        (status = 200, description = "One JSON object per line: the game summary, each raw message, then each country's censored view of every message", content_type = "application/x-ndjson", body = String),
+        (status = 404, description = "Game not found"),
+        (status = 500, description = "The archive could not be read")
+    )
+)]
+async fn export_game(
+    State(state): State<AppState>,
+    Path(game_id): Path<GameId>,
+) -> Result<Response, StatusCode> {
+    let game = state
+        .room_manager
+        .load_game(game_id)
+        .await
+        .map_err(|err| {
+            warn!(game_id, error = %err, "Failed to load archived game");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let body = game
+        .to_ndjson()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response())
+}
+
+// POST /api/rooms/{id}/ticket - Issue a ticket to open the room's WebSocket
+#[utoipa::path(
+    post,
+    path = "/api/rooms/{id}/ticket",
+    params(
+        ("id" = String, Path, description = "Room ID")
+    ),
+    responses(
+        (status = 200, description = "Single-use ticket for the room's connect WebSocket, valid for a few seconds", body = ConnectTicketResponse),
+        (status = 403, description = "Forbidden")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn issue_connect_ticket(
+    State(state): State<AppState>,
+    Path(room_id): Path<RoomId>,
+    headers: HeaderMap,
+) -> Result<Json<ConnectTicketResponse>, StatusCode> {
+    let user = extract_user_from_headers(&headers, &state.accounts).ok_or_else(|| {
+        warn!(room_id, "Unauthorized ticket request");
+        StatusCode::FORBIDDEN
+    })?;
+    let (ticket, expires_at) = state.tickets.issue(room_id, user.user_id, user.country);
+    Ok(Json(ConnectTicketResponse { ticket, expires_at }))
+}
+
 // GET /api/rooms/:id/connect - WebSocket for participants
 async fn connect_room(
     State(state): State<AppState>,
@@ -389,34 +1035,60 @@ async fn connect_room(
     Query(query): Query<ConnectQuery>,
     ws: WebSocketUpgrade,
 ) -> Result<Response, StatusCode> {
-    // Extract user from query parameter token
+    // Session tokens never travel in the URL; only single-use tickets do
     let (user_id, country) = state
-        .tokens_map
-        .get(&query.token)
-        .map(|pair| pair.value().clone())
+        .tickets
+        .redeem(&query.ticket, &room_id)
         .ok_or_else(|| {
-            warn!(room_id, token = %query.token, "Unauthorized connection attempt");
+            warn!(room_id, "Connection attempt with an invalid or used ticket");
             StatusCode::FORBIDDEN
         })?;
+    let user = AuthenticatedUser { user_id, country };
 
-    let user = AuthenticatedUser {
-        user_id: user_id.clone(),
-        country: country.clone(),
-    };
-
-    info!(room_id, user_id = %user.user_id, country = %user.country, "User connecting to room");
+    info!(room_id, user_id = %user.user_id, preferred_country = %user.country, "User connecting to room");
 
     // Get or create room if it doesn't exist (especially for test_room)
-    let connector = state
-        .room_manager
-        .connect_to_room(&room_id)
-        .unwrap_or_else(|| {
+    let connector = match state.room_manager.connect_to_room(&room_id) {
+        Some(connector) => connector,
+        None => {
             info!(room_id, "Room not found, creating it");
-            let new_room_id = Arc::clone(&state.room_manager).create_room_with_id(room_id.clone());
-            state.room_manager.connect_to_room(&new_room_id).unwrap()
-        });
+            Arc::clone(&state.room_manager)
+                .create_room_with_id(room_id.clone(), &RoomSettings::default())
+                .map_err(|err| {
+                    warn!(room_id, error = %err, "Failed to create room");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            // Another player may have closed it again in the meantime
+            state
+                .room_manager
+                .connect_to_room(&room_id)
+                .ok_or_else(|| {
+                    warn!(room_id, "Room closed before the player connected");
+                    StatusCode::NOT_FOUND
+                })?
+        }
+    };
+
+    {
+        let room = connector.room.lock().unwrap();
+        let participants = room.participants();
+        let rejoining = participants.iter().any(|p| p.user_id == user.user_id);
+        if !rejoining && !room.settings().admits(participants.len()) {
+            warn!(room_id, user_id = %user.user_id, "Room is full");
+            return Err(StatusCode::CONFLICT);
+        }
+        // Once the game has started, only its players can come back
+        let returning = room.players().iter().any(|p| p.user_id == user.user_id);
+        if room.phase() != RoomPhase::Lobby && !returning {
+            warn!(room_id, user_id = %user.user_id, phase = %room.phase(), "Game already started");
+            return Err(StatusCode::CONFLICT);
+        }
+    }
 
-    Ok(ws.on_upgrade(move |socket| handle_participant_socket(socket, connector, user, room_id)))
+    let resume_after = query.last_message_id;
+    Ok(ws.on_upgrade(move |socket| {
+        handle_participant_socket(socket, connector, user, room_id, resume_after)
+    }))
 }
 
 async fn handle_participant_socket(
@@ -424,27 +1096,48 @@ async fn handle_participant_socket(
     connector: RoomConnector,
     user: AuthenticatedUser,
     room_id: RoomId,
+    resume_after: Option<MessageId>,
 ) {
     let (mut ws_sender, mut ws_receiver) = socket.split();
     let RoomConnector {
         action_sender,
-        mut update_receiver,
-        update_sender: _,
-        room,
+        updates,
+        ..
     } = connector;
+    // Subscribe before joining so the join's own update is received
+    let (version, mut update_receiver) = updates.subscribe();
 
     let user_id = &user.user_id;
     debug!(room_id, user_id, "WebSocket connection established");
 
-    // Send initial join action
-    let join_message = UserMessage {
-        user_id: user.user_id.clone(),
-        country: user.country.clone(),
-        action: UserAction::SendMessage(String::new()), // Empty message to trigger join
-    };
-    if action_sender.send(join_message).await.is_err() {
-        warn!(room_id, user_id, "Failed to send join message");
+    let Some(initial_update) = join_room(
+        &user,
+        &action_sender,
+        &updates,
+        &mut update_receiver,
+        version,
+    )
+    .await
+    else {
+        warn!(room_id, user_id, "Failed to join room");
         return;
+    };
+
+    // A new player starts from a snapshot and a resuming one from the
+    // messages it missed, so both end up with the same view as everyone who
+    // stayed connected
+    let mut sync = ClientSync::new(resume_after);
+    let client_update = {
+        let views = updates.views();
+        let country = views.country_of(user_id);
+        sync.next_update(&views, initial_update, user_id, &country)
+    };
+    #[allow(clippy::collapsible_if)]
+    if let Ok(json) = to_string(&client_update) {
+        if ws_sender.send(WsMessage::Text(json.into())).await.is_err() {
+            debug!(room_id, user_id, "Failed to send initial state");
+            return;
+        }
     }
 
     loop {
@@ -453,7 +1146,10 @@ async fn handle_participant_socket(
                 match msg {
                     Some(Ok(WsMessage::Text(text))) => {
                         eprintln!("Received message from {}: {}", user.user_id, text);
-                        if let Ok(action) = from_str::<UserAction>(&text) {
+                        if let Ok(request) = from_str::<SyncRequest>(&text) {
+                            debug!(room_id, user_id, ?request, "Received sync request");
+                            sync.handle(request);
+                        } else if let Ok(action) = from_str::<UserAction>(&text) {
                             debug!(room_id, user_id, ?action, "Received action");
                             let user_message = UserMessage {
                                 user_id: user.user_id.clone(),
@@ -470,67 +1166,111 @@ async fn handle_participant_socket(
                     }
                     Some(Ok(WsMessage::Close(_))) | None => {
                         info!(room_id, user_id, "User disconnecting");
-                        // Send leave action
-                        let leave_message = UserMessage {
-                            user_id: user.user_id.clone(),
-                            country: user.country.clone(),
-                            action: UserAction::LeaveRoom,
-                        };
-                        let _ = action_sender.send(leave_message).await;
                         break;
                     }
                     _ => {}
                 }
             }
-            result = update_receiver.changed() => {
-                match result {
-                    Ok(_) => {
-                        let update = update_receiver.borrow().clone();
-
-                        // Lock the room only to extract the needed fields, then drop the lock before await
-                        // Censor messages for this user's country using the room's method
-                        let censored_messages: Vec<CensoredMessage> = {
-                            let locked_room = room.lock().unwrap();
-                            update
-                                .new_messages
-                                .iter()
-                                .map(|msg| locked_room.censor_message_for(msg, &user.country))
-                                .collect()
-                        };
-
-                        let client_update = ClientRoomUpdate {
-                            room_state: update.room_state,
-                            new_messages: censored_messages,
-                            notifications: update.notifications,
-                            room_closed: update.room_closed,
-                            victory: update.victory,
-                        };
-
-                        debug!(
-                            room_id,
-                            user_id,
-                            user_country = %user.country,
-                            message_count = client_update.new_messages.len(),
-                            "Sending censored update to client"
-                        );
-
-                        #[allow(clippy::collapsible_if)]
-                        if let Ok(json) = to_string(&client_update) {
-                            if ws_sender.send(WsMessage::Text(json.into())).await.is_err() {
-                                debug!(room_id, user_id, "Failed to send update, closing connection");
-                                break;
-                            }
-                        }
-                        if client_update.room_closed {
-                            info!(room_id, user_id, "Room closed");
-                            break;
+            result = update_receiver.recv() => {
+                let update = match result {
+                    Ok(update) => update,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(room_id, user_id, skipped, "Client fell behind");
+                        if PLAYER_LAG_POLICY.keep_up(&mut sync) {
+                            continue;
                         }
+                        break;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                // Seats change in the lobby, so look the country up each time
+                let (country, client_update) = {
+                    let views = updates.views();
+                    let country = views.country_of(user_id);
+                    let client_update = sync.next_update(&views, update, user_id, &country);
+                    (country, client_update)
+                };
+
+                debug!(
+                    room_id,
+                    user_id,
+                    user_country = %country,
+                    version = client_update.version,
+                    snapshot = client_update.snapshot.is_some(),
+                    message_count = client_update.new_messages.len(),
+                    "Sending censored update to client"
+                );
+
+                #[allow(clippy::collapsible_if)]
+                if let Ok(json) = to_string(&client_update) {
+                    if ws_sender.send(WsMessage::Text(json.into())).await.is_err() {
+                        debug!(room_id, user_id, "Failed to send update, closing connection");
+                        break;
                     }
-                    Err(_) => break,
+                }
+                if client_update.room_closed {
+                    info!(room_id, user_id, "Room closed");
+                    break;
                 }
             }
         }
     }
+
+    // However the socket ended, the room counts it closed. Once the player's
+    // last socket closes, they keep their seat for the room's grace period so
+    // they can reconnect
+    let disconnect_message = UserMessage {
+        user_id: user.user_id.clone(),
+        country: user.country.clone(),
+        action: UserAction::System(SystemAction::Disconnect),
+    };
+    let _ = action_sender.send(disconnect_message).await;
+}
+
+/// Asks the room to seat the player, then waits until it did, so the first
+/// frame is censored for the player's seat and not sent as spectators see it.
+/// Returns the update to build the first frame from, or `None` when the room
+/// is gone.
+async fn join_room(
+    user: &AuthenticatedUser,
+    action_sender: &mpsc::Sender<UserMessage>,
+    updates: &RoomUpdates,
+    update_receiver: &mut broadcast::Receiver<RoomUpdate>,
+    version: u64,
+) -> Option<RoomUpdate> {
+    let join_message = UserMessage {
+        user_id: user.user_id.clone(),
+        country: user.country.clone(),
+        action: UserAction::System(SystemAction::JoinRoom),
+    };
+    action_sender.send(join_message).await.ok()?;
+
+    let seated = || !updates.views().country_of(&user.user_id).is_empty();
+    // Players still seated from another socket need not wait
+    if seated() {
+        return Some(RoomUpdate {
+            version,
+            new_messages: vec![],
+            notifications: vec![],
+            room_closed: false,
+            victory: None,
+            rejections: vec![],
+        });
+    }
+    loop {
+        let update = match update_receiver.recv().await {
+            Ok(update) => update,
+            // The first frame is built from the views, so nothing is lost
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return None,
+        };
+        let refused = update.rejections.iter().any(|rejection| {
+            rejection.user_id == user.user_id && rejection.error == ActionError::CannotJoin
+        });
+        if refused || update.room_closed || seated() {
+            return Some(update);
+        }
+    }
 }
 
 // GET /api/rooms/:id/spectate - WebSocket for spectators (read-only)
@@ -552,22 +1292,73 @@ async fn spectate_room(
 }
 
 async fn handle_spectator_socket(socket: WebSocket, connector: RoomConnector, room_id: RoomId) {
-    let (mut ws_sender, _ws_receiver) = socket.split();
-    let mut update_stream = WatchStream::new(connector.update_receiver);
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let RoomConnector { updates, .. } = connector;
+    let (version, mut update_receiver) = updates.subscribe();
+    // Spectators see the room censored for no country and cannot act, but
+    // sync the same way players do
+    let (nobody, no_country) = (UserId::new(), CountryCode::new());
+    let mut sync = ClientSync::new(None);
+    let initial_update = RoomUpdate {
+        version,
+        new_messages: vec![],
+        notifications: vec![],
+        room_closed: false,
+        victory: None,
+        rejections: vec![],
+    };
+    let client_update = sync.next_update(&updates.views(), initial_update, &nobody, &no_country);
+    #[allow(clippy::collapsible_if)]
+    if let Ok(json) = to_string(&client_update) {
+        if ws_sender.send(WsMessage::Text(json.into())).await.is_err() {
+            return;
+        }
+    }
 
     debug!(room_id, "Spectator WebSocket established");
 
-    while let Some(update) = update_stream.next().await {
-        #[allow(clippy::collapsible_if)]
-        if let Ok(json) = to_string(&update) {
-            if ws_sender.send(WsMessage::Text(json.into())).await.is_err() {
-                debug!(room_id, "Spectator disconnected");
-                break;
+    loop {
+        tokio::select! {
+            msg = ws_receiver.next() => {
+                match msg {
+                    Some(Ok(WsMessage::Text(text))) => {
+                        if let Ok(request) = from_str::<SyncRequest>(&text) {
+                            sync.handle(request);
+                        }
+                    }
+                    Some(Ok(WsMessage::Close(_))) | None => {
+                        debug!(room_id, "Spectator disconnected");
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            result = update_receiver.recv() => {
+                let update = match result {
+                    Ok(update) => update,
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!(room_id, skipped, "Spectator fell behind");
+                        if SPECTATOR_LAG_POLICY.keep_up(&mut sync) {
+                            continue;
+                        }
+                        let _ = ws_sender.send(WsMessage::Close(None)).await;
+                        break;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let client_update = sync.next_update(&updates.views(), update, &nobody, &no_country);
+                #[allow(clippy::collapsible_if)]
+                if let Ok(json) = to_string(&client_update) {
+                    if ws_sender.send(WsMessage::Text(json.into())).await.is_err() {
+                        debug!(room_id, "Spectator disconnected");
+                        break;
+                    }
+                }
+                if client_update.room_closed {
+                    info!(room_id, "Room closed, spectator disconnecting");
+                    break;
+                }
             }
-        }
-        if update.room_closed {
-            info!(room_id, "Room closed, spectator disconnecting");
-            break;
         }
     }
 }
@@ -575,24 +1366,42 @@ async fn handle_spectator_socket(socket: WebSocket, connector: RoomConnector, ro
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        register,
         login,
+        logout,
+        logout_all,
+        refresh_token,
+        issue_connect_ticket,
         get_info,
+        list_packs,
         list_rooms,
         create_room,
+        get_room_rules,
+        list_games,
+        export_game,
         get_room_words_info,
+        get_room_messages,
         solve_room
     ),
     components(
         schemas(
+            RegisterRequest,
             LoginRequest,
             LoginResponse,
             RoomInfo,
             RoomId,
             RoomWordsInfo,
+            RoomSettings,
+            crate::words::GenerationParams,
+            PackInfo,
+            GameSummary,
             SolveRequest,
             SolveResponse,
             ClientRoomUpdate,
+            MessagePage,
             ConnectQuery,
+            SyncRequest,
+            ConnectTicketResponse,
             crate::data::FilterConfig,
             crate::data::Message,
             crate::data::CensoredMessage,
@@ -601,6 +1410,7 @@ async fn handle_spectator_socket(socket: WebSocket, connector: RoomConnector, ro
             crate::data::GameAction,
             crate::data::Participant,
             crate::data::RoomState,
+            crate::data::RoomRoster,
             crate::data::Notification,
             crate::data::RoomUpdate
         )
@@ -638,18 +1448,28 @@ pub fn build_router(state: AppState) -> Router {
     Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .route("/api/info", get(get_info))
+        .route("/api/packs", get(list_packs))
         .route("/api/rooms", get(list_rooms))
         .route("/api/rooms", post(create_room))
+        .route("/api/rooms/{id}/ticket", post(issue_connect_ticket))
         .route("/api/rooms/{id}/connect", get(connect_room))
         .route("/api/rooms/{id}/spectate", get(spectate_room))
         .route("/api/rooms/{id}/info", get(get_room_words_info))
+        .route("/api/rooms/{id}/rules", get(get_room_rules))
+        .route("/api/rooms/{id}/messages", get(get_room_messages))
         .route("/api/rooms/{id}/solve", post(solve_room))
+        .route("/api/games", get(list_games))
+        .route("/api/games/{id}/export", get(export_game))
         .route(
             "/api/rooms/{id}/solve_with_note",
             post(solve_room_with_note),
         )
         .route("/api/rooms/{id}/submit_notes", post(submit_notes))
+        .route("/api/register", post(register))
         .route("/api/login", post(login))
+        .route("/api/logout", post(logout))
+        .route("/api/logout/all", post(logout_all))
+        .route("/api/token/refresh", post(refresh_token))
         .layer(TraceLayer::new_for_http())
         .layer(cors)
         .with_state(state)
@@ -657,6 +1477,462 @@ pub fn build_router(state: AppState) -> Router {
 
 #[cfg(test)]
 mod tests {
-    // Note: Censorship tests are located in room.rs
-    // This module is kept for potential future server-specific tests
+    use super::*;
+    use crate::packs::{WordPack, WordPackRegistry};
+    use crate::room::ChatRoom;
+    use crate::store::{MemoryRoomStore, StoredRoom, read_messages};
+    use crate::words::GenerationError;
+    use std::sync::Mutex;
+
+    fn make_room() -> Mutex<Box<dyn Room>> {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("E".to_string(), vec!["hello".to_string()]);
+        let config = FilterConfig {
+            banned_words,
+            ..Default::default()
+        };
+        let mut room = ChatRoom::new("test_room".to_string(), &config);
+        room.add_participant("frank".to_string(), "F".to_string());
+        room.skip_lobby();
+        Mutex::new(Box::new(room))
+    }
+
+    /// The room's censored views as its sockets would see them.
+    fn views(room: &Mutex<Box<dyn Room>>) -> CensoredViews {
+        CensoredViews::new(room.lock().unwrap().as_ref())
+    }
+
+    fn empty_update() -> RoomUpdate {
+        RoomUpdate {
+            version: 0,
+            new_messages: vec![],
+            notifications: vec![],
+            room_closed: false,
+            victory: None,
+            rejections: vec![],
+        }
+    }
+
+    fn send(room: &Mutex<Box<dyn Room>>, content: &str) -> Message {
+        let (message, _) = room
+            .lock()
+            .unwrap()
+            .process_action(
+                &"frank".to_string(),
+                &"F".to_string(),
+                UserAction::SendMessage(content.into()),
+            )
+            .unwrap();
+        message.unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_censored_per_viewer() {
+        let room = make_room();
+        send(&room, "hello");
+
+        let frank = "frank".to_string();
+        let update = ClientSync::new(None).next_update(
+            &views(&room),
+            empty_update(),
+            &frank,
+            &"E".to_string(),
+        );
+        let last = update.snapshot.unwrap().recent_messages.pop().unwrap();
+        assert!(last.was_censored);
+        assert_eq!(last.content, "***");
+
+        let update = ClientSync::new(None).next_update(
+            &views(&room),
+            empty_update(),
+            &frank,
+            &"F".to_string(),
+        );
+        let last = update.snapshot.unwrap().recent_messages.pop().unwrap();
+        assert!(!last.was_censored);
+        assert_eq!(last.content, "hello");
+    }
+
+    #[test]
+    fn test_updates_carry_only_what_changed() {
+        let room = make_room();
+        let frank = "frank".to_string();
+        let country = "E".to_string();
+        let mut sync = ClientSync::new(None);
+        let first = sync.next_update(&views(&room), empty_update(), &frank, &country);
+        assert!(first.snapshot.is_some());
+        assert!(first.roster.is_none());
+
+        let message = send(&room, "hello");
+        room.lock().unwrap().close();
+        let update = sync.next_update(&views(&room), empty_update(), &frank, &country);
+        assert!(update.snapshot.is_none());
+        assert_eq!(update.roster.unwrap().phase, RoomPhase::Closed);
+        assert_eq!(update.new_messages.len(), 1);
+        assert_eq!(update.new_messages[0].id, message.id);
+        assert!(update.new_messages[0].was_censored);
+
+        let update = sync.next_update(&views(&room), empty_update(), &frank, &country);
+        assert!(update.snapshot.is_none() && update.roster.is_none());
+        assert!(update.new_messages.is_empty());
+
+        // A client that asks for a snapshot or changes country gets one
+        sync.handle(SyncRequest::Snapshot);
+        assert!(
+            sync.next_update(&views(&room), empty_update(), &frank, &country)
+                .snapshot
+                .is_some()
+        );
+        let update = sync.next_update(&views(&room), empty_update(), &frank, &"F".to_string());
+        assert_eq!(
+            update
+                .snapshot
+                .unwrap()
+                .recent_messages
+                .last()
+                .unwrap()
+                .content,
+            "hello"
+        );
+    }
+
+    #[test]
+    fn test_resuming_client_gets_missed_messages() {
+        let room = make_room();
+        let seen = send(&room, "hello");
+        let missed = send(&room, "hello");
+
+        let mut sync = ClientSync::new(Some(seen.id));
+        let update = sync.next_update(
+            &views(&room),
+            empty_update(),
+            &"frank".to_string(),
+            &"F".to_string(),
+        );
+        assert!(update.snapshot.is_none());
+        let ids: Vec<MessageId> = update.new_messages.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![missed.id]);
+    }
+
+    #[test]
+    fn test_client_too_far_behind_gets_snapshot() {
+        let room = make_room();
+        let frank = "frank".to_string();
+        let country = "F".to_string();
+        let mut sync = ClientSync::new(None);
+        sync.next_update(&views(&room), empty_update(), &frank, &country);
+
+        for _ in 0..MAX_UNACKED_MESSAGES {
+            send(&room, "hello");
+        }
+        let update = sync.next_update(&views(&room), empty_update(), &frank, &country);
+        assert_eq!(update.new_messages.len(), MAX_UNACKED_MESSAGES);
+        sync.handle(SyncRequest::Ack(update.new_messages[0].id));
+
+        send(&room, "hello");
+        let update = sync.next_update(&views(&room), empty_update(), &frank, &country);
+        assert!(update.new_messages.len() == 1 && update.snapshot.is_none());
+
+        send(&room, "hello");
+        let update = sync.next_update(&views(&room), empty_update(), &frank, &country);
+        assert!(update.snapshot.is_some());
+        assert!(update.new_messages.is_empty());
+    }
+
+    #[test]
+    fn test_client_missing_dropped_messages_gets_snapshot() {
+        let settings = RoomSettings {
+            seed: Some(42),
+            history_cap: 1,
+            ..Default::default()
+        };
+        let mut room = ChatRoom::generate(
+            "test_room".to_string(),
+            &FilterConfig::default(),
+            &crate::packs::WordPack::builtin(),
+            &settings,
+        )
+        .unwrap();
+        room.add_participant("frank".to_string(), "F".to_string());
+        room.skip_lobby();
+        let word = room.allowed_words()[0].clone();
+        let room: Mutex<Box<dyn Room>> = Mutex::new(Box::new(room));
+        for _ in 0..3 {
+            send(&room, &word);
+        }
+        room.lock().unwrap().drop_saved_messages(4);
+
+        let update = ClientSync::new(Some(2)).next_update(
+            &views(&room),
+            empty_update(),
+            &"frank".to_string(),
+            &"F".to_string(),
+        );
+        let snapshot = update.snapshot.unwrap();
+        assert_eq!(snapshot.recent_messages.len(), 1);
+        assert!(update.new_messages.is_empty());
+    }
+
+    #[test]
+    fn test_lag_policies() {
+        let room = make_room();
+        let country = "F".to_string();
+        let mut sync = ClientSync::new(None);
+        sync.next_update(
+            &views(&room),
+            empty_update(),
+            &"frank".to_string(),
+            &country,
+        );
+
+        assert!(!LagPolicy::Disconnect.keep_up(&mut sync));
+        assert!(LagPolicy::Resync.keep_up(&mut sync));
+        let update = sync.next_update(
+            &views(&room),
+            empty_update(),
+            &"frank".to_string(),
+            &country,
+        );
+        assert!(update.snapshot.is_some());
+    }
+
+    #[test]
+    fn test_parse_sync_requests() {
+        assert_eq!(
+            from_str::<SyncRequest>(r#"{"ack": 7}"#).unwrap(),
+            SyncRequest::Ack(7)
+        );
+        assert_eq!(
+            from_str::<SyncRequest>(r#""snapshot""#).unwrap(),
+            SyncRequest::Snapshot
+        );
+        assert!(from_str::<SyncRequest>(r#"{"system": {"send_message": "hi"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_rejections_reach_only_their_player() {
+        let room = make_room();
+        let mut update = empty_update();
+        update.rejections.push(ActionRejection {
+            user_id: "frank".to_string(),
+            error: ActionError::NotHost,
+        });
+
+        let frank = ClientSync::new(None).next_update(
+            &views(&room),
+            update.clone(),
+            &"frank".to_string(),
+            &"".to_string(),
+        );
+        assert_eq!(frank.errors, vec![ActionError::NotHost.to_string()]);
+        let grace = ClientSync::new(None).next_update(
+            &views(&room),
+            update,
+            &"grace".to_string(),
+            &"".to_string(),
+        );
+        assert!(grace.errors.is_empty());
+    }
+
+    fn read_page(
+        room: &Mutex<Box<dyn Room>>,
+        query: HistoryQuery,
+        country: &CountryCode,
+    ) -> MessagePage {
+        let store = MemoryRoomStore::default();
+        let read = |range| {
+            let room = room.lock().unwrap();
+            std::future::ready(read_messages(&store, room.as_ref(), range))
+        };
+        futures::executor::block_on(history_page(room, query, country, read)).unwrap()
+    }
+
+    #[test]
+    fn test_history_pages_back_and_forth() {
+        let room = make_room();
+        for _ in 0..5 {
+            send(&room, "hello");
+        }
+        // Six messages, with the welcome message
+        let ids: Vec<MessageId> = room
+            .lock()
+            .unwrap()
+            .messages_after(0)
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        let page_ids = |page: &MessagePage| page.messages.iter().map(|m| m.id).collect::<Vec<_>>();
+        let query = |before, after| HistoryQuery {
+            before,
+            after,
+            limit: Some(2),
+        };
+
+        // The newest page first, then older ones
+        let page = read_page(&room, query(None, None), &"F".to_string());
+        assert_eq!(page_ids(&page), ids[4..]);
+        assert_eq!((page.older, page.newer), (Some(ids[4]), None));
+        let page = read_page(&room, query(page.older, None), &"F".to_string());
+        assert_eq!(page_ids(&page), ids[2..4]);
+        let page = read_page(&room, query(page.older, None), &"F".to_string());
+        assert_eq!(page_ids(&page), ids[..2]);
+        assert_eq!((page.older, page.newer), (None, Some(ids[1])));
+
+        // Forward from a message
+        let page = read_page(&room, query(None, Some(ids[0])), &"F".to_string());
+        assert_eq!(page_ids(&page), ids[1..3]);
+        assert_eq!((page.older, page.newer), (Some(ids[1]), Some(ids[2])));
+
+        // Between two messages
+        let page = read_page(&room, query(Some(ids[3]), Some(ids[0])), &"F".to_string());
+        assert_eq!(page_ids(&page), ids[1..3]);
+    }
+
+    #[test]
+    fn test_history_censored_for_viewer() {
+        let room = make_room();
+        send(&room, "hello");
+
+        let page = read_page(&room, HistoryQuery::default(), &"E".to_string());
+        assert_eq!(page.messages.last().unwrap().content, "***");
+        let page = read_page(&room, HistoryQuery::default(), &"F".to_string());
+        assert_eq!(page.messages.last().unwrap().content, "hello");
+
+        // Anyone who never played reads the room as a spectator
+        let room = room.lock().unwrap();
+        assert_eq!(history_country(room.as_ref(), &"zoe".to_string()), "");
+    }
+
+    #[test]
+    fn test_solve_answer_treats_phrase_as_unit() {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("E".to_string(), vec!["not obey".to_string()]);
+        let config = FilterConfig {
+            banned_words,
+            ..Default::default()
+        };
+        let mut room: Box<dyn Room> = Box::new(ChatRoom::new("test_room".to_string(), &config));
+
+        let mut answer = room.filter_config().answer_key();
+        answer.insert("E".to_string(), vec!["not".to_string(), "obey".to_string()]);
+        assert!(!solve_answer(&mut room, answer.clone()));
+
+        answer.insert("E".to_string(), vec!["Not Obey".to_string()]);
+        assert!(solve_answer(&mut room, answer));
+    }
+
+    #[test]
+    fn test_parse_room_settings() {
+        assert_eq!(parse_room_settings(b"").unwrap(), RoomSettings::default());
+        assert_eq!(parse_room_settings(b"{}").unwrap(), RoomSettings::default());
+
+        let settings = parse_room_settings(
+            br#"{"shadow_ban": true, "max_players": 3, "seed": 7, "bans_per_country": 2}"#,
+        )
+        .unwrap();
+        assert!(settings.shadow_ban);
+        assert!(settings.sender_censor);
+        assert_eq!(settings.max_players, Some(3));
+        assert_eq!(settings.seed, Some(7));
+        assert_eq!(settings.generation.bans_per_country, 2);
+        assert_eq!(settings.generation.country_count, 4);
+
+        assert!(parse_room_settings(br#"{"max_players": "many"}"#).is_err());
+    }
+
+    /// Creates rooms whose game is under way, where bob said a word banned
+    /// for alice's country after alice left.
+    struct ReturningPlayerConfig {
+        filter_config: FilterConfig,
+        word_packs: WordPackRegistry,
+    }
+
+    impl RoomConfig for ReturningPlayerConfig {
+        fn get_filter_config(&self) -> &FilterConfig {
+            &self.filter_config
+        }
+
+        fn word_packs(&self) -> &WordPackRegistry {
+            &self.word_packs
+        }
+
+        fn init_room(
+            &self,
+            room_id: RoomId,
+            pack: &WordPack,
+            settings: &RoomSettings,
+        ) -> Result<Box<dyn Room>, GenerationError> {
+            let mut room = ChatRoom::generate(room_id, &self.filter_config, pack, settings)?;
+            room.add_participant("alice".to_string(), "A".to_string());
+            room.add_participant("bob".to_string(), "B".to_string());
+            room.skip_lobby();
+            room.remove_participant(&"alice".to_string());
+            let banned = &room.filter_config().banned_words;
+            let word = banned["A"]
+                .iter()
+                .find(|word| !banned["B"].contains(word))
+                .unwrap()
+                .clone();
+            room.process_action(
+                &"bob".to_string(),
+                &"B".to_string(),
+                UserAction::SendMessage(word),
+            )
+            .unwrap();
+            Ok(Box::new(room))
+        }
+
+        fn restore_room(
+            &self,
+            pack: &WordPack,
+            stored: StoredRoom,
+        ) -> Result<Box<dyn Room>, GenerationError> {
+            Ok(Box::new(ChatRoom::restore(
+                &self.filter_config,
+                pack,
+                stored,
+            )?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_first_frame_censored_for_seat() {
+        let manager = RoomManager::from_config(ReturningPlayerConfig {
+            filter_config: FilterConfig::default(),
+            word_packs: WordPackRegistry::default(),
+        });
+        let settings = RoomSettings {
+            seed: Some(7),
+            ..Default::default()
+        };
+        let room_id = Arc::clone(&manager).create_and_run_room(&settings).unwrap();
+        let connector = manager.connect_to_room(&room_id).unwrap();
+        let last = |state: RoomState| state.recent_messages.last().unwrap().clone();
+        // Spectators see the word, so a first frame sent before the join
+        // would show it
+        let spectated = last(connector.updates.views().state_for(&String::new()));
+        assert!(!spectated.was_censored);
+
+        let alice = AuthenticatedUser {
+            user_id: "alice".to_string(),
+            country: "B".to_string(),
+        };
+        let (version, mut receiver) = connector.updates.subscribe();
+        let update = join_room(
+            &alice,
+            &connector.action_sender,
+            &connector.updates,
+            &mut receiver,
+            version,
+        )
+        .await
+        .unwrap();
+        let views = connector.updates.views();
+        let country = views.country_of(&alice.user_id);
+        assert_eq!(country, "A");
+        let first = ClientSync::new(None).next_update(&views, update, &alice.user_id, &country);
+        let seen = last(first.snapshot.unwrap());
+        assert!(seen.was_censored);
+        assert_ne!(seen.content, spectated.content);
+    }
 }
@@ -0,0 +1,978 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use tokio::sync::oneshot;
+
+use crate::accounts::Account;
+use crate::archive::{ArchivedGame, GameId, GameSummary};
+use crate::data::*;
+use crate::events::LogEntry;
+use crate::game::GameSnapshot;
+
+/// Environment variable naming the SQLite database rooms are saved to.
+pub const DB_PATH_ENV: &str = "BABEL_DB_PATH";
+
+/// Database file rooms are saved to when `DB_PATH_ENV` is unset.
+pub const DEFAULT_DB_PATH: &str = "babel.db";
+
+/// Everything needed to restore a room apart from its messages.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomRecord {
+    pub room_id: RoomId,
+    /// Settings with the seed the room used, so its words can be regenerated.
+    pub settings: RoomSettings,
+    pub participants: Vec<Participant>,
+    /// Everyone who has joined, including those who have left.
+    #[serde(default)]
+    pub players: Vec<Participant>,
+    #[serde(default = "already_started")]
+    pub phase: RoomPhase,
+    /// Participants ready for the game to start.
+    #[serde(default)]
+    pub ready: Vec<UserId>,
+    pub message_counter: MessageId,
+    /// Number of messages players sent. Rooms saved before it was kept
+    /// count from zero.
+    #[serde(default)]
+    pub messages_sent: usize,
+    pub game: GameSnapshot,
+}
+
+/// Rooms saved before rooms had phases were played from the start.
+fn already_started() -> RoomPhase {
+    RoomPhase::InProgress
+}
+
+/// A saved room with its messages in id order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredRoom {
+    pub record: RoomRecord,
+    pub messages: Vec<Message>,
+}
+
+/// Why the store could not save or load rooms.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The database rejected a query.
+    Database(rusqlite::Error),
+    /// A saved record could not be encoded or decoded.
+    Encoding(serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Database(err) => write!(f, "database error: {err}"),
+            Self::Encoding(err) => write!(f, "invalid room record: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Encoding(err)
+    }
+}
+
+/// Durable storage for active rooms: their state, messages and event logs.
+pub trait RoomStore: Send + Sync {
+    /// Save the room's record and append messages it has not saved yet.
+    fn save_room(&self, record: &RoomRecord, new_messages: &[Message]) -> Result<(), StoreError>;
+
+    /// Load every saved room with its last `history_cap` messages, the ones
+    /// the room keeps in memory. Older messages are read with
+    /// `load_messages`.
+    fn load_rooms(&self) -> Result<Vec<StoredRoom>, StoreError>;
+
+    /// Load a range of the room's saved messages, in id order.
+    fn load_messages(
+        &self,
+        room_id: &RoomId,
+        range: MessageRange,
+    ) -> Result<Vec<Message>, StoreError>;
+
+    /// Append an entry to the room's event log.
+    fn append_log(&self, room_id: &RoomId, entry: &LogEntry) -> Result<(), StoreError>;
+
+    /// Load the room's event log in the order it was appended.
+    fn load_log(&self, room_id: &RoomId) -> Result<Vec<LogEntry>, StoreError>;
+
+    /// Forget a room, its messages and its log. Archive the room's game
+    /// first to keep them.
+    fn delete_room(&self, room_id: &RoomId) -> Result<(), StoreError>;
+}
+
+/// Durable storage for finished games.
+pub trait ArchiveStore: Send + Sync {
+    /// Keep a finished game and return the id it was given.
+    fn archive_game(&self, game: &ArchivedGame) -> Result<GameId, StoreError>;
+
+    /// Replace the archived game with this id, keeping its id.
+    fn replace_game(&self, id: GameId, game: &ArchivedGame) -> Result<(), StoreError>;
+
+    /// Summaries of every archived game, oldest first.
+    fn list_games(&self) -> Result<Vec<GameSummary>, StoreError>;
+
+    /// Load an archived game, if there is one with this id.
+    fn load_game(&self, id: GameId) -> Result<Option<ArchivedGame>, StoreError>;
+}
+
+/// Durable storage for registered accounts.
+pub trait AccountStore: Send + Sync {
+    /// Save an account, replacing any saved account with the same user id.
+    fn save_account(&self, account: &Account) -> Result<(), StoreError>;
+
+    /// Load every saved account.
+    fn load_accounts(&self) -> Result<Vec<Account>, StoreError>;
+}
+
+/// Keeps rooms in memory only, so nothing survives a restart.
+#[derive(Default)]
+pub struct MemoryRoomStore {
+    rooms: Mutex<HashMap<RoomId, StoredRoom>>,
+    logs: Mutex<HashMap<RoomId, Vec<LogEntry>>>,
+    /// Archived games; a game's id is its position plus one.
+    games: Mutex<Vec<ArchivedGame>>,
+    accounts: Mutex<HashMap<UserId, Account>>,
+}
+
+impl RoomStore for MemoryRoomStore {
+    fn save_room(&self, record: &RoomRecord, new_messages: &[Message]) -> Result<(), StoreError> {
+        let mut rooms = self.rooms.lock().unwrap();
+        let stored = rooms
+            .entry(record.room_id.clone())
+            .or_insert_with(|| StoredRoom {
+                record: record.clone(),
+                messages: Vec::new(),
+            });
+        stored.record = record.clone();
+        let last_id = stored.messages.last().map_or(0, |m| m.id);
+        stored
+            .messages
+            .extend(new_messages.iter().filter(|m| m.id > last_id).cloned());
+        Ok(())
+    }
+
+    fn load_rooms(&self) -> Result<Vec<StoredRoom>, StoreError> {
+        let rooms = self.rooms.lock().unwrap();
+        Ok(rooms
+            .values()
+            .map(|stored| {
+                let cap = stored.record.settings.history_cap;
+                let kept = stored.messages.len().saturating_sub(cap);
+                StoredRoom {
+                    record: stored.record.clone(),
+                    messages: stored.messages[kept..].to_vec(),
+                }
+            })
+            .collect())
+    }
+
+    fn load_messages(
+        &self,
+        room_id: &RoomId,
+        range: MessageRange,
+    ) -> Result<Vec<Message>, StoreError> {
+        let rooms = self.rooms.lock().unwrap();
+        Ok(rooms
+            .get(room_id)
+            .map(|stored| range.select(&stored.messages).to_vec())
+            .unwrap_or_default())
+    }
+
+    fn append_log(&self, room_id: &RoomId, entry: &LogEntry) -> Result<(), StoreError> {
+        self.logs
+            .lock()
+            .unwrap()
+            .entry(room_id.clone())
+            .or_default()
+            .push(entry.clone());
+        Ok(())
+    }
+
+    fn load_log(&self, room_id: &RoomId) -> Result<Vec<LogEntry>, StoreError> {
+        Ok(self
+            .logs
+            .lock()
+            .unwrap()
+            .get(room_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn delete_room(&self, room_id: &RoomId) -> Result<(), StoreError> {
+        self.rooms.lock().unwrap().remove(room_id);
+        self.logs.lock().unwrap().remove(room_id);
+        Ok(())
+    }
+}
+
+impl ArchiveStore for MemoryRoomStore {
+    fn archive_game(&self, game: &ArchivedGame) -> Result<GameId, StoreError> {
+        let mut games = self.games.lock().unwrap();
+        let id = games.len() as GameId + 1;
+        let mut game = game.clone();
+        game.summary.id = id;
+        games.push(game);
+        Ok(id)
+    }
+
+    fn replace_game(&self, id: GameId, game: &ArchivedGame) -> Result<(), StoreError> {
+        let mut games = self.games.lock().unwrap();
+        let index = (id as usize).checked_sub(1);
+        if let Some(slot) = index.and_then(|index| games.get_mut(index)) {
+            *slot = game.clone();
+            slot.summary.id = id;
+        }
+        Ok(())
+    }
+
+    fn list_games(&self) -> Result<Vec<GameSummary>, StoreError> {
+        let games = self.games.lock().unwrap();
+        Ok(games.iter().map(|game| game.summary.clone()).collect())
+    }
+
+    fn load_game(&self, id: GameId) -> Result<Option<ArchivedGame>, StoreError> {
+        let games = self.games.lock().unwrap();
+        let index = (id as usize).checked_sub(1);
+        Ok(index.and_then(|index| games.get(index)).cloned())
+    }
+}
+
+impl AccountStore for MemoryRoomStore {
+    fn save_account(&self, account: &Account) -> Result<(), StoreError> {
+        self.accounts
+            .lock()
+            .unwrap()
+            .insert(account.user_id.clone(), account.clone());
+        Ok(())
+    }
+
+    fn load_accounts(&self) -> Result<Vec<Account>, StoreError> {
+        Ok(self.accounts.lock().unwrap().values().cloned().collect())
+    }
+}
+
+fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Message> {
+    Ok(Message {
+        id: row.get::<_, i64>(0)? as MessageId,
+        sender_id: row.get(1)?,
+        sender_country: row.get(2)?,
+        content: row.get(3)?,
+        timestamp: row.get::<_, i64>(4)? as Timestamp,
+    })
+}
+
+/// A range of a room's messages, split between those the room still keeps in
+/// memory and the older ones it let go of, which only the store has.
+pub struct MessageRead {
+    range: MessageRange,
+    in_memory: Vec<Message>,
+    /// The part of the range to load from the store, if the room let go of
+    /// some of it.
+    pub stored: Option<MessageRange>,
+}
+
+impl MessageRead {
+    pub fn new(room: &dyn Room, range: MessageRange) -> Self {
+        let kept = room.messages_after(0);
+        let kept_from = kept.first().map_or(MessageId::MAX, |m| m.id);
+        // Every message the room let go of was saved first
+        let stored = (range.after.saturating_add(1) < kept_from).then_some(MessageRange {
+            before: range.before.min(kept_from),
+            ..range
+        });
+        Self {
+            range,
+            in_memory: range.select(kept).to_vec(),
+            stored,
+        }
+    }
+
+    /// The messages of the range, given those loaded from the store.
+    pub fn finish(self, mut stored: Vec<Message>) -> Vec<Message> {
+        if self.stored.is_none() {
+            return self.in_memory;
+        }
+        stored.extend(self.in_memory);
+        self.range.select(&stored).to_vec()
+    }
+}
+
+/// Reads a range of a room's messages: from memory where the room still
+/// keeps them, and from the store for older ones it let go of.
+pub fn read_messages(
+    store: &dyn RoomStore,
+    room: &dyn Room,
+    range: MessageRange,
+) -> Result<Vec<Message>, StoreError> {
+    let read = MessageRead::new(room, range);
+    let stored = match read.stored {
+        Some(older) => store.load_messages(room.room_id(), older)?,
+        None => Vec::new(),
+    };
+    Ok(read.finish(stored))
+}
+
+/// The stores a room manager keeps its rooms and finished games in.
+pub struct Stores {
+    pub rooms: Arc<dyn RoomStore>,
+    pub archive: Arc<dyn ArchiveStore>,
+}
+
+type StoreJob = Box<dyn FnOnce(&Stores) + Send>;
+
+/// Runs store calls one at a time on a thread of its own, so a slow disk
+/// holds up neither the async runtime nor a room's lock. Calls run in the
+/// order they were queued: one queued while holding a room's lock sees the
+/// room's events and messages up to then, and none after.
+#[derive(Clone)]
+pub struct StoreWorker {
+    jobs: mpsc::Sender<StoreJob>,
+}
+
+impl StoreWorker {
+    /// Start the worker thread. It stops once every handle is dropped.
+    pub fn spawn(stores: Stores) -> Self {
+        let (jobs, queue) = mpsc::channel::<StoreJob>();
+        thread::Builder::new()
+            .name("store".to_string())
+            .spawn(move || {
+                for job in queue {
+                    job(&stores);
+                }
+            })
+            .expect("the store thread starts");
+        Self { jobs }
+    }
+
+    /// Queue a call without waiting for it.
+    pub fn queue(&self, job: impl FnOnce(&Stores) + Send + 'static) {
+        // The thread only stops early if a call panicked
+        let _ = self.jobs.send(Box::new(job));
+    }
+
+    /// Queue a call now and return its result once it ran.
+    pub fn run<T, F>(&self, job: F) -> impl Future<Output = T> + use<T, F>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Stores) -> T + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        self.queue(move |stores| {
+            let _ = reply.send(job(stores));
+        });
+        async move { result.await.expect("the store thread stopped") }
+    }
+}
+
+/// Keeps rooms in an embedded SQLite database.
+pub struct SqliteRoomStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteRoomStore {
+    /// Open or create the database file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Open a database that lives only as long as the store.
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, StoreError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS rooms (
+                room_id TEXT PRIMARY KEY,
+                record TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS messages (
+                room_id TEXT NOT NULL,
+                id INTEGER NOT NULL,
+                sender_id TEXT NOT NULL,
+                sender_country TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                PRIMARY KEY (room_id, id)
+            );
+            CREATE TABLE IF NOT EXISTS room_log (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                room_id TEXT NOT NULL,
+                entry TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS room_log_room ON room_log (room_id, seq);
+            CREATE TABLE IF NOT EXISTS games (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                summary TEXT NOT NULL,
+                game TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS accounts (
+                user_id TEXT PRIMARY KEY,
+                account TEXT NOT NULL
+            );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl RoomStore for SqliteRoomStore {
+    fn save_room(&self, record: &RoomRecord, new_messages: &[Message]) -> Result<(), StoreError> {
+        let json = serde_json::to_string(record)?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO rooms (room_id, record) VALUES (?1, ?2)
+             ON CONFLICT (room_id) DO UPDATE SET record = excluded.record",
+            params![record.room_id, json],
+        )?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO messages
+                 (room_id, id, sender_id, sender_country, content, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for message in new_messages {
+                insert.execute(params![
+                    record.room_id,
+                    message.id as i64,
+                    message.sender_id,
+                    message.sender_country,
+                    message.content,
+                    message.timestamp as i64,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_rooms(&self) -> Result<Vec<StoredRoom>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut select_rooms = conn.prepare("SELECT record FROM rooms ORDER BY room_id")?;
+        let records = select_rooms
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut select_messages = conn.prepare(
+            "SELECT id, sender_id, sender_country, content, timestamp
+             FROM messages WHERE room_id = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let mut rooms = Vec::with_capacity(records.len());
+        for json in records {
+            let record: RoomRecord = serde_json::from_str(&json)?;
+            let cap = record.settings.history_cap.min(i64::MAX as usize) as i64;
+            let mut messages = select_messages
+                .query_map(params![record.room_id, cap], message_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            messages.reverse();
+            rooms.push(StoredRoom { record, messages });
+        }
+        Ok(rooms)
+    }
+
+    fn load_messages(
+        &self,
+        room_id: &RoomId,
+        range: MessageRange,
+    ) -> Result<Vec<Message>, StoreError> {
+        let order = if range.from_oldest { "ASC" } else { "DESC" };
+        let conn = self.conn.lock().unwrap();
+        let mut select = conn.prepare(&format!(
+            "SELECT id, sender_id, sender_country, content, timestamp
+             FROM messages WHERE room_id = ?1 AND id > ?2 AND id < ?3
+             ORDER BY id {order} LIMIT ?4"
+        ))?;
+        // SQLite integers are signed
+        let as_sql = |n: u64| n.min(i64::MAX as u64) as i64;
+        let mut messages = select
+            .query_map(
+                params![
+                    room_id,
+                    as_sql(range.after),
+                    as_sql(range.before),
+                    as_sql(range.limit as u64)
+                ],
+                message_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        if !range.from_oldest {
+            messages.reverse();
+        }
+        Ok(messages)
+    }
+
+    fn append_log(&self, room_id: &RoomId, entry: &LogEntry) -> Result<(), StoreError> {
+        let json = serde_json::to_string(entry)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO room_log (room_id, entry) VALUES (?1, ?2)",
+            params![room_id, json],
+        )?;
+        Ok(())
+    }
+
+    fn load_log(&self, room_id: &RoomId) -> Result<Vec<LogEntry>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut select =
+            conn.prepare("SELECT entry FROM room_log WHERE room_id = ?1 ORDER BY seq")?;
+        let entries = select
+            .query_map([room_id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        entries
+            .iter()
+            .map(|json| Ok(serde_json::from_str(json)?))
+            .collect()
+    }
+
+    fn delete_room(&self, room_id: &RoomId) -> Result<(), StoreError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM room_log WHERE room_id = ?1", [room_id])?;
+        tx.execute("DELETE FROM messages WHERE room_id = ?1", [room_id])?;
+        tx.execute("DELETE FROM rooms WHERE room_id = ?1", [room_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl ArchiveStore for SqliteRoomStore {
+    // The id column is the source of truth; the saved JSON carries no id
+    fn archive_game(&self, game: &ArchivedGame) -> Result<GameId, StoreError> {
+        let summary = serde_json::to_string(&game.summary)?;
+        let json = serde_json::to_string(game)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO games (summary, game) VALUES (?1, ?2)",
+            params![summary, json],
+        )?;
+        Ok(conn.last_insert_rowid() as GameId)
+    }
+
+    fn replace_game(&self, id: GameId, game: &ArchivedGame) -> Result<(), StoreError> {
+        let summary = serde_json::to_string(&game.summary)?;
+        let json = serde_json::to_string(game)?;
+        self.conn.lock().unwrap().execute(
+            "UPDATE games SET summary = ?2, game = ?3 WHERE id = ?1",
+            params![id as i64, summary, json],
+        )?;
+        Ok(())
+    }
+
+    fn list_games(&self) -> Result<Vec<GameSummary>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut select = conn.prepare("SELECT id, summary FROM games ORDER BY id")?;
+        let rows = select
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)? as GameId, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(id, json)| {
+                let mut summary: GameSummary = serde_json::from_str(&json)?;
+                summary.id = id;
+                Ok(summary)
+            })
+            .collect()
+    }
+
+    fn load_game(&self, id: GameId) -> Result<Option<ArchivedGame>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let json = conn
+            .query_row("SELECT game FROM games WHERE id = ?1", [id as i64], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?;
+        let Some(json) = json else {
+            return Ok(None);
+        };
+        let mut game: ArchivedGame = serde_json::from_str(&json)?;
+        game.summary.id = id;
+        Ok(Some(game))
+    }
+}
+
+impl AccountStore for SqliteRoomStore {
+    fn save_account(&self, account: &Account) -> Result<(), StoreError> {
+        let json = serde_json::to_string(account)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO accounts (user_id, account) VALUES (?1, ?2)
+             ON CONFLICT (user_id) DO UPDATE SET account = excluded.account",
+            params![account.user_id, json],
+        )?;
+        Ok(())
+    }
+
+    fn load_accounts(&self) -> Result<Vec<Account>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut select = conn.prepare("SELECT account FROM accounts ORDER BY user_id")?;
+        let accounts = select
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        accounts
+            .iter()
+            .map(|json| Ok(serde_json::from_str(json)?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::AccountKind;
+    use crate::events::RoomEvent;
+    use std::collections::HashSet;
+
+    fn make_record(room_id: &str) -> RoomRecord {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("A".to_string(), vec!["freedom".to_string()]);
+        RoomRecord {
+            room_id: room_id.to_string(),
+            settings: RoomSettings {
+                seed: Some(7),
+                ..Default::default()
+            },
+            participants: vec![Participant {
+                user_id: "alice".to_string(),
+                country: "A".to_string(),
+                joined_at: 100,
+                disconnected_at: None,
+            }],
+            players: vec![Participant {
+                user_id: "alice".to_string(),
+                country: "A".to_string(),
+                joined_at: 100,
+                disconnected_at: None,
+            }],
+            phase: RoomPhase::InProgress,
+            ready: vec![],
+            message_counter: 2,
+            messages_sent: 0,
+            game: GameSnapshot {
+                banned_words,
+                player_notes: HashMap::new(),
+                allowed_countries: HashSet::new(),
+                victory_achieved: false,
+                victory_timestamp: None,
+                started_at: 100,
+                pending_escalations: Vec::new(),
+            },
+        }
+    }
+
+    fn make_message(id: MessageId, content: &str) -> Message {
+        Message {
+            id,
+            sender_id: "alice".to_string(),
+            sender_country: "A".to_string(),
+            content: content.to_string(),
+            timestamp: 100 + id,
+        }
+    }
+
+    fn check_round_trip(store: &dyn RoomStore) {
+        let mut record = make_record("room_1");
+        let first = [make_message(1, "welcome"), make_message(2, "hello")];
+        store.save_room(&record, &first).unwrap();
+
+        // Later saves update the record and only add new messages
+        record.message_counter = 3;
+        record.game.victory_achieved = true;
+        record.game.victory_timestamp = Some(200);
+        store
+            .save_room(&record, &[first[1].clone(), make_message(3, "you")])
+            .unwrap();
+        store.save_room(&make_record("room_2"), &[]).unwrap();
+
+        let mut rooms = store.load_rooms().unwrap();
+        rooms.sort_by(|a, b| a.record.room_id.cmp(&b.record.room_id));
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[0].record, record);
+        let contents: Vec<&str> = rooms[0]
+            .messages
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["welcome", "hello", "you"]);
+        assert!(rooms[1].messages.is_empty());
+
+        let load = |after, before, limit, from_oldest| -> Vec<MessageId> {
+            let range = MessageRange {
+                after,
+                before,
+                limit,
+                from_oldest,
+            };
+            let messages = store.load_messages(&"room_1".to_string(), range);
+            messages.unwrap().iter().map(|m| m.id).collect()
+        };
+        assert_eq!(load(0, MessageId::MAX, 2, false), vec![2, 3]);
+        assert_eq!(load(0, MessageId::MAX, 2, true), vec![1, 2]);
+        assert_eq!(load(1, 3, 10, true), vec![2]);
+
+        store.delete_room(&"room_1".to_string()).unwrap();
+        let rooms = store.load_rooms().unwrap();
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].record.room_id, "room_2");
+
+        // Rooms load only the messages they keep in memory
+        let mut capped = make_record("room_3");
+        capped.settings.history_cap = 2;
+        let messages: Vec<Message> = (1..=3).map(|id| make_message(id, "hello")).collect();
+        store.save_room(&capped, &messages).unwrap();
+        let rooms = store.load_rooms().unwrap();
+        let room = rooms.iter().find(|r| r.record.room_id == "room_3").unwrap();
+        let ids: Vec<MessageId> = room.messages.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+    }
+
+    fn check_log(store: &dyn RoomStore) {
+        let room_id = "room_1".to_string();
+        let entries = [
+            LogEntry {
+                timestamp: 100,
+                seed: 7,
+                event: RoomEvent::Created {
+                    settings: make_record("room_1").settings,
+                },
+            },
+            LogEntry {
+                timestamp: 101,
+                seed: 7,
+                event: RoomEvent::UserMessage(UserMessage {
+                    user_id: "alice".to_string(),
+                    country: "A".to_string(),
+                    action: UserAction::SendMessage("hello".to_string()),
+                }),
+            },
+            LogEntry {
+                timestamp: 102,
+                seed: 7,
+                event: RoomEvent::Tick,
+            },
+        ];
+        for entry in &entries {
+            store.append_log(&room_id, entry).unwrap();
+        }
+        store.save_room(&make_record("room_1"), &[]).unwrap();
+
+        assert_eq!(store.load_log(&room_id).unwrap(), entries);
+        assert!(store.load_log(&"room_2".to_string()).unwrap().is_empty());
+
+        store.delete_room(&room_id).unwrap();
+        assert!(store.load_log(&room_id).unwrap().is_empty());
+    }
+
+    fn make_game(room_id: &str) -> ArchivedGame {
+        let record = make_record(room_id);
+        let message = make_message(1, "welcome");
+        let view = CensoredMessage {
+            id: 1,
+            sender_id: message.sender_id.clone(),
+            content: message.content.clone(),
+            was_censored: false,
+            hidden: false,
+        };
+        ArchivedGame {
+            summary: GameSummary {
+                id: 0,
+                room_id: record.room_id,
+                pack: record.settings.pack.clone(),
+                settings: record.settings,
+                players: record.players,
+                countries: vec!["A".to_string()],
+                banned_words: record.game.banned_words,
+                started_at: 100,
+                ended_at: 160,
+                duration: 60,
+                victory: false,
+                message_count: 1,
+            },
+            messages: vec![message],
+            views: [("A".to_string(), vec![view])].into(),
+            log: vec![LogEntry {
+                timestamp: 100,
+                seed: 7,
+                event: RoomEvent::Win,
+            }],
+        }
+    }
+
+    fn check_archive(store: &dyn ArchiveStore) {
+        assert!(store.list_games().unwrap().is_empty());
+
+        // A room id can be reused, so each archived game gets its own id
+        let first = store.archive_game(&make_game("room_1")).unwrap();
+        let second = store.archive_game(&make_game("room_1")).unwrap();
+        assert_ne!(first, second);
+
+        let games = store.list_games().unwrap();
+        let ids: Vec<GameId> = games.iter().map(|g| g.id).collect();
+        assert_eq!(ids, vec![first, second]);
+
+        let game = store.load_game(second).unwrap().unwrap();
+        let mut expected = make_game("room_1");
+        expected.summary.id = second;
+        assert_eq!(game.summary, expected.summary);
+        assert_eq!(game.messages, expected.messages);
+        assert_eq!(game.views["A"][0].content, "welcome");
+        assert_eq!(game.log, expected.log);
+
+        assert!(store.load_game(0).unwrap().is_none());
+        assert!(store.load_game(second + 1).unwrap().is_none());
+
+        // Replacing a game keeps its id and place in the listing
+        let mut closed = make_game("room_1");
+        closed.summary.ended_at = 200;
+        store.replace_game(first, &closed).unwrap();
+        let games = store.list_games().unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].id, first);
+        assert_eq!(games[0].ended_at, 200);
+        assert_eq!(store.load_game(first).unwrap().unwrap().summary.id, first);
+    }
+
+    fn check_accounts(store: &dyn AccountStore) {
+        let mut account = Account {
+            user_id: "alice".to_string(),
+            kind: AccountKind::Registered,
+            password_hash: Some("$argon2id$first".to_string()),
+            created_at: 100,
+            revoked_before: 0,
+            held_until: None,
+        };
+        store.save_account(&account).unwrap();
+        account.password_hash = Some("$argon2id$second".to_string());
+        store.save_account(&account).unwrap();
+        assert_eq!(store.load_accounts().unwrap(), vec![account]);
+    }
+
+    #[test]
+    fn test_memory_store_round_trip() {
+        check_round_trip(&MemoryRoomStore::default());
+        check_log(&MemoryRoomStore::default());
+        check_archive(&MemoryRoomStore::default());
+        check_accounts(&MemoryRoomStore::default());
+    }
+
+    #[test]
+    fn test_sqlite_store_round_trip() {
+        check_round_trip(&SqliteRoomStore::open_in_memory().unwrap());
+        check_log(&SqliteRoomStore::open_in_memory().unwrap());
+        check_archive(&SqliteRoomStore::open_in_memory().unwrap());
+        check_accounts(&SqliteRoomStore::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_store_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("babel_store_{}.db", std::process::id()));
+        {
+            let store = SqliteRoomStore::open(&path).unwrap();
+            store
+                .save_room(&make_record("room_1"), &[make_message(1, "welcome")])
+                .unwrap();
+        }
+        let store = SqliteRoomStore::open(&path).unwrap();
+        let rooms = store.load_rooms().unwrap();
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].record, make_record("room_1"));
+        assert_eq!(rooms[0].messages, vec![make_message(1, "welcome")]);
+
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_worker_runs_calls_in_queue_order() {
+        let store = Arc::new(MemoryRoomStore::default());
+        let worker = StoreWorker::spawn(Stores {
+            rooms: store.clone(),
+            archive: store,
+        });
+        let room_id = "room_1".to_string();
+        let entries: Vec<LogEntry> = (0..50)
+            .map(|timestamp| LogEntry {
+                timestamp,
+                seed: 7,
+                event: RoomEvent::Tick,
+            })
+            .collect();
+        for entry in entries.clone() {
+            let room_id = room_id.clone();
+            worker.queue(move |stores| stores.rooms.append_log(&room_id, &entry).unwrap());
+        }
+
+        // A read sees every write queued before it
+        let log = worker
+            .run(move |stores| stores.rooms.load_log(&room_id))
+            .await
+            .unwrap();
+        assert_eq!(log, entries);
+    }
+
+    #[test]
+    fn test_read_messages_spans_store_and_memory() {
+        use crate::packs::WordPack;
+        use crate::room::ChatRoom;
+
+        let settings = RoomSettings {
+            seed: Some(42),
+            history_cap: 2,
+            ..Default::default()
+        };
+        let mut room = ChatRoom::generate(
+            "room_1".to_string(),
+            &FilterConfig::default(),
+            &WordPack::builtin(),
+            &settings,
+        )
+        .unwrap();
+        room.add_participant("alice".to_string(), "A".to_string());
+        room.skip_lobby();
+        let word = room.allowed_words()[0].clone();
+        for _ in 0..4 {
+            room.process_action(
+                &"alice".to_string(),
+                &"A".to_string(),
+                UserAction::SendMessage(word.clone()),
+            )
+            .unwrap();
+        }
+        let store = SqliteRoomStore::open_in_memory().unwrap();
+        store
+            .save_room(&room.record(), room.messages_after(0))
+            .unwrap();
+        room.drop_saved_messages(5);
+        assert_eq!(room.messages_after(0).len(), 2);
+
+        let read = |after, before, limit, from_oldest| -> Vec<MessageId> {
+            let range = MessageRange {
+                after,
+                before,
+                limit,
+                from_oldest,
+            };
+            let messages = read_messages(&store, &room, range).unwrap();
+            messages.iter().map(|m| m.id).collect()
+        };
+        assert_eq!(read(0, MessageId::MAX, 10, true), vec![1, 2, 3, 4, 5]);
+        assert_eq!(read(0, MessageId::MAX, 3, false), vec![3, 4, 5]);
+        assert_eq!(read(1, MessageId::MAX, 3, true), vec![2, 3, 4]);
+        assert_eq!(read(3, MessageId::MAX, 3, true), vec![4, 5]);
+        assert_eq!(read(0, 4, 2, false), vec![2, 3]);
+    }
+}
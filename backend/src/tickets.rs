@@ -0,0 +1,104 @@
+use dashmap::DashMap;
+use rand::distr::{Alphanumeric, SampleString};
+
+use crate::data::*;
+use crate::utils;
+
+/// Seconds a connect ticket can be redeemed for after it is issued.
+pub const TICKET_TTL: u64 = 10;
+
+pub type Ticket = String;
+
+struct PendingTicket {
+    room_id: RoomId,
+    user_id: UserId,
+    country: CountryCode,
+    expires_at: Timestamp,
+}
+
+/// Single-use tickets that let an authenticated player open a room's
+/// WebSocket without putting their session token in the URL.
+#[derive(Default)]
+pub struct ConnectTickets {
+    pending: DashMap<Ticket, PendingTicket>,
+}
+
+impl ConnectTickets {
+    /// Issue a ticket for a player to connect to a room. Returns the ticket
+    /// and when it expires.
+    pub fn issue(
+        &self,
+        room_id: RoomId,
+        user_id: UserId,
+        country: CountryCode,
+    ) -> (Ticket, Timestamp) {
+        let now = utils::now();
+        // Tickets that were never redeemed would otherwise pile up
+        self.pending.retain(|_, pending| now < pending.expires_at);
+
+        let ticket = Alphanumeric.sample_string(&mut rand::rng(), 32);
+        let expires_at = now + TICKET_TTL;
+        self.pending.insert(
+            ticket.clone(),
+            PendingTicket {
+                room_id,
+                user_id,
+                country,
+                expires_at,
+            },
+        );
+        (ticket, expires_at)
+    }
+
+    /// Use up a ticket to connect to `room_id`, returning the player it was
+    /// issued to. Fails if the ticket is unknown, already used, expired or
+    /// issued for another room.
+    pub fn redeem(&self, ticket: &str, room_id: &RoomId) -> Option<(UserId, CountryCode)> {
+        let (_, pending) = self.pending.remove(ticket)?;
+        if &pending.room_id != room_id || utils::now() >= pending.expires_at {
+            return None;
+        }
+        Some((pending.user_id, pending.country))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticket_is_single_use() {
+        let tickets = ConnectTickets::default();
+        let room_id = "room_1".to_string();
+        let (ticket, _) = tickets.issue(room_id.clone(), "alice".to_string(), "A".to_string());
+
+        assert_eq!(
+            tickets.redeem(&ticket, &room_id),
+            Some(("alice".to_string(), "A".to_string()))
+        );
+        assert_eq!(tickets.redeem(&ticket, &room_id), None);
+    }
+
+    #[test]
+    fn test_ticket_is_bound_to_room() {
+        let tickets = ConnectTickets::default();
+        let (ticket, _) = tickets.issue("room_1".to_string(), "alice".to_string(), "A".to_string());
+
+        assert_eq!(tickets.redeem(&ticket, &"room_2".to_string()), None);
+        // A failed attempt uses the ticket up as well
+        assert_eq!(tickets.redeem(&ticket, &"room_1".to_string()), None);
+    }
+
+    #[test]
+    fn test_ticket_expires() {
+        let tickets = ConnectTickets::default();
+        let room_id = "room_1".to_string();
+        let (ticket, expires_at) = utils::at_time(1000, || {
+            tickets.issue(room_id.clone(), "alice".to_string(), "A".to_string())
+        });
+        assert_eq!(expires_at, 1000 + TICKET_TTL);
+
+        let redeemed = utils::at_time(expires_at, || tickets.redeem(&ticket, &room_id));
+        assert_eq!(redeemed, None);
+    }
+}
@@ -0,0 +1,189 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+
+use crate::data::*;
+
+/// Environment variable holding the key session tokens are signed with.
+/// Processes that share the key accept each other's tokens.
+pub const TOKEN_KEY_ENV: &str = "BABEL_TOKEN_KEY";
+
+/// Shortest signing key accepted, in bytes.
+pub const MIN_KEY_LEN: usize = 32;
+
+pub type SessionId = String;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a session token says about its holder.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// User id of the account the token was issued to.
+    pub sub: UserId,
+    pub country: CountryCode,
+    /// Session the token belongs to; refreshing keeps the same session.
+    pub sid: SessionId,
+    /// Issue time, Unix seconds.
+    pub iat: Timestamp,
+    /// Expiry time, Unix seconds. The token is refused from this time on.
+    pub exp: Timestamp,
+}
+
+/// Why a token was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenError {
+    /// The token is not two base64url parts holding claims and a signature.
+    Malformed,
+    /// The signature does not match the claims.
+    BadSignature,
+    /// The token has expired.
+    Expired,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed token"),
+            Self::BadSignature => write!(f, "invalid token signature"),
+            Self::Expired => write!(f, "token expired"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Why a signing key was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyTooShort(pub usize);
+
+impl fmt::Display for KeyTooShort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "signing key has {} bytes, at least {MIN_KEY_LEN} are needed",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for KeyTooShort {}
+
+/// Signs and checks session tokens with HMAC-SHA256.
+///
+/// A token is `<claims>.<signature>`, both base64url without padding, where
+/// the claims are JSON. Checking a token needs only the key.
+pub struct TokenSigner {
+    key: Vec<u8>,
+}
+
+impl TokenSigner {
+    pub fn new(key: impl Into<Vec<u8>>) -> Result<Self, KeyTooShort> {
+        let key = key.into();
+        if key.len() < MIN_KEY_LEN {
+            return Err(KeyTooShort(key.len()));
+        }
+        Ok(Self { key })
+    }
+
+    /// A signer with a random key. Its tokens stop working when it is dropped.
+    pub fn random() -> Self {
+        let mut key = vec![0u8; MIN_KEY_LEN];
+        rand::rng().fill_bytes(&mut key);
+        Self { key }
+    }
+
+    pub fn sign(&self, claims: &TokenClaims) -> String {
+        let claims = serde_json::to_vec(claims).expect("claims serialize to JSON");
+        let claims = URL_SAFE_NO_PAD.encode(claims);
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&claims).finalize().into_bytes());
+        format!("{claims}.{signature}")
+    }
+
+    /// The claims of a token signed with this key that has not expired by `now`.
+    pub fn verify(&self, token: &str, now: Timestamp) -> Result<TokenClaims, TokenError> {
+        let (claims, signature) = token.split_once('.').ok_or(TokenError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| TokenError::Malformed)?;
+        self.mac(claims)
+            .verify_slice(&signature)
+            .map_err(|_| TokenError::BadSignature)?;
+
+        let claims = URL_SAFE_NO_PAD
+            .decode(claims)
+            .map_err(|_| TokenError::Malformed)?;
+        let claims: TokenClaims =
+            serde_json::from_slice(&claims).map_err(|_| TokenError::Malformed)?;
+        if now >= claims.exp {
+            return Err(TokenError::Expired);
+        }
+        Ok(claims)
+    }
+
+    fn mac(&self, encoded_claims: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(encoded_claims.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_claims() -> TokenClaims {
+        TokenClaims {
+            sub: "alice".to_string(),
+            country: "A".to_string(),
+            sid: "session".to_string(),
+            iat: 1000,
+            exp: 2000,
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = TokenSigner::new("k".repeat(MIN_KEY_LEN)).unwrap();
+        let token = signer.sign(&make_claims());
+        assert_eq!(signer.verify(&token, 1500), Ok(make_claims()));
+        assert_eq!(signer.verify(&token, 2000), Err(TokenError::Expired));
+
+        // Another process with the same key accepts the token
+        let other = TokenSigner::new("k".repeat(MIN_KEY_LEN)).unwrap();
+        assert_eq!(other.verify(&token, 1500), Ok(make_claims()));
+    }
+
+    #[test]
+    fn test_rejects_forged_tokens() {
+        let signer = TokenSigner::random();
+        let token = signer.sign(&make_claims());
+
+        let other = TokenSigner::random();
+        assert_eq!(other.verify(&token, 1500), Err(TokenError::BadSignature));
+
+        // Changing the claims invalidates the signature
+        let (_, signature) = token.split_once('.').unwrap();
+        let mut forged = make_claims();
+        forged.exp = u64::MAX;
+        let forged_claims = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        assert_eq!(
+            signer.verify(&format!("{forged_claims}.{signature}"), 1500),
+            Err(TokenError::BadSignature)
+        );
+
+        assert_eq!(signer.verify("no-dot", 1500), Err(TokenError::Malformed));
+        assert_eq!(signer.verify("a.!!!", 1500), Err(TokenError::Malformed));
+    }
+
+    #[test]
+    fn test_rejects_short_keys() {
+        assert_eq!(
+            TokenSigner::new("short").err(),
+            Some(KeyTooShort("short".len()))
+        );
+    }
+}
@@ -1,7 +1,34 @@
 use serde::de::DeserializeOwned;
+use std::cell::Cell;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::data::Timestamp;
+
+thread_local! {
+    static FROZEN_NOW: Cell<Option<Timestamp>> = const { Cell::new(None) };
+}
+
+/// Current Unix time in seconds, or the time fixed by `at_time` on this thread.
+pub fn now() -> Timestamp {
+    FROZEN_NOW.with(Cell::get).unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    })
+}
+
+/// Run `f` with `now()` fixed to `timestamp` on this thread, so an event
+/// applies the same way live and when replayed from the room log.
+pub fn at_time<R>(timestamp: Timestamp, f: impl FnOnce() -> R) -> R {
+    let previous = FROZEN_NOW.with(|now| now.replace(Some(timestamp)));
+    let result = f();
+    FROZEN_NOW.with(|now| now.set(previous));
+    result
+}
 
 pub fn deserialize_from_file<T: DeserializeOwned + Default, P: AsRef<Path>>(path: P) -> T {
     let path = path.as_ref();
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use crate::data::*;
+
+/// The messages a room keeps in memory, censored for each country that views
+/// them and shared by every socket of the room. Each message is censored once
+/// per country, however many players and spectators watch the room.
+pub struct CensoredViews {
+    room_id: RoomId,
+    /// Censorship epoch of the room the views were censored in.
+    epoch: u64,
+    /// Id of the last message censored.
+    last_id: MessageId,
+    roster: RoomRoster,
+    /// The kept messages censored for each country, in id order. Messages a
+    /// firewall drops keep their place, marked hidden.
+    views: HashMap<CountryCode, Vec<CensoredMessage>>,
+}
+
+impl CensoredViews {
+    /// Censor the messages `room` keeps for every country with rules or a
+    /// seat in it, and for spectators, who have no country.
+    pub fn new(room: &dyn Room) -> Self {
+        let mut views = Self {
+            room_id: room.room_id().clone(),
+            epoch: room.censorship_epoch(),
+            last_id: 0,
+            roster: room.roster(),
+            views: HashMap::new(),
+        };
+        views.refresh(room);
+        views
+    }
+
+    /// Catch up with `room`: censor the messages it added since the last
+    /// refresh and forget those it let go of. Everything is censored again
+    /// when the censorship rules changed.
+    pub fn refresh(&mut self, room: &dyn Room) {
+        self.roster = room.roster();
+        if room.censorship_epoch() != self.epoch {
+            self.epoch = room.censorship_epoch();
+            self.views.clear();
+        }
+
+        let kept = room.messages_after(0);
+        let new_messages = room.messages_after(self.last_id);
+        for (country, view) in &mut self.views {
+            let dropped =
+                view.partition_point(|msg| kept.first().is_none_or(|first| msg.id < first.id));
+            view.drain(..dropped);
+            view.extend(
+                new_messages
+                    .iter()
+                    .map(|msg| room.censor_message_for(msg, country)),
+            );
+        }
+
+        // Countries seen for the first time get the whole kept history
+        let countries = room
+            .filter_config()
+            .banned_words
+            .keys()
+            .chain(self.roster.participants.iter().map(|p| &p.country))
+            .cloned()
+            .chain([CountryCode::new()]);
+        for country in countries {
+            self.views.entry(country).or_insert_with_key(|country| {
+                kept.iter()
+                    .map(|msg| room.censor_message_for(msg, country))
+                    .collect()
+            });
+        }
+        self.last_id = kept.last().map_or(self.last_id, |msg| msg.id);
+    }
+
+    /// Who was in the room at the last refresh.
+    pub fn roster(&self) -> &RoomRoster {
+        &self.roster
+    }
+
+    /// Country a participant is seated in, or no country if they are not
+    /// seated.
+    pub fn country_of(&self, user_id: &UserId) -> CountryCode {
+        self.roster
+            .participants
+            .iter()
+            .find(|p| &p.user_id == user_id)
+            .map(|p| p.country.clone())
+            .unwrap_or_default()
+    }
+
+    /// The kept messages with ids greater than `id`, censored for `country`,
+    /// including those a firewall drops. A country with neither rules nor a
+    /// seat in the room sees what spectators see.
+    pub fn messages_after(&self, country: &CountryCode, id: MessageId) -> &[CensoredMessage] {
+        let view = self
+            .views
+            .get(country)
+            .or_else(|| self.views.get(""))
+            .map_or(&[][..], Vec::as_slice);
+        &view[view.partition_point(|msg| msg.id <= id)..]
+    }
+
+    /// The room state censored for `country`.
+    pub fn state_for(&self, country: &CountryCode) -> RoomState {
+        RoomState {
+            room_id: self.room_id.clone(),
+            roster: self.roster.clone(),
+            recent_messages: self
+                .messages_after(country, 0)
+                .iter()
+                // Messages dropped by a firewall are hidden from the viewer entirely
+                .filter(|msg| !msg.hidden)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::WordPack;
+    use crate::room::ChatRoom;
+
+    fn make_room(settings: &RoomSettings) -> ChatRoom {
+        let mut banned_words = HashMap::new();
+        banned_words.insert("E".to_string(), vec!["hello".to_string()]);
+        let config = FilterConfig {
+            banned_words,
+            ..Default::default()
+        };
+        let mut room = ChatRoom::generate(
+            "test_room".to_string(),
+            &config,
+            &WordPack::builtin(),
+            settings,
+        )
+        .unwrap();
+        room.add_participant("frank".to_string(), "F".to_string());
+        room.skip_lobby();
+        room
+    }
+
+    fn send(room: &mut ChatRoom, content: &str) -> MessageId {
+        let (message, _) = room
+            .process_action(
+                &"frank".to_string(),
+                &"F".to_string(),
+                UserAction::SendMessage(content.into()),
+            )
+            .unwrap();
+        message.unwrap().id
+    }
+
+    fn contents(views: &CensoredViews, country: &str) -> Vec<String> {
+        views
+            .messages_after(&country.to_string(), 1)
+            .iter()
+            .map(|msg| msg.content.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_refresh_censors_new_messages_for_each_country() {
+        let mut room = make_room(&RoomSettings::default());
+        let mut views = CensoredViews::new(&room);
+        assert!(contents(&views, "E").is_empty());
+
+        let id = send(&mut room, "hello");
+        views.refresh(&room);
+        assert_eq!(contents(&views, "E"), vec!["***"]);
+        assert_eq!(contents(&views, ""), vec!["hello"]);
+        // Countries without a view of their own see what spectators see
+        assert_eq!(contents(&views, "F"), vec!["hello"]);
+        assert!(views.messages_after(&"E".to_string(), id).is_empty());
+    }
+
+    #[test]
+    fn test_refresh_censors_again_when_rules_change() {
+        let mut room = make_room(&RoomSettings::default());
+        send(&mut room, "hello");
+        let mut views = CensoredViews::new(&room);
+        assert_eq!(contents(&views, "E"), vec!["***"]);
+
+        // Winning unlocks every country, including for earlier messages
+        room.win();
+        views.refresh(&room);
+        let contents = contents(&views, "E");
+        assert_eq!(contents[0], "hello");
+        assert_eq!(views.roster().phase, RoomPhase::Finished);
+    }
+
+    #[test]
+    fn test_refresh_forgets_messages_the_room_drops() {
+        let mut room = make_room(&RoomSettings {
+            history_cap: 2,
+            ..Default::default()
+        });
+        for _ in 0..3 {
+            send(&mut room, "hello");
+        }
+        let mut views = CensoredViews::new(&room);
+        room.drop_saved_messages(4);
+        send(&mut room, "hello");
+        views.refresh(&room);
+
+        let ids: Vec<MessageId> = views
+            .messages_after(&"E".to_string(), 0)
+            .iter()
+            .map(|msg| msg.id)
+            .collect();
+        assert_eq!(ids, vec![3, 4, 5]);
+        assert_eq!(views.state_for(&"E".to_string()).recent_messages.len(), 3);
+    }
+}
@@ -1,40 +1,308 @@
+use rand::SeedableRng;
 use rand::prelude::IndexedRandom;
 use rand::seq::SliceRandom;
-use serde::Deserialize;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
+use std::fmt;
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize)]
+use crate::data::{CountryCode, GameSeed};
+
+#[derive(Clone, Debug, Deserialize)]
 pub struct Words {
     pub normal: Vec<String>,
     pub censored: Vec<Vec<String>>,
 }
 
-pub fn load_words(path: &str) -> Words {
-    let data = fs::read_to_string(path).expect("Failed to read words.json");
-    serde_json::from_str(&data).expect("Failed to parse words.json")
+/// Number of countries in a game when none is requested.
+pub const DEFAULT_COUNTRY_COUNT: usize = 4;
+
+/// Largest number of countries a game can have, one per letter code.
+pub const MAX_COUNTRIES: usize = 26;
+
+/// Shape of a generated puzzle.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct GenerationParams {
+    /// Number of countries, coded "A", "B", ...
+    pub country_count: usize,
+    /// Number of words banned in each country.
+    pub bans_per_country: usize,
+    /// Whether two countries may ban the same word.
+    pub allow_overlap: bool,
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self {
+            country_count: DEFAULT_COUNTRY_COUNT,
+            bans_per_country: 1,
+            allow_overlap: false,
+        }
+    }
+}
+
+impl GenerationParams {
+    /// Codes of the countries the puzzle is generated for.
+    pub fn country_codes(&self) -> Vec<CountryCode> {
+        ('A'..='Z')
+            .take(self.country_count)
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Why a puzzle could not be generated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GenerationError {
+    /// The game needs at least one country.
+    NoCountries,
+    /// More countries were requested than there are country codes.
+    TooManyCountries { requested: usize, max: usize },
+    /// Each country needs at least one banned word.
+    NoBans,
+    /// The word list has too few non-empty censored groups for the requested bans.
+    NotEnoughGroups { required: usize, available: usize },
 }
 
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoCountries => write!(f, "a game needs at least one country"),
+            Self::TooManyCountries { requested, max } => {
+                write!(
+                    f,
+                    "{requested} countries requested, at most {max} are supported"
+                )
+            }
+            Self::NoBans => write!(f, "each country needs at least one banned word"),
+            Self::NotEnoughGroups {
+                required,
+                available,
+            } => write!(
+                f,
+                "the word list needs {required} censored groups but only has {available}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/// Allowed words and the banned words of each country code.
+pub type GeneratedWords = (Vec<String>, HashMap<String, Vec<String>>);
+
 /// Returns (allowed_words, banned_map) where banned_map: country_code -> Vec<String>
+///
+/// The same seed, word list and parameters always produce the same result.
 pub fn generate_allowed_and_banned_words(
     words: &Words,
-    country_codes: &[&str],
-) -> (Vec<String>, HashMap<String, Vec<String>>) {
-    let mut rng = rand::rng();
+    params: &GenerationParams,
+    seed: GameSeed,
+) -> Result<GeneratedWords, GenerationError> {
+    if params.country_count == 0 {
+        return Err(GenerationError::NoCountries);
+    }
+    if params.country_count > MAX_COUNTRIES {
+        return Err(GenerationError::TooManyCountries {
+            requested: params.country_count,
+            max: MAX_COUNTRIES,
+        });
+    }
+    if params.bans_per_country == 0 {
+        return Err(GenerationError::NoBans);
+    }
+
+    let available = words.censored.iter().filter(|g| !g.is_empty()).count();
+    // Without overlap every country needs its own groups
+    let required = if params.allow_overlap {
+        Some(params.bans_per_country)
+    } else {
+        params.country_count.checked_mul(params.bans_per_country)
+    };
+    // A count too large to represent is more than any word list holds
+    let Some(required) = required else {
+        return Err(GenerationError::NotEnoughGroups {
+            required: usize::MAX,
+            available,
+        });
+    };
+    if available < required {
+        return Err(GenerationError::NotEnoughGroups {
+            required,
+            available,
+        });
+    }
+
+    // ChaCha8 is specified to give the same stream on every platform and release
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
     // 1. Pick one word from each censored group
     let mut complex_words: Vec<String> = words
         .censored
         .iter()
-        .map(|group| group.choose(&mut rng).unwrap().clone())
+        .filter_map(|group| group.choose(&mut rng).cloned())
         .collect();
     // 2. allowed_words = normal + complex_words
     let mut allowed_words = words.normal.clone();
     allowed_words.extend(complex_words.iter().cloned());
-    // 3. Pick 4 complex words for 4 countries as banned
+    // 3. Ban complex words in each country, disjoint unless overlap is allowed
     complex_words.shuffle(&mut rng);
     let mut banned_map = HashMap::new();
-    for (i, &country) in country_codes.iter().enumerate().take(4) {
-        banned_map.insert(country.to_string(), vec![complex_words[i].clone()]);
+    for (i, country) in params.country_codes().into_iter().enumerate() {
+        let banned = if params.allow_overlap {
+            complex_words
+                .choose_multiple(&mut rng, params.bans_per_country)
+                .cloned()
+                .collect()
+        } else {
+            let start = i * params.bans_per_country;
+            complex_words[start..start + params.bans_per_country].to_vec()
+        };
+        banned_map.insert(country, banned);
+    }
+    Ok((allowed_words, banned_map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_words() -> Words {
+        Words {
+            normal: vec!["you".to_string(), "me".to_string()],
+            censored: (0..6)
+                .map(|i| vec![format!("left{i}"), format!("right{i}")])
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_words() {
+        let words = make_words();
+        let params = GenerationParams::default();
+
+        let first = generate_allowed_and_banned_words(&words, &params, 42);
+        let second = generate_allowed_and_banned_words(&words, &params, 42);
+        assert!(first.is_ok());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_seeds_vary_words() {
+        let words = make_words();
+        let params = GenerationParams::default();
+
+        let first = generate_allowed_and_banned_words(&words, &params, 1);
+        let differs =
+            (2..20).any(|seed| generate_allowed_and_banned_words(&words, &params, seed) != first);
+        assert!(differs);
+    }
+
+    #[test]
+    fn test_countries_and_bans_per_country() {
+        let words = make_words();
+        let params = GenerationParams {
+            country_count: 3,
+            bans_per_country: 2,
+            allow_overlap: false,
+        };
+
+        let (allowed, banned) = generate_allowed_and_banned_words(&words, &params, 7).unwrap();
+        let mut countries: Vec<_> = banned.keys().cloned().collect();
+        countries.sort();
+        assert_eq!(countries, vec!["A", "B", "C"]);
+
+        // Every word is banned once, and all banned words are allowed to be sent
+        let mut all_banned: Vec<_> = banned.values().flatten().collect();
+        assert!(banned.values().all(|words| words.len() == 2));
+        all_banned.sort();
+        all_banned.dedup();
+        assert_eq!(all_banned.len(), 6);
+        assert!(all_banned.iter().all(|w| allowed.contains(w)));
+    }
+
+    #[test]
+    fn test_overlap_allows_more_bans_than_groups() {
+        let words = make_words();
+        let params = GenerationParams {
+            country_count: 5,
+            bans_per_country: 4,
+            allow_overlap: true,
+        };
+
+        let (_, banned) = generate_allowed_and_banned_words(&words, &params, 7).unwrap();
+        assert_eq!(banned.len(), 5);
+        for words in banned.values() {
+            let mut unique = words.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_invalid_params_are_errors() {
+        let words = make_words();
+        let generate = |country_count, bans_per_country, allow_overlap| {
+            let params = GenerationParams {
+                country_count,
+                bans_per_country,
+                allow_overlap,
+            };
+            generate_allowed_and_banned_words(&words, &params, 0).map(|_| ())
+        };
+
+        assert_eq!(generate(0, 1, false), Err(GenerationError::NoCountries));
+        assert_eq!(
+            generate(27, 1, true),
+            Err(GenerationError::TooManyCountries {
+                requested: 27,
+                max: MAX_COUNTRIES
+            })
+        );
+        assert_eq!(generate(2, 0, false), Err(GenerationError::NoBans));
+        assert_eq!(
+            generate(4, 2, false),
+            Err(GenerationError::NotEnoughGroups {
+                required: 8,
+                available: 6
+            })
+        );
+        assert_eq!(
+            generate(4, 7, true),
+            Err(GenerationError::NotEnoughGroups {
+                required: 7,
+                available: 6
+            })
+        );
+        for allow_overlap in [false, true] {
+            assert_eq!(
+                generate(4, usize::MAX, allow_overlap),
+                Err(GenerationError::NotEnoughGroups {
+                    required: usize::MAX,
+                    available: 6
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_groups_are_skipped() {
+        let mut words = make_words();
+        words.censored.push(vec![]);
+        let params = GenerationParams {
+            country_count: 7,
+            bans_per_country: 1,
+            allow_overlap: false,
+        };
+
+        assert_eq!(
+            generate_allowed_and_banned_words(&words, &params, 0).map(|_| ()),
+            Err(GenerationError::NotEnoughGroups {
+                required: 7,
+                available: 6
+            })
+        );
     }
-    (allowed_words, banned_map)
 }
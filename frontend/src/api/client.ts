@@ -25,12 +25,17 @@ export function getApiUrl(endpoint: string): string {
 }
 
 /**
- * Get WebSocket URL for a given room and token
+ * Get WebSocket URL for a given room and connect ticket
  * @param roomId - Room ID to connect to
- * @param token - Authentication token
+ * @param ticket - Single-use ticket from POST /rooms/{id}/ticket
+ * @param lastMessageId - Last message received, to get the ones missed since
  * @returns WebSocket URL string
  */
-export function getWebSocketUrl(roomId: string, token: string): string {
+export function getWebSocketUrl(
+  roomId: string,
+  ticket: string,
+  lastMessageId?: number,
+): string {
   // Convert HTTP(S) URL to WS(S) URL
   let wsBase: string;
 
@@ -45,7 +50,10 @@ export function getWebSocketUrl(roomId: string, token: string): string {
   }
 
   const base = wsBase.endsWith("/") ? wsBase.slice(0, -1) : wsBase;
-  return `${base}/rooms/${roomId}/connect?token=${token}`;
+  const url = `${base}/rooms/${roomId}/connect?ticket=${ticket}`;
+  return lastMessageId === undefined
+    ? url
+    : `${url}&last_message_id=${lastMessageId}`;
 }
 
 /**
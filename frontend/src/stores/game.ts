@@ -5,15 +5,27 @@ import { apiClient, getWebSocketUrl } from "@/api/client";
 import type {
   CensoredMessage,
   ConnectionState,
+  MessagePage,
+  RoomRoster,
   RoomUpdate,
   RoomWordsInfo,
+  SyncRequest,
   UserAction,
 } from "@/types/websocket";
 
 interface LoginResponse {
   token: string;
+  expires_at: number;
 }
 
+interface ConnectTicketResponse {
+  ticket: string;
+  expires_at: number;
+}
+
+const RECONNECT_RETRIES = 3;
+const RECONNECT_DELAY_MS = 1000;
+
 const TEST_ROOM_ID = "test_room";
 
 export const useGameStore = defineStore("game", () => {
@@ -25,14 +37,23 @@ export const useGameStore = defineStore("game", () => {
   const playerName = ref("");
   const playerToken = ref("");
   const currentRoomId = ref("");
-  const roomState = ref<RoomUpdate["room_state"] | null>(null);
+  const roomState = ref<RoomRoster | null>(null);
   const notifications = ref<string[]>([]);
   const allowedWords = ref<string[]>([]);
   const bannedWords = ref<Record<string, string[]>>({});
   const victoryState = ref<RoomUpdate["victory"] | null>(null);
+  // Snapshots hold only the newest messages; older ones are paged in from
+  // the history endpoint, before this message
+  const olderCursor = ref<number | null>(null);
 
   // WebSocket instance (will be set in connect)
   let ws: ReturnType<typeof useWebSocket> | null = null;
+  // Tickets are single-use, so every (re)connect needs a new URL
+  const wsUrl = ref<string | undefined>(undefined);
+  let reconnectAttempts = 0;
+  // Last message received from the server; notifications shown as messages
+  // have made-up ids, so they are not counted
+  let lastMessageId: number | undefined = undefined;
 
   function generateRoomId() {
     return Math.random().toString(36).substring(2, 10);
@@ -54,11 +75,32 @@ export const useGameStore = defineStore("game", () => {
     }
   }
 
-  function connect(roomId: string, token: string) {
-    console.log("[WebSocket] connect() called", {
-      roomId,
-      token: token.substring(0, 10) + "...",
-    });
+  async function fetchConnectTicket(
+    roomId: string,
+    token: string,
+  ): Promise<string> {
+    const data = await apiClient.post<ConnectTicketResponse>(
+      `/rooms/${roomId}/ticket`,
+      undefined,
+      { "X-User-Token": token },
+    );
+    return data.ticket;
+  }
+
+  // Point the socket at a URL with a fresh ticket; useWebSocket reopens on change
+  async function openWithNewTicket(roomId: string, token: string) {
+    try {
+      const ticket = await fetchConnectTicket(roomId, token);
+      // Determine WebSocket URL using VITE_BACKEND_URL if configured
+      wsUrl.value = getWebSocketUrl(roomId, ticket, lastMessageId);
+    } catch (err) {
+      console.error("[WebSocket] Failed to get a connect ticket:", err);
+      connectionState.value = "error";
+    }
+  }
+
+  async function connect(roomId: string, token: string) {
+    console.log("[WebSocket] connect() called", { roomId });
 
     currentRoomId.value = roomId;
     if (ws) {
@@ -68,31 +110,34 @@ export const useGameStore = defineStore("game", () => {
 
     connectionState.value = "connecting";
     console.log("[WebSocket] State: connecting");
+    reconnectAttempts = 0;
+    wsUrl.value = undefined;
+    lastMessageId = undefined;
 
     fetchRoomWordsInfo(roomId);
 
-    // Determine WebSocket URL using VITE_BACKEND_URL if configured
-    const wsUrl = getWebSocketUrl(roomId, token);
-    console.log("[WebSocket] Connecting to:", wsUrl);
-
     ws = useWebSocket(wsUrl, {
-      autoReconnect: {
-        retries: 3,
-        delay: 1000,
-        onFailed() {
-          console.error("[WebSocket] Auto-reconnect failed after 3 retries");
-          connectionState.value = "error";
-        },
-      },
       onConnected() {
         console.log("[WebSocket] ✅ Connected successfully!");
         connected.value = true;
         connectionState.value = "connected";
+        reconnectAttempts = 0;
       },
       onDisconnected() {
         console.log("[WebSocket] ❌ Disconnected");
         connected.value = false;
         connectionState.value = "disconnected";
+        // ws is cleared when the player leaves on purpose
+        if (!ws || currentRoomId.value !== roomId) return;
+        if (reconnectAttempts >= RECONNECT_RETRIES) {
+          console.error(
+            `[WebSocket] Reconnect failed after ${RECONNECT_RETRIES} retries`,
+          );
+          connectionState.value = "error";
+          return;
+        }
+        reconnectAttempts += 1;
+        setTimeout(() => openWithNewTicket(roomId, token), RECONNECT_DELAY_MS);
       },
       onError(_ws, event) {
         console.error("[WebSocket] ❌ Error:", event);
@@ -103,18 +148,30 @@ export const useGameStore = defineStore("game", () => {
         try {
           const data = JSON.parse(event.data) as RoomUpdate;
           console.log("[WebSocket] Parsed data:", data);
-          roomState.value = data.room_state;
-
-          // Initialize messages from room state if this is the first update
-          if (
-            messages.value.length === 0 &&
-            data.room_state.recent_messages.length > 0
-          ) {
-            messages.value = [...data.room_state.recent_messages];
+          if (data.snapshot) {
+            // A snapshot replaces everything we hold
+            const { phase, host, ready, participants, recent_messages } =
+              data.snapshot;
+            roomState.value = { phase, host, ready, participants };
+            messages.value = [...recent_messages];
+            olderCursor.value = recent_messages[0]?.id ?? null;
+            lastMessageId = undefined;
+          } else {
+            if (data.roster) roomState.value = data.roster;
+            messages.value.push(...data.new_messages);
           }
 
-          // Add new messages
-          messages.value.push(...data.new_messages);
+          const received = data.snapshot
+            ? data.snapshot.recent_messages
+            : data.new_messages;
+          for (const message of received) {
+            lastMessageId = Math.max(lastMessageId ?? 0, message.id);
+          }
+          // Tell the server what we have so it keeps sending only what's new
+          if (received.length > 0 && lastMessageId !== undefined) {
+            const ack: SyncRequest = { ack: lastMessageId };
+            ws?.send(JSON.stringify(ack));
+          }
 
           // Convert notifications to system messages
           if (data.notifications && data.notifications.length > 0) {
@@ -130,10 +187,28 @@ export const useGameStore = defineStore("game", () => {
                 sender_id: "SYSTEM",
                 content: n.message,
                 was_censored: false,
+                hidden: false,
               }));
             messages.value.push(...notificationMessages);
           }
 
+          // Refused actions are shown to this player only
+          if (data.errors && data.errors.length > 0) {
+            const lastMessageId =
+              messages.value.length > 0
+                ? Math.max(...messages.value.map((m) => m.id))
+                : 0;
+            messages.value.push(
+              ...data.errors.map((error, index) => ({
+                id: lastMessageId + index + 1,
+                sender_id: "SYSTEM",
+                content: `⚠️ ${error}`,
+                was_censored: false,
+                hidden: false,
+              })),
+            );
+          }
+
           // Check for victory
           if (data.victory) {
             victoryState.value = data.victory;
@@ -153,6 +228,25 @@ export const useGameStore = defineStore("game", () => {
     });
 
     console.log("[WebSocket] WebSocket instance created");
+    await openWithNewTicket(roomId, token);
+  }
+
+  async function loadOlderMessages() {
+    const before = olderCursor.value;
+    if (before === null || !currentRoomId.value) return;
+
+    try {
+      const page = await apiClient.get<MessagePage>(
+        `/rooms/${currentRoomId.value}/messages?before=${before}`,
+        { "X-User-Token": playerToken.value },
+      );
+      // A snapshot may have replaced the messages in the meantime
+      if (olderCursor.value !== before) return;
+      messages.value = [...page.messages, ...messages.value];
+      olderCursor.value = page.older;
+    } catch (err) {
+      console.error("[Store] Failed to load older messages:", err);
+    }
   }
 
   function sendMessage(content: string) {
@@ -168,6 +262,36 @@ export const useGameStore = defineStore("game", () => {
     ws.send(payload);
   }
 
+  // Seat changes are only accepted while the room is in the lobby
+  function chooseCountry(country: string) {
+    if (!ws || !ws.send) return;
+
+    const action: UserAction = { system: { choose_country: country } };
+    ws.send(JSON.stringify(action));
+  }
+
+  function requestSwap(userId: string) {
+    if (!ws || !ws.send) return;
+
+    const action: UserAction = { system: { request_swap: userId } };
+    ws.send(JSON.stringify(action));
+  }
+
+  function setReady(ready: boolean) {
+    if (!ws || !ws.send) return;
+
+    const action: UserAction = { system: { set_ready: ready } };
+    ws.send(JSON.stringify(action));
+  }
+
+  // Only the host can start, once every player is ready
+  function startGame() {
+    if (!ws || !ws.send) return;
+
+    const action: UserAction = { system: { start_game: null } };
+    ws.send(JSON.stringify(action));
+  }
+
   function leaveRoom() {
     if (!ws || !ws.send) return;
 
@@ -175,6 +299,7 @@ export const useGameStore = defineStore("game", () => {
     ws.send(JSON.stringify(action));
     ws.close();
     ws = null;
+    wsUrl.value = undefined;
     connected.value = false;
     connectionState.value = "idle";
     victoryState.value = null; // Clear victory state when leaving
@@ -185,9 +310,12 @@ export const useGameStore = defineStore("game", () => {
       ws.close();
       ws = null;
     }
+    wsUrl.value = undefined;
+    lastMessageId = undefined;
     connected.value = false;
     connectionState.value = "idle";
     messages.value = [];
+    olderCursor.value = null;
     roomState.value = null;
     notifications.value = [];
     allowedWords.value = [];
@@ -277,8 +405,14 @@ export const useGameStore = defineStore("game", () => {
     allowedWords,
     bannedWords,
     victoryState,
+    olderCursor,
     connect,
+    loadOlderMessages,
     sendMessage,
+    chooseCountry,
+    requestSwap,
+    setReady,
+    startGame,
     leaveRoom,
     cleanup,
     setPlayerInfo,
@@ -35,6 +35,8 @@ export interface CensoredMessage {
   sender_id: UserId;
   content: string;
   was_censored: boolean;
+  /** Dropped by the viewer's firewall; such messages are never sent to clients. */
+  hidden: boolean;
   timestamp?: Timestamp;
 }
 
@@ -42,11 +44,22 @@ export interface Participant {
   user_id: UserId;
   country: CountryCode;
   joined_at: Timestamp;
+  // Set while the player's connection is lost; they keep their seat for a while
+  disconnected_at: Timestamp | null;
 }
 
-export interface RoomState {
-  room_id: RoomId;
+export type RoomPhase = "lobby" | "in_progress" | "finished" | "closed";
+
+// Who is in the room; the room state apart from its messages
+export interface RoomRoster {
+  phase: RoomPhase;
+  host: UserId | null;
+  ready: UserId[];
   participants: Participant[];
+}
+
+export interface RoomState extends RoomRoster {
+  room_id: RoomId;
   recent_messages: CensoredMessage[];
 }
 
@@ -68,12 +81,19 @@ export interface VictoryState {
   unlocked_at: number | null;
 }
 
+// Apart from snapshots, an update carries only what changed since the last one
 export interface RoomUpdate {
-  room_state: RoomState;
+  version: number;
+  // Replaces everything the client holds
+  snapshot: RoomState | null;
+  // Set when the roster changed
+  roster: RoomRoster | null;
   new_messages: CensoredMessage[];
   notifications: Notification[];
   room_closed: boolean;
   victory: VictoryState | null;
+  // Why this player's own actions were refused
+  errors: string[];
 }
 
 // UserAction - matches backend UserAction enum (snake_case)
@@ -81,7 +101,12 @@ export interface RoomUpdate {
 export type SystemAction =
   | { send_message: string }
   | { send_message_array: string[] }
-  | { leave_room: null };
+  | { join_room: null }
+  | { leave_room: null }
+  | { choose_country: CountryCode }
+  | { request_swap: UserId }
+  | { set_ready: boolean }
+  | { start_game: null };
 
 // Game-specific actions delegated to GameEngine/GameRules
 export type GameAction = { submit_notes: Record<string, string[]> };
@@ -89,6 +114,18 @@ export type GameAction = { submit_notes: Record<string, string[]> };
 // Transport layer envelope for user actions
 export type UserAction = { system: SystemAction } | { game: GameAction };
 
+// A page of history - from /api/rooms/{id}/messages
+export interface MessagePage {
+  messages: CensoredMessage[];
+  // Pass as `before` for the older page
+  older: MessageId | null;
+  // Pass as `after` for the newer page
+  newer: MessageId | null;
+}
+
+// Sync requests, answered by the socket rather than the room
+export type SyncRequest = { ack: MessageId } | "snapshot";
+
 // Connection states
 export type ConnectionState =
   | "idle"